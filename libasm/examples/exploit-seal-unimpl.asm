@@ -4,7 +4,7 @@ _start:
         ; because the root allocator capability is sealed.
 	loadi a2, SYS_ALLOC_INIT
 	loadi a3, 1 ; bump
-	loadi a4, 255 ; all
+	loadi a4, 15 ; all
 	cpy a5, z0
 	syscall
 
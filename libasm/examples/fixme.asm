@@ -1,3 +1,3 @@
 loadi t0, 23
-; TODOO: the error is foo, not the args!!
+; the error is foo, not the args
 foo t0, 25
@@ -0,0 +1,12 @@
+; UGran::MAX + 1 raises an exception instead of wrapping
+
+loadi t0, 1
+sub t1, zero, t0
+; t1 contains UGran::MAX
+add t2, t1, t0
+; t2 contains 0
+addovf t3, t1, t0
+; unreachable
+
+loadi a2, SYS_EXIT
+syscall
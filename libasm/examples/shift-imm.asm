@@ -0,0 +1,17 @@
+; shift amounts wrap around at UGRAN_BITS
+
+loadi t0, 1
+slli t1, t0, 129
+; t1 contains 2
+
+loadi t0, 256
+srli t2, t0, 132
+; t2 contains 16
+
+loadi t0, 8
+sub t0, zero, t0
+srai t3, t0, 129
+; t3 contains -4
+
+loadi a2, SYS_EXIT
+syscall
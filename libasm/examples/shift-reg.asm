@@ -0,0 +1,24 @@
+; shift amounts wrap around at UGRAN_BITS
+
+loadi t5, 128
+loadi t6, 129
+
+loadi t0, 3
+sll t1, t0, t5
+; t1 contains 3
+sll t2, t0, t6
+; t2 contains 6
+
+loadi t0, 12
+srl t3, t0, t6
+; t3 contains 6
+srl t3, t3, t6
+; t3 contains 3
+
+loadi t0, 6
+sub t0, zero, t0
+sra t4, t0, t6
+; t4 contains -3
+
+loadi a2, SYS_EXIT
+syscall
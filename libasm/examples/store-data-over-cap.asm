@@ -0,0 +1,20 @@
+_start:
+	; sp.addr -= UGRAN_SIZE
+	loadi t0, UGRAN_SIZE
+	cgetaddr t1, sp
+	sub t1, t1, t0
+	csetaddr sp, t1
+
+	; store a capability, then read it back
+	storec sp, sp
+	loadc t2, sp
+	cgetvalid t4, t2
+
+	; overwrite the capability with a full granule of data
+	store64 sp, t0
+	loadc t2, sp
+	cgetvalid t3, t2
+
+exit:
+	loadi a2, SYS_EXIT
+	syscall
@@ -0,0 +1,95 @@
+use core::ops::Range;
+
+use fruticose_vm::abi::Ty;
+use fruticose_vm::int::{addr_sign, UAddr, UGran};
+use fruticose_vm::op::{Op, OpKind, OperandRole};
+use fruticose_vm::registers::Register;
+
+/// Run of operations which is only entered at its first operation and only
+/// left after its last.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    /// Indices of the operations in the block.
+    pub ops: Range<usize>,
+    /// Indices of the blocks which may execute next, in ascending order.
+    /// Targets of `jalr` aren't known statically, so aren't included.
+    pub succs: Vec<usize>,
+}
+
+/// Split `ops` into basic blocks, in program order.
+///
+/// A new block starts at the first operation, at every branch or jump target,
+/// and after every branch or jump. Jumps which store a return address are
+/// treated as calls, so they also flow to the following operation.
+pub fn basic_blocks(ops: &[Op]) -> Vec<Block> {
+    let mut leaders = vec![false; ops.len()];
+    if let Some(first) = leaders.first_mut() {
+        *first = true;
+    }
+    for (idx, op) in ops.iter().enumerate() {
+        if let Some(target) = target(ops, idx, op) {
+            leaders[target] = true;
+        }
+        if (op.kind.is_branch() || op.kind.is_jump()) && idx + 1 < ops.len() {
+            leaders[idx + 1] = true;
+        }
+    }
+
+    // index of the block each leader starts
+    let starts: Vec<usize> = (0..ops.len()).filter(|idx| leaders[*idx]).collect();
+    let block_of = |idx: usize| starts.partition_point(|start| *start <= idx) - 1;
+
+    let mut blocks = Vec::with_capacity(starts.len());
+    for (block, start) in starts.iter().copied().enumerate() {
+        let endb = starts.get(block + 1).copied().unwrap_or(ops.len());
+        let last_idx = endb - 1;
+        let last = &ops[last_idx];
+
+        let mut succs = Vec::new();
+        if let Some(target) = target(ops, last_idx, last) {
+            succs.push(block_of(target));
+        }
+        let falls_through = match last.kind {
+            kind if kind.is_branch() => true,
+            kind if kind.is_jump() => links(last),
+            _ => true,
+        };
+        if falls_through && endb < ops.len() {
+            succs.push(block + 1);
+        }
+        succs.sort_unstable();
+        succs.dedup();
+
+        blocks.push(Block {
+            ops: start..endb,
+            succs,
+        });
+    }
+    blocks
+}
+
+/// Index of the operation which the branch or `jal` at `idx` may jump to, if it
+/// lands on an operation.
+fn target(ops: &[Op], idx: usize, op: &Op) -> Option<usize> {
+    if !op.kind.is_branch() && op.kind != OpKind::Jal {
+        return None;
+    }
+    let pos = op
+        .kind
+        .operand_roles()
+        .iter()
+        .position(|role| *role == Some(OperandRole::Offset))?;
+    let offset = [op.op1, op.op2, op.op3][pos];
+    let offset = isize::from(addr_sign(offset.to_ugran() as UAddr));
+    let stride = isize::try_from(Op::LAYOUT.size).ok()?;
+    if offset % stride != 0 {
+        return None;
+    }
+    let target = idx.checked_add_signed(offset / stride)?;
+    (target < ops.len()).then_some(target)
+}
+
+/// Whether the jump at `op` stores a return address.
+fn links(op: &Op) -> bool {
+    op.op1.to_ugran() != Register::Zero as UGran
+}
@@ -1,16 +1,18 @@
 use core::fmt;
 
-use crate::lex::TokenTyp;
-use crate::parse1::{OperandType, TokenClass};
+use crate::lex::{IntTy, TokenTyp};
+use crate::parse1::{OperandType, PseudoOp, TokenClass};
 
 impl fmt::Display for TokenTyp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let class = self.classify();
         match self {
             Self::Op(op) => write!(f, "{class} {op}", class = class.unwrap()),
+            Self::Pseudo(pseudo) => write!(f, "{class} {pseudo}", class = class.unwrap()),
             Self::Register(reg) => write!(f, "{class} {reg}", class = class.unwrap()),
             Self::Syscall(syscall) => write!(f, "{class} {syscall}", class = class.unwrap()),
             Self::UnsignedInt(_) => write!(f, "{class} number", class = class.unwrap()),
+            Self::TypedInt(_, ty) => write!(f, "{class} {ty}", class = class.unwrap()),
             Self::Identifier => write!(f, "identifier"),
             Self::Comma => write!(f, "comma"),
             Self::Colon => write!(f, "colon"),
@@ -20,6 +22,19 @@ impl fmt::Display for TokenTyp {
     }
 }
 
+impl fmt::Display for IntTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.signed { 's' } else { 'u' };
+        write!(f, "{sign}{}", self.bits)
+    }
+}
+
+impl fmt::Display for PseudoOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.display())
+    }
+}
+
 impl fmt::Display for TokenClass {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
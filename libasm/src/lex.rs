@@ -1,4 +1,6 @@
-use fruticose_vm::int::{UAddr, UGran, UADDR_SIZE, UGRAN_SIZE};
+use fruticose_vm::abi::{self, Layout};
+use fruticose_vm::capability::{Permissions, TaggedCapability};
+use fruticose_vm::int::{gran_unsign, SGran, UAddr, UGran, UADDR_SIZE, UGRAN_BITS, UGRAN_SIZE};
 use fruticose_vm::op::OpKind;
 use fruticose_vm::registers::Register;
 use fruticose_vm::syscall::SyscallKind;
@@ -7,10 +9,12 @@ use unicode_segmentation::{GraphemeIndices, UnicodeSegmentation};
 use core::iter::Peekable;
 use core::num::{IntErrorKind, ParseIntError};
 
-use crate::parse1::OperandType;
+use crate::parse1::{OperandType, PseudoOp};
 use crate::Span;
 
 pub const COMMENT: &str = ";";
+/// Prefix of permission literals, such as `%r-x`.
+pub const PERMS_PREFIX: &str = "%";
 
 // TODO: warn about suspicious unicode characters
 
@@ -24,9 +28,13 @@ pub struct Token<'s> {
 pub enum TokenTyp {
     // require adjacent: whitespace or start/end of file
     Op(OpKind),
+    Pseudo(PseudoOp),
     Register(Register),
     Syscall(SyscallKind),
-    UnsignedInt(UGran), // TODO: support signed ints. also we should have a clear way to notate type of literal (eg, <number>_s for signed and <number>_u for unsigned)
+    UnsignedInt(UGran),
+    /// Literal with a type suffix, such as `42u8` or `-1s32`. Signed values
+    /// are sign extended to fill a granule.
+    TypedInt(UGran, IntTy),
     Identifier,
 
     // if seen, immediately yield
@@ -41,7 +49,9 @@ impl TokenTyp {
     pub const fn operand_type(self) -> Option<OperandType> {
         match self {
             Self::Register(_) => Some(OperandType::Register),
-            Self::Syscall(_) | Self::UnsignedInt(_) => Some(OperandType::Immediate),
+            Self::Syscall(_) | Self::UnsignedInt(_) | Self::TypedInt(..) => {
+                Some(OperandType::Immediate)
+            }
             Self::Identifier => Some(OperandType::Label),
             _ => None,
         }
@@ -58,6 +68,54 @@ pub struct LexErr<'s> {
 pub enum LexErrTyp {
     UnknownIdent,
     InvalidUnsignedInt(ParseIntError),
+    InvalidPermissions,
+    /// A typed literal (eg. `255u8`) doesn't fit in its type.
+    TypedIntOverflow {
+        ty: IntTy,
+    },
+}
+
+/// Intended type of a literal, given by its suffix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntTy {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl IntTy {
+    // NOTE: no type may be wider than a granule
+    const WIDTHS: [u32; 4] = [8, 16, 32, 64];
+
+    /// Parse a type suffix: `u` for unsigned, `s` or `i` for signed, then the
+    /// width in bits.
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        let signed = match suffix.get(..1)? {
+            "u" => false,
+            "s" | "i" => true,
+            _ => return None,
+        };
+        let bits = suffix[1..].parse().ok()?;
+        Self::WIDTHS
+            .contains(&bits)
+            .then_some(Self { signed, bits })
+    }
+
+    /// Check that `int` fits, returning it as it is stored in a granule.
+    fn fit(self, int: i128) -> Option<UGran> {
+        let range = if self.signed {
+            -(1 << (self.bits - 1))..1 << (self.bits - 1)
+        } else {
+            0..1 << self.bits
+        };
+        if !range.contains(&int) {
+            return None;
+        }
+        Some(if int < 0 {
+            gran_unsign(SGran::try_from(int).ok()?)
+        } else {
+            UGran::try_from(int).ok()?
+        })
+    }
 }
 
 pub struct Lexer<'s> {
@@ -94,6 +152,8 @@ impl<'s> Lexer<'s> {
     fn check_ctx(span: &'s str) -> Result<TokenTyp, LexErrTyp> {
         let typ = if let Some(op) = OpKind::from_str(span) {
             TokenTyp::Op(op)
+        } else if let Some(pseudo) = PseudoOp::from_str(span) {
+            TokenTyp::Pseudo(pseudo)
         } else if let Some(reg) = Register::from_str(span) {
             TokenTyp::Register(reg)
         } else {
@@ -106,27 +166,72 @@ impl<'s> Lexer<'s> {
                 "SYS_ALLOC_FREE" => TokenTyp::Syscall(SyscallKind::AllocFree),
                 "SYS_ALLOC_FREE_ALL" => TokenTyp::Syscall(SyscallKind::AllocFreeAll),
                 "SYS_ALLOC_STAT" => TokenTyp::Syscall(SyscallKind::AllocStat),
+                "SYS_CAP_INFO" => TokenTyp::Syscall(SyscallKind::CapInfo),
+                "SYS_LAYOUT_ARRAY" => TokenTyp::Syscall(SyscallKind::LayoutArray),
+                "SYS_MEM_SET" => TokenTyp::Syscall(SyscallKind::MemSet),
+                "SYS_MEM_CPY" => TokenTyp::Syscall(SyscallKind::MemCpy),
+                "SYS_ALLOC_STAT_INTO" => TokenTyp::Syscall(SyscallKind::AllocStatInto),
+                "SYS_ALLOC_REALLOC" => TokenTyp::Syscall(SyscallKind::AllocRealloc),
 
                 // helpful constants
                 "UGRAN_SIZE" => TokenTyp::UnsignedInt(UGRAN_SIZE.into()),
-                "UGRAN_BITS" => TokenTyp::UnsignedInt(UGran::BITS.into()),
+                "UGRAN_BITS" => TokenTyp::UnsignedInt(UGRAN_BITS.into()),
                 "UADDR_SIZE" => TokenTyp::UnsignedInt(UADDR_SIZE.into()),
                 "UADDR_BITS" => TokenTyp::UnsignedInt(UAddr::BITS.into()),
+                // NOTE: alignments are encoded as in a Layout (base 2 logarithm)
+                "LAYOUT_SIZE" => TokenTyp::UnsignedInt(abi::size_of::<Layout>().into()),
+                "LAYOUT_ALIGN" => TokenTyp::UnsignedInt(abi::align_of::<Layout>().repr().into()),
+                "CAP_SIZE" => TokenTyp::UnsignedInt(abi::size_of::<TaggedCapability>().into()),
+                "CAP_ALIGN" => {
+                    TokenTyp::UnsignedInt(abi::align_of::<TaggedCapability>().repr().into())
+                }
 
-                _ => match span.parse::<UGran>() {
-                    Ok(int) => TokenTyp::UnsignedInt(int),
-                    Err(err) => {
-                        if *err.kind() == IntErrorKind::PosOverflow {
-                            return Err(LexErrTyp::InvalidUnsignedInt(err));
+                _ if span.starts_with(PERMS_PREFIX) => {
+                    match Permissions::from_display(&span[PERMS_PREFIX.len()..]) {
+                        Some(perms) => TokenTyp::UnsignedInt(perms.bits().into()),
+                        None => return Err(LexErrTyp::InvalidPermissions),
+                    }
+                }
+
+                _ => {
+                    if let Some(typed) = Self::check_typed_int(span) {
+                        return typed;
+                    }
+                    match span.parse::<UGran>() {
+                        Ok(int) => TokenTyp::UnsignedInt(int),
+                        Err(err) => {
+                            if *err.kind() == IntErrorKind::PosOverflow {
+                                return Err(LexErrTyp::InvalidUnsignedInt(err));
+                            }
+                            TokenTyp::Identifier
                         }
-                        TokenTyp::Identifier
                     }
-                },
+                }
             }
         };
         Ok(typ)
     }
 
+    /// Lex a literal with a type suffix, such as `42u8` or `-1s32`, checking
+    /// that it fits. Returns [`None`] if `span` isn't a typed literal.
+    fn check_typed_int(span: &'s str) -> Option<Result<TokenTyp, LexErrTyp>> {
+        let magnitude = span.strip_prefix('-').unwrap_or(span);
+        let (digits, suffix) = magnitude.split_at(magnitude.find(|c: char| !c.is_ascii_digit())?);
+        let ty = IntTy::from_suffix(suffix)?;
+        if digits.is_empty() {
+            return None;
+        }
+        // every type fits in an i128, so failing to parse means overflow
+        let fit = span[..span.len() - suffix.len()]
+            .parse::<i128>()
+            .ok()
+            .and_then(|int| ty.fit(int));
+        Some(match fit {
+            Some(int) => Ok(TokenTyp::TypedInt(int, ty)),
+            None => Err(LexErrTyp::TypedIntOverflow { ty }),
+        })
+    }
+
     fn next_inner(&mut self) -> Option<<Self as Iterator>::Item> {
         /* skip into non-whitespace, yielding context-independent token if we
          * find one */
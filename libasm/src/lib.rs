@@ -1,18 +1,35 @@
 #![deny(elided_lifetimes_in_paths)]
 
+pub mod cfg;
 mod fmt;
 pub mod lex;
 pub mod parse1;
 pub mod parse2;
+pub mod source;
 
 #[cfg(test)]
 mod tests;
 
+use fruticose_vm::op::Op;
+
+use crate::parse1::ParseErr;
+use crate::parse2::Parser2;
+
+/// Assemble `src` into operations, or return every error encountered.
+pub fn assemble(src: &str) -> Result<Vec<Op>, Vec<ParseErr<'_>>> {
+    let (ops, errs) = Parser2::new(src).finish();
+    if errs.is_empty() {
+        Ok(ops)
+    } else {
+        Err(errs)
+    }
+}
+
 /// Rich representation of source text span.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Span<'s> {
-    /// Zero based line number where the span starts.
-    /// Spans cannot exceed a line.
+    /// Zero based line number where the span starts. The span may continue
+    /// onto following lines, see [`Span::lines`].
     pub line: usize,
 
     /// Zero based column index, in bytes, and starting from `line_start`, where
@@ -30,9 +47,61 @@ pub struct Span<'s> {
 }
 
 impl<'s> Span<'s> {
+    /// Absolute byte index of the start of the span.
+    pub const fn offset(&self) -> usize {
+        self.line_start + self.col_idx
+    }
+
+    /// Smallest span covering both `self` and `other`, including any text
+    /// between them. Both must be in the same source.
+    #[must_use]
+    pub fn merge(self, other: Self) -> Self {
+        assert!(
+            core::ptr::eq(self.src, other.src),
+            "spans must share source text"
+        );
+        let end = (self.offset() + self.len).max(other.offset() + other.len);
+        let first = if self.offset() <= other.offset() {
+            self
+        } else {
+            other
+        };
+        Self {
+            len: end - first.offset(),
+            ..first
+        }
+    }
+
+    /// Split the span into one span per line it covers. Each keeps its line
+    /// break, if any.
+    pub fn lines(self) -> Vec<Self> {
+        if self.len == 0 {
+            return vec![self];
+        }
+        let mut lines = Vec::new();
+        let (mut line_start, mut col_idx) = (self.line_start, self.col_idx);
+        for (idx, text) in self.get().split_inclusive('\n').enumerate() {
+            let line = Self {
+                line: self.line + idx,
+                col_idx,
+                len: text.len(),
+                line_start,
+                src: self.src,
+            };
+            line_start = line.offset() + line.len;
+            col_idx = 0;
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Zero based line number where the span ends.
+    pub fn end_line(&self) -> usize {
+        self.lines().last().unwrap().line
+    }
+
     pub fn get(&self) -> &'s str {
-        let start = self.line_start + self.col_idx;
-        &self.src[start..][..self.len]
+        &self.src[self.offset()..][..self.len]
     }
 
     pub fn get_line(&self) -> &'s str {
@@ -1,14 +1,14 @@
 use fruticose_vm::capability::TaggedCapability;
-use fruticose_vm::op::OpKind;
+use fruticose_vm::op::{OpKind, OperandRole};
+use fruticose_vm::registers::Register;
 
-use core::iter::Peekable;
+use std::collections::VecDeque;
 
-use crate::lex::{LexErrTyp, Lexer, Token, TokenTyp};
+use crate::lex::{LexErr, LexErrTyp, Lexer, Token, TokenTyp};
 use crate::Span;
 
-/* TODOO: if `next` last yielded Err, context of call stack is lost so parser
- * may for instance expect that an operand is actually the start of an
- * operation */
+/* NOTE: if `next` yields Err, the rest of the line is skipped so that the
+ * parser doesn't mistake a stray operand for the start of a statement */
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ParseErr<'s> {
@@ -41,7 +41,21 @@ pub enum ParseErrTyp<'s> {
         first_def: Span<'s>,
     },
     LabelUndef,
-    LabelOffsetOverflow,
+    /// An operand directly follows operand number `after_operand` (counting
+    /// from 1), without a comma between them.
+    MissingComma {
+        after_operand: u8,
+    },
+    /// A token other than a newline follows the operands of `op`.
+    TooManyOperands {
+        op: OpKind,
+        extra: TokenTyp,
+    },
+    /// The byte offset from the referencing operation to the label doesn't
+    /// fit in an address.
+    LabelOffsetOverflow {
+        label: Span<'s>,
+    },
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -56,16 +70,60 @@ pub enum TokenClass {
 impl TokenTyp {
     pub const fn classify(self) -> Option<TokenClass> {
         match self {
-            Self::Op(_) => Some(TokenClass::Op),
+            Self::Op(_) | Self::Pseudo(_) => Some(TokenClass::Op),
             Self::Register(_) => Some(TokenClass::Register),
             Self::Syscall(_) => Some(TokenClass::Syscall),
-            Self::UnsignedInt(_) => Some(TokenClass::Literal),
+            Self::UnsignedInt(_) | Self::TypedInt(..) => Some(TokenClass::Literal),
             Self::Identifier => Some(TokenClass::Identifier),
             Self::Comma | Self::Colon | Self::Newline | Self::Eof => None,
         }
     }
 }
 
+/// Operation which is lowered to a real [`OpKind`] by the assembler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PseudoOp {
+    /// `j label` jumps to `label`, lowered to `jal zero, label`.
+    J,
+
+    /// `jr reg` jumps to the address in register `reg`, lowered to
+    /// `jalr zero, reg, 0`.
+    Jr,
+
+    /// `li reg, imm` is an alias for `loadi reg, imm`.
+    Li,
+
+    /// `mv dst, src` is an alias for `cpy dst, src`.
+    Mv,
+
+    /// `nop` does nothing, lowered to `cpy zero, zero`.
+    Nop,
+}
+
+impl PseudoOp {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "j" => Some(Self::J),
+            "jr" => Some(Self::Jr),
+            "li" => Some(Self::Li),
+            "mv" => Some(Self::Mv),
+            "nop" => Some(Self::Nop),
+            _ => None,
+        }
+    }
+
+    pub const fn display(self) -> &'static str {
+        match self {
+            Self::J => "j",
+            Self::Jr => "jr",
+            Self::Li => "li",
+            Self::Mv => "mv",
+            Self::Nop => "nop",
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OperandType {
     Register,
@@ -123,97 +181,81 @@ pub enum Stmt<'s> {
 }
 
 pub const fn type_signature(op: OpKind) -> [Option<OperandType>; 3] {
-    const fn sig<const N: usize>(
-        op: OpKind,
-        sig: [OperandType; N],
-    ) -> [Option<OperandType>; OpKind::MAX_OPERANDS] {
-        assert!(N <= OpKind::MAX_OPERANDS);
-        if sig.len() != op.operand_count() as _ {
-            panic!("signature must have correct operand count");
-        }
-        let mut out = [None; 3];
-
-        let mut idx = 0;
-        while idx < sig.len() {
-            out[idx] = Some(sig[idx]);
-            idx += 1;
-        }
-        out
-    }
-
-    use OperandType::*;
-
-    match op {
-        OpKind::CGetValid => sig(op, [Register, Register]),
-        OpKind::CGetAddr => sig(op, [Register, Register]),
-        OpKind::CSetAddr => sig(op, [Register, Register]),
-        OpKind::CGetBound => sig(op, [Register, Register, Register]),
-        OpKind::CSetBound => sig(op, [Register, Register, Register]),
-        OpKind::CGetPerm => sig(op, [Register, Register]),
-        OpKind::CSetPerm => sig(op, [Register, Register]),
-        OpKind::CGetType => sig(op, [Register, Register]),
-        OpKind::CSeal => sig(op, [Register, Register, Register]),
-        OpKind::CUnseal => sig(op, [Register, Register, Register]),
-        OpKind::Cpy => sig(op, [Register, Register]),
-        OpKind::LoadI => sig(op, [Register, Immediate]),
-        OpKind::LoadU8 => sig(op, [Register, Register]),
-        OpKind::LoadU16 => sig(op, [Register, Register]),
-        OpKind::LoadU32 => sig(op, [Register, Register]),
-        OpKind::LoadU64 => sig(op, [Register, Register]),
-        OpKind::LoadC => sig(op, [Register, Register]),
-        OpKind::Store8 => sig(op, [Register, Register]),
-        OpKind::Store16 => sig(op, [Register, Register]),
-        OpKind::Store32 => sig(op, [Register, Register]),
-        OpKind::Store64 => sig(op, [Register, Register]),
-        OpKind::StoreC => sig(op, [Register, Register]),
-        OpKind::AddI => sig(op, [Register, Register, Immediate]),
-        OpKind::Add => sig(op, [Register, Register, Register]),
-        OpKind::Sub => sig(op, [Register, Register, Register]),
-        OpKind::SltsI => sig(op, [Register, Register, Immediate]),
-        OpKind::SltuI => sig(op, [Register, Register, Immediate]),
-        OpKind::Slts => sig(op, [Register, Register, Register]),
-        OpKind::Sltu => sig(op, [Register, Register, Register]),
-        OpKind::XorI => sig(op, [Register, Register, Immediate]),
-        OpKind::Xor => sig(op, [Register, Register, Register]),
-        OpKind::OrI => sig(op, [Register, Register, Immediate]),
-        OpKind::Or => sig(op, [Register, Register, Register]),
-        OpKind::AndI => sig(op, [Register, Register, Immediate]),
-        OpKind::And => sig(op, [Register, Register, Register]),
-        OpKind::SllI => sig(op, [Register, Register, Immediate]),
-        OpKind::Sll => sig(op, [Register, Register, Register]),
-        OpKind::SrlI => sig(op, [Register, Register, Immediate]),
-        OpKind::Srl => sig(op, [Register, Register, Register]),
-        OpKind::SraI => sig(op, [Register, Register, Immediate]),
-        OpKind::Sra => sig(op, [Register, Register, Register]),
-        OpKind::Jal => sig(op, [Register, Label]),
-        OpKind::Jalr => sig(op, [Register, Register, Label]),
-        OpKind::Beq => sig(op, [Register, Register, Label]),
-        OpKind::Bne => sig(op, [Register, Register, Label]),
-        OpKind::Blts => sig(op, [Register, Register, Label]),
-        OpKind::Bges => sig(op, [Register, Register, Label]),
-        OpKind::Bltu => sig(op, [Register, Register, Label]),
-        OpKind::Bgeu => sig(op, [Register, Register, Label]),
-        OpKind::Syscall => sig(op, []),
+    let roles = op.operand_roles();
+    let mut out = [None; OpKind::MAX_OPERANDS];
+
+    let mut idx = 0;
+    while idx < roles.len() {
+        out[idx] = match roles[idx] {
+            Some(OperandRole::Register) => Some(OperandType::Register),
+            Some(OperandRole::Immediate) => Some(OperandType::Immediate),
+            Some(OperandRole::Offset) => Some(OperandType::Label),
+            None => None,
+        };
+        idx += 1;
     }
+    out
 }
 
 pub struct Parser1<'s> {
-    lexer: Peekable<Lexer<'s>>,
+    lexer: Lexer<'s>,
+    // lexed but not yet consumed, oldest first. at most LOOKAHEAD long.
+    ahead: VecDeque<Result<Token<'s>, LexErr<'s>>>,
+    // whether the last consumed token ended a line
+    at_line_end: bool,
     op_idx: usize,
 }
 
 impl<'s> Parser1<'s> {
+    /// Number of tokens which may be peeked without consuming them.
+    const LOOKAHEAD: usize = 2;
+
     pub fn new(src: &'s str) -> Self {
         Self {
-            lexer: Lexer::new(src).peekable(),
+            lexer: Lexer::new(src),
+            ahead: VecDeque::with_capacity(Self::LOOKAHEAD),
+            at_line_end: true,
             op_idx: 0,
         }
     }
 }
 
 impl<'s> Parser1<'s> {
+    /// Peek at the `n`th token which hasn't been consumed, counting from 0.
+    fn peek_nth(&mut self, n: usize) -> Option<&Result<Token<'s>, LexErr<'s>>> {
+        debug_assert!(n < Self::LOOKAHEAD);
+        while self.ahead.len() <= n {
+            self.ahead.push_back(self.lexer.next()?);
+        }
+        self.ahead.get(n)
+    }
+
+    fn peek_typ(&mut self, n: usize) -> Option<TokenTyp> {
+        self.peek_nth(n)?.as_ref().ok().map(|tok| tok.typ)
+    }
+
+    fn next_token(&mut self) -> Option<Result<Token<'s>, LexErr<'s>>> {
+        let tok = self.ahead.pop_front().or_else(|| self.lexer.next());
+        self.at_line_end = matches!(
+            tok,
+            None | Some(Ok(Token {
+                typ: TokenTyp::Newline | TokenTyp::Eof,
+                ..
+            }))
+        );
+        tok
+    }
+
+    /// Discard tokens through the end of the current line, so that parsing
+    /// resumes at the start of the next statement after an error.
+    fn recover(&mut self) {
+        while !self.at_line_end {
+            self.next_token();
+        }
+    }
+
     fn expect_token(&mut self) -> Result<Token<'s>, ParseErr<'s>> {
-        match self.lexer.next() {
+        match self.next_token() {
             Some(Ok(tok)) => Ok(tok),
             Some(Err(lex_err)) => {
                 Err(ParseErr {
@@ -252,7 +294,14 @@ impl<'s> Parser1<'s> {
             span: try_operand.span,
         })?;
         if !last {
-            Self::expect_typ(TokenTyp::Comma, self.expect_token()?)?;
+            let sep = self.expect_token()?;
+            Self::expect_typ(TokenTyp::Comma, sep).map_err(|mut err| {
+                if sep.typ.operand_type().is_some() {
+                    // underline both operands which are missing a comma
+                    err.span = try_operand.span.merge(sep.span);
+                }
+                err
+            })?;
         }
         if operand_typ != expected_typ {
             return Err(ParseErr {
@@ -274,7 +323,7 @@ impl<'s> Parser1<'s> {
                     syscall as _,
                 )))
             }
-            TokenTyp::UnsignedInt(int) => {
+            TokenTyp::UnsignedInt(int) | TokenTyp::TypedInt(int, _) => {
                 Some(OperandVal::Known(TaggedCapability::from_ugran(int)))
             }
             TokenTyp::Identifier => Some(OperandVal::Ref(try_operand.span)),
@@ -309,19 +358,94 @@ impl<'s> Parser1<'s> {
         for arg in 0..argc {
             let last = arg + 1 == argc;
             let typ = type_signature(op_kind)[arg as usize].unwrap();
-            *args[arg as usize] = self.expect_operand(typ, last)?;
+            *args[arg as usize] = self
+                .expect_operand(typ, last)
+                .map_err(|err| match err.typ {
+                    ParseErrTyp::ExpectedTyp {
+                        expected: TokenTyp::Comma,
+                        found,
+                    } if found.operand_type().is_some() => ParseErr {
+                        typ: ParseErrTyp::MissingComma {
+                            after_operand: arg + 1,
+                        },
+                        span: err.span,
+                    },
+                    _ => err,
+                })?;
         }
 
-        // verify that operation ends with newline
-        Self::expect_typ(TokenTyp::Newline, self.expect_token()?)?;
+        self.expect_op_end(op_kind)?;
+        Ok(op)
+    }
 
+    /// Verify that the operation `op_kind` ends with its line.
+    fn expect_op_end(&mut self, op_kind: OpKind) -> Result<(), ParseErr<'s>> {
+        let end = self.expect_token()?;
+        if !matches!(end.typ, TokenTyp::Newline | TokenTyp::Eof) {
+            return Err(ParseErr {
+                typ: ParseErrTyp::TooManyOperands {
+                    op: op_kind,
+                    extra: end.typ,
+                },
+                span: end.span,
+            });
+        }
+        Ok(())
+    }
+
+    fn expect_pseudo(&mut self, pseudo: PseudoOp) -> Result<XOp<'s>, ParseErr<'s>> {
+        let zero = Operand {
+            typ: OperandType::Register,
+            val: Some(OperandVal::Known(TaggedCapability::from_ugran(
+                Register::Zero as _,
+            ))),
+        };
+        let op = match pseudo {
+            PseudoOp::J => XOp {
+                kind: OpKind::Jal,
+                op1: zero,
+                op2: self.expect_operand(OperandType::Label, true)?,
+                op3: Operand::UNUSED,
+            },
+            PseudoOp::Jr => XOp {
+                kind: OpKind::Jalr,
+                op1: zero,
+                op2: self.expect_operand(OperandType::Register, true)?,
+                op3: Operand {
+                    typ: OperandType::Label,
+                    val: Some(OperandVal::Known(TaggedCapability::from_ugran(0))),
+                },
+            },
+            PseudoOp::Nop => XOp {
+                kind: OpKind::Cpy,
+                op1: zero,
+                op2: zero,
+                op3: Operand::UNUSED,
+            },
+            // aliases check their own operands and newline
+            PseudoOp::Li => return self.expect_operation(OpKind::LoadI),
+            PseudoOp::Mv => return self.expect_operation(OpKind::Cpy),
+        };
+
+        self.expect_op_end(op.kind)?;
         Ok(op)
     }
 
     fn next_inner(&mut self) -> Result<Option<Stmt<'s>>, ParseErr<'s>> {
         // skip newlines and handle eof
         let try_start = loop {
-            if self.lexer.peek().is_none() {
+            // an identifier not followed by a colon can't be a label, so is
+            // most likely a misspelled operation
+            if self.peek_typ(0) == Some(TokenTyp::Identifier)
+                && self.peek_typ(1) != Some(TokenTyp::Colon)
+            {
+                let ident = self.expect_token()?;
+                return Err(ParseErr {
+                    typ: ParseErrTyp::Lex(LexErrTyp::UnknownIdent),
+                    span: ident.span,
+                });
+            }
+            if self.peek_nth(0).is_none() {
                 return Ok(None);
             }
             let tok = self.expect_token()?;
@@ -334,6 +458,7 @@ impl<'s> Parser1<'s> {
 
         let stmt = match try_start.typ {
             TokenTyp::Op(op_kind) => Stmt::Op(self.expect_operation(op_kind)?),
+            TokenTyp::Pseudo(pseudo) => Stmt::Op(self.expect_pseudo(pseudo)?),
             TokenTyp::Identifier => Stmt::Label(self.expect_label(try_start.span)?),
             found => {
                 return Err(ParseErr {
@@ -352,8 +477,10 @@ impl<'s> Iterator for Parser1<'s> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let item = self.next_inner().transpose();
-        if let Some(Ok(Stmt::Op(_))) = item {
-            self.op_idx += 1;
+        match item {
+            Some(Ok(Stmt::Op(_))) => self.op_idx += 1,
+            Some(Err(_)) => self.recover(),
+            _ => (),
         }
         item
     }
@@ -4,7 +4,7 @@ use fruticose_vm::int::{gran_unsign, SAddr};
 use fruticose_vm::op::Op;
 
 use core::iter::Enumerate;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::vec;
 
 use crate::parse1::{Label, OperandType, OperandVal, ParseErr, ParseErrTyp, Parser1, Stmt, XOp};
@@ -12,27 +12,28 @@ use crate::parse1::{Label, OperandType, OperandVal, ParseErr, ParseErrTyp, Parse
 pub struct Parser2<'s> {
     xops: Enumerate<vec::IntoIter<XOp<'s>>>,
     labels: HashMap<&'s str, Label<'s>>,
-    errs: Vec<ParseErr<'s>>,
+    // first pass errors, in source order
+    errs: VecDeque<ParseErr<'s>>,
 }
 
 impl<'s> Parser2<'s> {
     pub fn new(s: &'s str) -> Self {
         let mut xops: Vec<XOp<'_>> = Vec::new();
         let mut labels: HashMap<&str, Label<'_>> = HashMap::new();
-        let mut errs: Vec<ParseErr<'_>> = Vec::new();
+        let mut errs: VecDeque<ParseErr<'_>> = VecDeque::new();
 
         for stmt in Parser1::new(s) {
             match stmt {
                 Ok(Stmt::Op(xop)) => xops.push(xop),
                 Ok(Stmt::Label(label)) => {
                     if let Some(old) = labels.insert(label.id.get(), label) {
-                        errs.push(ParseErr {
+                        errs.push_back(ParseErr {
                             typ: ParseErrTyp::LabelRedef { first_def: old.id },
                             span: label.id,
                         });
                     }
                 }
-                Err(err) => errs.push(err),
+                Err(err) => errs.push_back(err),
             }
         }
 
@@ -43,6 +44,19 @@ impl<'s> Parser2<'s> {
         }
     }
 
+    /// Drain every remaining operation and error.
+    pub fn finish(self) -> (Vec<Op>, Vec<ParseErr<'s>>) {
+        let mut ops = Vec::new();
+        let mut errs = Vec::new();
+        for try_op in self {
+            match try_op {
+                Ok(op) => ops.push(op),
+                Err(err) => errs.push(err),
+            }
+        }
+        (ops, errs)
+    }
+
     fn next_inner(&mut self) -> Result<Option<Op>, ParseErr<'s>> {
         if let Some((cur_op_idx, mut xop)) = self.xops.next() {
             let mut op = Op {
@@ -63,20 +77,20 @@ impl<'s> Parser2<'s> {
                             Some(OperandVal::Ref(lref)) => match self.labels.get(lref.get()) {
                                 Some(label) => {
                                     let overflow_err = ParseErr {
-                                        typ: ParseErrTyp::LabelOffsetOverflow,
+                                        typ: ParseErrTyp::LabelOffsetOverflow { label: label.id },
                                         span: lref,
                                     };
 
-                                    let label_op_idx: SAddr = SAddr::try_from(label.op_idx)
-                                        .ok()
-                                        .and_then(|idx| idx.checked_mul(Op::LAYOUT.size as _))
-                                        .ok_or_else(|| overflow_err.clone())?;
-                                    let cur_op_idx: SAddr = SAddr::try_from(cur_op_idx)
-                                        .ok()
-                                        .and_then(|idx| idx.checked_mul(Op::LAYOUT.size as _))
-                                        .ok_or_else(|| overflow_err.clone())?;
-                                    let offset: SAddr =
-                                        label_op_idx.checked_sub(cur_op_idx).ok_or(overflow_err)?;
+                                    // only the byte offset between the operations needs
+                                    // to fit, not their absolute positions
+                                    let label_op_idx = isize::try_from(label.op_idx).ok();
+                                    let cur_op_idx = isize::try_from(cur_op_idx).ok();
+                                    let offset: SAddr = label_op_idx
+                                        .zip(cur_op_idx)
+                                        .and_then(|(label, cur)| label.checked_sub(cur))
+                                        .and_then(|ops| ops.checked_mul(Op::LAYOUT.size as _))
+                                        .and_then(|bytes| SAddr::try_from(bytes).ok())
+                                        .ok_or(overflow_err)?;
                                     TaggedCapability::from_ugran(gran_unsign(offset.into()))
                                 }
                                 None => {
@@ -104,7 +118,7 @@ impl<'s> Iterator for Parser2<'s> {
     type Item = Result<Op, ParseErr<'s>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(err) = self.errs.pop() {
+        if let Some(err) = self.errs.pop_front() {
             Some(Err(err))
         } else {
             self.next_inner().transpose()
@@ -0,0 +1,221 @@
+use core::fmt;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::Span;
+
+mod macros;
+
+pub use macros::{MacroErr, MacroErrTyp, ENDM, MACRO};
+
+/// Directive which splices the contents of another file in its place.
+pub const INCLUDE: &str = ".include";
+
+/// Source text assembled from one or more named sources, which remembers where
+/// each line came from.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    text: String,
+    names: Vec<String>,
+    segments: Vec<Segment>,
+}
+
+/// Run of spliced text taken from a single source.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    /// Byte index into the spliced text where the segment starts.
+    start: usize,
+    /// Index into `names`.
+    source: usize,
+    /// Zero based line number in the source where the segment starts.
+    line: usize,
+}
+
+/// Position within a named source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location<'m> {
+    pub name: &'m str,
+    /// Zero based line number.
+    pub line: usize,
+    /// Zero based column index, in bytes.
+    pub col: usize,
+}
+
+#[derive(Debug)]
+pub struct IncludeErr {
+    pub typ: IncludeErrTyp,
+    /// File containing the offending directive, if any.
+    pub file: Option<PathBuf>,
+    /// Zero based line number of the offending directive.
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub enum IncludeErrTyp {
+    Io { path: PathBuf, err: io::Error },
+    Malformed,
+    Cycle { path: PathBuf },
+}
+
+impl SourceMap {
+    /// Read the file at `path`, recursively expanding `.include "path"`
+    /// directives. Included paths are relative to the including file.
+    pub fn load(path: &Path) -> Result<Self, IncludeErr> {
+        let mut map = Self::default();
+        map.splice(path, &mut Vec::new(), None)?;
+        Ok(map)
+    }
+
+    /// Wrap source text which didn't come from a file.
+    pub fn anonymous(text: &str) -> Self {
+        let mut map = Self::default();
+        map.add("<anonymous>", text);
+        map
+    }
+
+    /// Append the source `text`, attributing it to `name`.
+    pub fn add(&mut self, name: impl Into<String>, text: &str) {
+        let source = self.push_name(name.into());
+        self.segments.push(Segment {
+            start: self.text.len(),
+            source,
+            line: 0,
+        });
+        self.text.push_str(text);
+        self.end_line();
+    }
+
+    /// Returns the text of all sources, one after another.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Translate a byte index into [`text`](Self::text) to a position within
+    /// the source it came from.
+    pub fn lookup(&self, offset: usize) -> Option<Location<'_>> {
+        if offset > self.text.len() {
+            return None;
+        }
+        let seg_idx = self.segments.partition_point(|seg| seg.start <= offset);
+        let seg = self.segments[seg_idx.checked_sub(1)?];
+        let before = &self.text[seg.start..offset];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        Some(Location {
+            name: &self.names[seg.source],
+            line: seg.line + before.matches('\n').count(),
+            col: before.len() - line_start,
+        })
+    }
+
+    /// Translate the start of `span`, which must point into
+    /// [`text`](Self::text).
+    pub fn locate(&self, span: Span<'_>) -> Option<Location<'_>> {
+        self.lookup(span.offset())
+    }
+
+    fn push_name(&mut self, name: String) -> usize {
+        self.names.push(name);
+        self.names.len() - 1
+    }
+
+    fn end_line(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+
+    fn splice(
+        &mut self,
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        directive: Option<(&Path, usize)>,
+    ) -> Result<(), IncludeErr> {
+        let err = |typ| IncludeErr {
+            typ,
+            file: directive.map(|(file, _)| file.to_owned()),
+            line: directive.map_or(0, |(_, line)| line),
+        };
+
+        let canon = fs::canonicalize(path).map_err(|e| {
+            err(IncludeErrTyp::Io {
+                path: path.to_owned(),
+                err: e,
+            })
+        })?;
+        if stack.contains(&canon) {
+            return Err(err(IncludeErrTyp::Cycle {
+                path: path.to_owned(),
+            }));
+        }
+        let src = fs::read_to_string(path).map_err(|e| {
+            err(IncludeErrTyp::Io {
+                path: path.to_owned(),
+                err: e,
+            })
+        })?;
+
+        let source = self.push_name(path.display().to_string());
+        stack.push(canon);
+        self.segments.push(Segment {
+            start: self.text.len(),
+            source,
+            line: 0,
+        });
+
+        for (line, text) in src.split_inclusive('\n').enumerate() {
+            let Some(rest) = text.trim_start().strip_prefix(INCLUDE) else {
+                self.text.push_str(text);
+                continue;
+            };
+            let included = parse_include(rest).ok_or_else(|| IncludeErr {
+                typ: IncludeErrTyp::Malformed,
+                file: Some(path.to_owned()),
+                line,
+            })?;
+            let included = path.parent().unwrap_or(Path::new("")).join(included);
+            self.splice(&included, stack, Some((path, line)))?;
+            // resume the including file on the next line
+            self.segments.push(Segment {
+                start: self.text.len(),
+                source,
+                line: line + 1,
+            });
+        }
+        self.end_line();
+
+        stack.pop();
+        Ok(())
+    }
+}
+
+/// Parse the quoted path following an include directive, allowing a trailing
+/// comment.
+fn parse_include(rest: &str) -> Option<&str> {
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim_start().strip_prefix('"')?;
+    let (path, rest) = rest.split_once('"')?;
+    let rest = rest.trim();
+    (rest.is_empty() || rest.starts_with(crate::lex::COMMENT)).then_some(path)
+}
+
+impl fmt::Display for IncludeErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.typ {
+            IncludeErrTyp::Io { path, err } => {
+                write!(f, "failed to read '{}': {err}", path.display())?;
+            }
+            IncludeErrTyp::Malformed => write!(f, "expected {INCLUDE} \"<path>\"")?,
+            IncludeErrTyp::Cycle { path } => {
+                write!(f, "'{}' includes itself", path.display())?;
+            }
+        }
+        if let Some(file) = &self.file {
+            write!(f, " (at {}:{})", file.display(), self.line + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for IncludeErr {}
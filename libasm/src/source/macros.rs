@@ -0,0 +1,233 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use super::{Segment, SourceMap};
+use crate::lex::COMMENT;
+
+/// Directive which begins a macro definition.
+pub const MACRO: &str = ".macro";
+
+/// Directive which ends a macro definition.
+pub const ENDM: &str = ".endm";
+
+/// Maximum depth of macros invoked by other macros.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MacroErr {
+    pub typ: MacroErrTyp,
+    /// Name of the source containing the offending line.
+    pub name: String,
+    /// Zero based line number of the offending line.
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MacroErrTyp {
+    Malformed,
+    Unterminated,
+    UnmatchedEnd,
+    Redefined,
+    ArgCount { expected: usize, found: usize },
+    TooDeep,
+}
+
+/// Line of source text, and where it came from.
+#[derive(Clone, Copy)]
+struct Line<'t> {
+    source: usize,
+    line: usize,
+    text: &'t str,
+}
+
+struct Macro<'t> {
+    params: Vec<&'t str>,
+    body: Vec<Line<'t>>,
+}
+
+/// Source map being built from expanded lines.
+struct Expansion {
+    map: SourceMap,
+    /// Source and line number which would continue the last segment.
+    next: Option<(usize, usize)>,
+}
+
+impl Expansion {
+    fn push_line(&mut self, line: Line<'_>) {
+        if self.next != Some((line.source, line.line)) {
+            self.map.segments.push(Segment {
+                start: self.map.text.len(),
+                source: line.source,
+                line: line.line,
+            });
+        }
+        self.next = Some((line.source, line.line + 1));
+        self.map.text.push_str(line.text);
+        self.map.end_line();
+    }
+}
+
+impl SourceMap {
+    /// Remove `.macro name params...` / `.endm` definitions, and expand lines
+    /// starting with a macro's name. Arguments are separated by commas and
+    /// bound to parameters by position. Within a macro's body, `\param` is
+    /// replaced with the corresponding argument.
+    ///
+    /// Expanded lines are attributed to the macro definition.
+    pub fn expand_macros(&mut self) -> Result<(), MacroErr> {
+        let mut out = Expansion {
+            map: Self {
+                text: String::new(),
+                names: self.names.clone(),
+                segments: Vec::new(),
+            },
+            next: None,
+        };
+        self.expand_into(&mut out)?;
+        *self = out.map;
+        Ok(())
+    }
+
+    fn expand_into(&self, out: &mut Expansion) -> Result<(), MacroErr> {
+        let mut macros: HashMap<&str, Macro<'_>> = HashMap::new();
+        let mut lines = self.lines();
+        while let Some(line) = lines.next() {
+            let mut words = strip_comment(line.text).split_whitespace();
+            if words.next() == Some(MACRO) {
+                let err = |typ| self.macro_err(typ, line);
+                let name = words.next().ok_or_else(|| err(MacroErrTyp::Malformed))?;
+                let params = words
+                    .flat_map(|word| word.split(','))
+                    .filter(|param| !param.is_empty())
+                    .collect();
+                let mut body = Vec::new();
+                loop {
+                    let body_line = lines.next().ok_or_else(|| err(MacroErrTyp::Unterminated))?;
+                    match strip_comment(body_line.text).split_whitespace().next() {
+                        Some(ENDM) => break,
+                        Some(MACRO) => {
+                            return Err(self.macro_err(MacroErrTyp::Malformed, body_line))
+                        }
+                        _ => body.push(body_line),
+                    }
+                }
+                if macros.insert(name, Macro { params, body }).is_some() {
+                    return Err(err(MacroErrTyp::Redefined));
+                }
+            } else {
+                self.expand_line(out, &macros, line, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_line(
+        &self,
+        out: &mut Expansion,
+        macros: &HashMap<&str, Macro<'_>>,
+        line: Line<'_>,
+        depth: usize,
+    ) -> Result<(), MacroErr> {
+        let code = strip_comment(line.text).trim_start();
+        let (first, rest) = code
+            .split_once(char::is_whitespace)
+            .unwrap_or((code.trim_end(), ""));
+        if first == ENDM {
+            return Err(self.macro_err(MacroErrTyp::UnmatchedEnd, line));
+        }
+        let Some(mac) = macros.get(first) else {
+            out.push_line(line);
+            return Ok(());
+        };
+        if depth == MAX_DEPTH {
+            return Err(self.macro_err(MacroErrTyp::TooDeep, line));
+        }
+
+        let args: Vec<&str> = if rest.trim().is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(str::trim).collect()
+        };
+        if args.len() != mac.params.len() {
+            let typ = MacroErrTyp::ArgCount {
+                expected: mac.params.len(),
+                found: args.len(),
+            };
+            return Err(self.macro_err(typ, line));
+        }
+        // substitute longer parameters first, so they aren't clobbered by
+        // parameters which are a prefix of them
+        let mut bindings: Vec<(String, &str)> = mac
+            .params
+            .iter()
+            .map(|param| format!("\\{param}"))
+            .zip(args)
+            .collect();
+        bindings.sort_by_key(|(param, _)| core::cmp::Reverse(param.len()));
+
+        for body_line in &mac.body {
+            let mut text = body_line.text.to_owned();
+            for (param, arg) in &bindings {
+                text = text.replace(param, arg);
+            }
+            let expanded = Line {
+                text: &text,
+                ..*body_line
+            };
+            self.expand_line(out, macros, expanded, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Iterate over every line of text, along with where it came from.
+    fn lines(&self) -> impl Iterator<Item = Line<'_>> {
+        self.segments.iter().enumerate().flat_map(|(idx, seg)| {
+            let endb = self
+                .segments
+                .get(idx + 1)
+                .map_or(self.text.len(), |next| next.start);
+            self.text[seg.start..endb]
+                .split_inclusive('\n')
+                .enumerate()
+                .map(|(offset, text)| Line {
+                    source: seg.source,
+                    line: seg.line + offset,
+                    text,
+                })
+        })
+    }
+
+    fn macro_err(&self, typ: MacroErrTyp, line: Line<'_>) -> MacroErr {
+        MacroErr {
+            typ,
+            name: self.names[line.source].clone(),
+            line: line.line,
+        }
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split_once(COMMENT).map_or(line, |(code, _)| code)
+}
+
+impl fmt::Display for MacroErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.typ {
+            MacroErrTyp::Malformed => write!(f, "expected {MACRO} <name> <params...>")?,
+            MacroErrTyp::Unterminated => write!(f, "macro definition is missing {ENDM}")?,
+            MacroErrTyp::UnmatchedEnd => write!(f, "{ENDM} outside of macro definition")?,
+            MacroErrTyp::Redefined => write!(f, "macros cannot be redefined")?,
+            MacroErrTyp::ArgCount { expected, found } => write!(
+                f,
+                "macro expects {expected} argument{s}, but found {found}",
+                s = if expected == 1 { "" } else { "s" }
+            )?,
+            MacroErrTyp::TooDeep => {
+                write!(f, "macros invoke each other more than {MAX_DEPTH} deep")?;
+            }
+        }
+        write!(f, " (at {}:{})", self.name, self.line + 1)
+    }
+}
+
+impl std::error::Error for MacroErr {}
@@ -1,16 +1,17 @@
 use fruticose_vm::capability::TaggedCapability;
 use fruticose_vm::op::{Op, OpKind};
-use fruticose_vm::registers::Register;
+use fruticose_vm::registers::{Register, Registers};
 use fruticose_vm::syscall::SyscallKind;
 
-use crate::lex::{Lexer, Token, TokenTyp};
-use crate::parse1::{Label, Operand, OperandType, OperandVal, Parser1, Stmt, XOp};
+use crate::lex::{LexErrTyp, Lexer, Token, TokenTyp};
+use crate::parse1::{Label, Operand, OperandType, OperandVal, ParseErrTyp, Parser1, Stmt, XOp};
 use crate::parse2::Parser2;
 use crate::Span;
 
 const EXIT: &str = include_str!("../examples/exit.asm");
 const ADD: &str = include_str!("../examples/add.asm");
 const CMP: &str = include_str!("../examples/cmp.asm");
+const FIXME: &str = include_str!("../examples/fixme.asm");
 
 #[test]
 fn exit_lex() {
@@ -148,6 +149,323 @@ fn exit_parse2() {
     assert_eq!(parser.next(), None);
 }
 
+#[test]
+fn assemble() {
+    let ops = crate::assemble(EXIT).unwrap();
+    assert_eq!(
+        ops,
+        Parser2::new(EXIT).collect::<Result<Vec<_>, _>>().unwrap()
+    );
+
+    let mut errs = crate::assemble("loadi t0\nsyscall\nadd t0, t1, bogus\n").unwrap_err();
+    errs.sort_by_key(|err| err.span.line);
+    assert_eq!(errs.len(), 2);
+    assert_eq!(errs[0].span.line, 0);
+    assert_eq!(errs[1].span.line, 2);
+}
+
+#[test]
+fn error_recovery() {
+    let src = "foo t0, 25\nloadi t0, 5\nadd t0 t1, t2 t3\nloadi\nlabel:\nsyscall\n";
+    let stmts: Vec<_> = Parser1::new(src).collect();
+    assert_eq!(stmts.len(), 6, "{stmts:?}");
+
+    // an identifier which isn't a label is reported, not its operands
+    let err = stmts[0].clone().unwrap_err();
+    assert_eq!(err.typ, ParseErrTyp::Lex(LexErrTyp::UnknownIdent));
+    assert_eq!(err.span.get(), "foo");
+    assert!(matches!(
+        stmts[1],
+        Ok(Stmt::Op(XOp {
+            kind: OpKind::LoadI,
+            ..
+        }))
+    ));
+    // only the first error on a line is reported
+    assert_eq!(
+        stmts[2].clone().unwrap_err().typ,
+        ParseErrTyp::MissingComma { after_operand: 1 }
+    );
+    // an error at the end of a line doesn't skip the next one
+    assert!(matches!(
+        stmts[3].clone().unwrap_err().typ,
+        ParseErrTyp::InvalidOperand {
+            found: TokenTyp::Newline
+        }
+    ));
+    assert!(matches!(stmts[4], Ok(Stmt::Label(Label { op_idx: 1, .. }))));
+    assert!(matches!(
+        stmts[5],
+        Ok(Stmt::Op(XOp {
+            kind: OpKind::Syscall,
+            ..
+        }))
+    ));
+
+    let err = Parser1::new(FIXME).find_map(Result::err).unwrap();
+    assert_eq!(err.span.get(), "foo");
+}
+
+#[test]
+fn independent_errors() {
+    let src = "loadi t0, t1 t2\nsyscall\nadd t0, t1, 5, 6\nsyscall\n";
+    let errs = crate::assemble(src).unwrap_err();
+    assert_eq!(errs.len(), 2, "{errs:?}");
+    assert_eq!((errs[0].span.line, errs[0].span.get()), (0, "t1"));
+    assert_eq!((errs[1].span.line, errs[1].span.get()), (2, "5"));
+
+    // truncating a valid program anywhere yields errors, not panics
+    for src in [EXIT, ADD, CMP] {
+        for end in (0..=src.len()).filter(|end| src.is_char_boundary(*end)) {
+            _ = crate::assemble(&src[..end]);
+        }
+    }
+}
+
+#[test]
+fn span_merge() {
+    let src = "loadi t0, 5\nadd t0, t1, t2\n";
+    let tokens: Vec<_> = Lexer::new(src).map(Result::unwrap).collect();
+    let (add, t0, t2) = (tokens[5].span, tokens[6].span, tokens[10].span);
+    assert_eq!(add.merge(t0).get(), "add t0");
+    // order doesn't matter, and text between the spans is covered
+    assert_eq!(t2.merge(add).get(), "add t0, t1, t2");
+    assert_eq!(t0.merge(t0), t0);
+    let merged = t0.merge(t2);
+    assert_eq!((merged.line, merged.col_idx), (1, 4));
+
+    // spans may cover several lines
+    let (loadi, five) = (tokens[0].span, tokens[3].span);
+    let both = t0.merge(five);
+    assert_eq!(both.get(), "5\nadd t0");
+    assert_eq!((both.line, both.end_line()), (0, 1));
+    let lines: Vec<_> = both.lines().iter().map(Span::get).collect();
+    assert_eq!(lines, ["5\n", "add t0"]);
+    assert_eq!(loadi.lines(), [loadi]);
+    assert_eq!(loadi.end_line(), 0);
+}
+
+#[test]
+fn missing_comma() {
+    for (src, after_operand, underlined) in [
+        ("add t0 t1, t2\n", 1, "t0 t1"),
+        ("add t0, t1 t2\n", 2, "t1 t2"),
+        ("loadi t0   5\n", 1, "t0   5"),
+    ] {
+        let err = Parser1::new(src).find_map(Result::err).unwrap();
+        assert_eq!(
+            err.typ,
+            ParseErrTyp::MissingComma { after_operand },
+            "{src}"
+        );
+        assert_eq!(err.span.get(), underlined, "{src}");
+    }
+    // anything else in place of a comma is still reported as such
+    let err = Parser1::new("add t0: t1, t2\n")
+        .find_map(Result::err)
+        .unwrap();
+    assert!(matches!(
+        err.typ,
+        ParseErrTyp::ExpectedTyp {
+            expected: TokenTyp::Comma,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn too_many_operands() {
+    for (src, op, extra, col_idx) in [
+        (
+            "add t0, t1, t2 t3\n",
+            OpKind::Add,
+            TokenTyp::Register(Register::T3),
+            15,
+        ),
+        ("add t0, t1, t2, t3\n", OpKind::Add, TokenTyp::Comma, 14),
+        (
+            "syscall t0\n",
+            OpKind::Syscall,
+            TokenTyp::Register(Register::T0),
+            8,
+        ),
+        // pseudo-operations report the operation they lower to
+        ("nop t0\n", OpKind::Cpy, TokenTyp::Register(Register::T0), 4),
+        ("j lbl x\n", OpKind::Jal, TokenTyp::Identifier, 6),
+        (
+            "jr ra t0\n",
+            OpKind::Jalr,
+            TokenTyp::Register(Register::T0),
+            6,
+        ),
+    ] {
+        let err = Parser1::new(src).find_map(Result::err).unwrap();
+        assert_eq!(err.typ, ParseErrTyp::TooManyOperands { op, extra }, "{src}");
+        assert_eq!(err.span.col_idx, col_idx, "{src}");
+    }
+}
+
+#[test]
+fn empty_source() {
+    for src in [
+        "",
+        "\n\n",
+        "; comment only\n",
+        "; comment only",
+        "  \t\n ; x\n\n",
+    ] {
+        assert_eq!(crate::assemble(src), Ok(Vec::new()), "{src:?}");
+        assert!(Parser1::new(src).all(|stmt| stmt.is_ok()), "{src:?}");
+    }
+}
+
+#[test]
+fn finish() {
+    let (ops, errs) = Parser2::new("loadi t0, 1\njal zero, missing\nsyscall\n").finish();
+    assert_eq!(
+        ops.iter().map(|op| op.kind).collect::<Vec<_>>(),
+        [OpKind::LoadI, OpKind::Syscall]
+    );
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].typ, ParseErrTyp::LabelUndef);
+    assert_eq!(errs[0].span.line, 1);
+}
+
+#[test]
+fn label_offsets() {
+    use fruticose_vm::abi::Ty;
+    use fruticose_vm::int::{gran_sign, SAddr};
+
+    let offset = |op: &Op| gran_sign(op.op2.to_ugran());
+    let stride = i64::from(Op::LAYOUT.size);
+
+    // offsets are in bytes, not operations
+    let ops = crate::assemble("jal zero, fwd\nback:\nsyscall\nfwd:\njal zero, back\n").unwrap();
+    assert_eq!(offset(&ops[0]), 2 * stride);
+    assert_eq!(offset(&ops[2]), -stride);
+
+    // a far jump doesn't fit in an address
+    let max_ops = usize::try_from(SAddr::MAX / SAddr::try_from(stride).unwrap()).unwrap();
+    let pad = "syscall\n".repeat(max_ops + 1);
+    let src = format!("jal zero, far\n{pad}far:\nsyscall\n");
+    let errs = crate::assemble(&src).unwrap_err();
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].span.get(), "far");
+    assert_eq!(errs[0].span.line, 0);
+    assert!(matches!(
+        errs[0].typ,
+        ParseErrTyp::LabelOffsetOverflow { label } if label.line == max_ops + 2
+    ));
+
+    // but a short jump between far away operations does
+    let src = format!("{pad}{pad}near:\nsyscall\njal zero, near\n");
+    let ops = crate::assemble(&src).unwrap();
+    assert_eq!(offset(ops.last().unwrap()), -stride);
+}
+
+#[test]
+fn cmp_blocks() {
+    use crate::cfg::{basic_blocks, Block};
+
+    let ops = crate::assemble(CMP).unwrap();
+    let block = |ops, succs: &[usize]| Block {
+        ops,
+        succs: succs.to_vec(),
+    };
+    assert_eq!(
+        basic_blocks(&ops),
+        [
+            block(0..3, &[1, 2]), // bne to cmp_true, else fall through
+            block(3..4, &[3]),    // jump to cmp_false
+            block(4..6, &[4]),    // cmp_true
+            block(6..8, &[4]),    // cmp_false
+            block(8..10, &[]),    // exit
+        ]
+    );
+
+    // calls return to the following op, but register jumps go who knows where
+    let ops = crate::assemble("jal ra, f\nsyscall\nf:\njr ra\n").unwrap();
+    assert_eq!(
+        basic_blocks(&ops),
+        [block(0..1, &[1, 2]), block(1..2, &[2]), block(2..3, &[])]
+    );
+    assert!(basic_blocks(&[]).is_empty());
+}
+
+#[test]
+fn op_display() {
+    use fruticose_vm::abi::Ty;
+
+    for src in [
+        "add t0, t1, t2",
+        "loadi a2, 5",
+        "loadu64o t0, sp, 8",
+        "syscall",
+    ] {
+        let ops = crate::assemble(&format!("{src}\n")).unwrap();
+        assert_eq!(ops[0].to_string(), src);
+    }
+
+    // label operands show the offset they were resolved to
+    let ops = crate::assemble("back:\nsyscall\nbne t0, zero, back\n").unwrap();
+    let stride = Op::LAYOUT.size;
+    assert_eq!(ops[1].to_string(), format!("bne t0, zero, -{stride}"));
+    assert_eq!(
+        format!("{:?}", ops[1]),
+        format!("Op {{ kind: Bne, op1: t0, op2: zero, op3: -{stride} }}")
+    );
+}
+
+#[test]
+fn pseudo_ops() {
+    // j label -> jal zero, label
+    assert_eq!(
+        crate::assemble("j over\nsyscall\nover:\nsyscall\n"),
+        crate::assemble("jal zero, over\nsyscall\nover:\nsyscall\n"),
+    );
+
+    // jr reg -> jalr zero, reg, 0
+    let ops = crate::assemble("jr ra\n").unwrap();
+    assert_eq!(
+        ops,
+        [Op {
+            kind: OpKind::Jalr,
+            op1: TaggedCapability::from_ugran(Register::Zero as _),
+            op2: TaggedCapability::from_ugran(Register::Ra as _),
+            op3: TaggedCapability::from_ugran(0),
+        }]
+    );
+
+    // pseudo ops still check their operands
+    assert!(crate::assemble("j\n").is_err());
+    assert!(crate::assemble("j t0\n").is_err());
+    assert!(crate::assemble("jr ra, t0\n").is_err());
+
+    // li and mv are aliases, and the canonical forms still work
+    assert_eq!(
+        crate::assemble("li t0, 5\nmv t1, t0\n"),
+        crate::assemble("loadi t0, 5\ncpy t1, t0\n"),
+    );
+    assert!(crate::assemble("li t0, t1\n").is_err());
+    assert!(crate::assemble("mv t1, 5\n").is_err());
+
+    // nop -> cpy zero, zero
+    assert_eq!(
+        crate::assemble("nop\n"),
+        crate::assemble("cpy zero, zero\n")
+    );
+    assert!(crate::assemble("nop t0\n").is_err());
+    assert_eq!(
+        crate::assemble(&format!("nop\n{EXIT}")).unwrap()[1..],
+        crate::assemble(EXIT).unwrap()
+    );
+
+    // cmp.asm reads more clearly with j
+    let rewritten = CMP.replace("jal zero,", "j");
+    assert_ne!(rewritten, CMP);
+    assert_eq!(crate::assemble(&rewritten), crate::assemble(CMP));
+}
+
 #[test]
 fn add_lex() {
     let src = ADD;
@@ -850,6 +1168,167 @@ fn cmp_parse1() {
     assert_eq!(parser.next(), None);
 }
 
+#[test]
+fn width_consts() {
+    use fruticose_vm::int::{UAddr, UADDR_SIZE, UGRAN_BITS, UGRAN_SIZE};
+
+    let expect = [
+        ("UGRAN_SIZE", UGRAN_SIZE.into()),
+        ("UGRAN_BITS", UGRAN_BITS.into()),
+        ("UADDR_SIZE", UADDR_SIZE.into()),
+        ("UADDR_BITS", UAddr::BITS.into()),
+    ];
+    for (src, val) in expect {
+        let token = Lexer::new(src).next().unwrap().unwrap();
+        assert_eq!(token.typ, TokenTyp::UnsignedInt(val), "{src}");
+    }
+    assert_eq!(UGRAN_BITS, u32::from(UGRAN_SIZE) * 8);
+}
+
+#[test]
+fn layout_consts() {
+    use fruticose_vm::abi::{Layout, Ty};
+
+    let expect = [
+        ("LAYOUT_SIZE", Layout::LAYOUT.size.into()),
+        ("LAYOUT_ALIGN", Layout::LAYOUT.align.repr().into()),
+        ("CAP_SIZE", TaggedCapability::LAYOUT.size.into()),
+        ("CAP_ALIGN", TaggedCapability::LAYOUT.align.repr().into()),
+    ];
+    for (src, val) in expect {
+        let token = Lexer::new(src).next().unwrap().unwrap();
+        assert_eq!(token.typ, TokenTyp::UnsignedInt(val), "{src}");
+    }
+}
+
+#[test]
+fn typed_ints() {
+    use fruticose_vm::int::{gran_unsign, UGran};
+
+    use crate::lex::IntTy;
+
+    let ty = |signed, bits| IntTy { signed, bits };
+    for (src, val, expect_ty) in [
+        ("42u8", 42, ty(false, 8)),
+        ("255u8", 255, ty(false, 8)),
+        ("65535u16", 65535, ty(false, 16)),
+        ("18446744073709551615u64", UGran::MAX, ty(false, 64)),
+        ("-1s32", UGran::MAX, ty(true, 32)),
+        ("-128i8", gran_unsign(-128), ty(true, 8)),
+        ("127s8", 127, ty(true, 8)),
+    ] {
+        let token = Lexer::new(src).next().unwrap().unwrap();
+        assert_eq!(token.typ, TokenTyp::TypedInt(val, expect_ty), "{src}");
+    }
+    for (src, expect_ty) in [
+        ("256u8", ty(false, 8)),
+        ("300u8", ty(false, 8)),
+        ("4294967296u32", ty(false, 32)),
+        ("128s8", ty(true, 8)),
+        ("-129s8", ty(true, 8)),
+        ("-1u8", ty(false, 8)),
+        ("18446744073709551616u64", ty(false, 64)),
+    ] {
+        let err = Lexer::new(src).next().unwrap().unwrap_err();
+        assert_eq!(
+            err.typ,
+            LexErrTyp::TypedIntOverflow { ty: expect_ty },
+            "{src}"
+        );
+    }
+    // a suffix alone, or an unknown width, is an identifier
+    for src in ["u8", "-s8", "42u7"] {
+        let token = Lexer::new(src).next().unwrap().unwrap();
+        assert_eq!(token.typ, TokenTyp::Identifier, "{src}");
+    }
+}
+
+#[test]
+fn display_round_trip() {
+    use crate::parse1::PseudoOp;
+
+    for op in (0..=u8::MAX).filter_map(|byte| OpKind::from_byte(byte).ok()) {
+        assert_eq!(OpKind::from_str(op.display()), Some(op));
+    }
+    for reg in (0..Registers::COUNT).map(|byte| Register::from_byte(byte).unwrap()) {
+        assert_eq!(Register::from_str(reg.display()), Some(reg));
+    }
+    for pseudo in [
+        PseudoOp::J,
+        PseudoOp::Jr,
+        PseudoOp::Li,
+        PseudoOp::Mv,
+        PseudoOp::Nop,
+    ] {
+        assert_eq!(PseudoOp::from_str(pseudo.display()), Some(pseudo));
+    }
+}
+
+#[test]
+fn examples_lex() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let src = std::fs::read_to_string(&path).unwrap();
+        for token in Lexer::new(&src) {
+            let token = token.unwrap_or_else(|err| panic!("{}: {err:?}", path.display()));
+            if matches!(token.typ, TokenTyp::Eof | TokenTyp::Newline) {
+                continue;
+            }
+            // each token lexes the same in isolation
+            let alone = Lexer::new(token.span.get()).next().unwrap().unwrap();
+            assert_eq!(alone.typ, token.typ, "{}", path.display());
+        }
+    }
+}
+
+#[test]
+fn numeric_registers() {
+    for reg in (0..Registers::COUNT).map(|byte| Register::from_byte(byte).unwrap()) {
+        let src = format!("x{}", reg as u8);
+        let token = Lexer::new(&src).next().unwrap().unwrap();
+        assert_eq!(token.typ, TokenTyp::Register(reg), "{src}");
+    }
+    let token = Lexer::new("x1").next().unwrap().unwrap();
+    assert_eq!(token.typ, TokenTyp::Register(Register::Pc));
+    for src in ["x33", "x01", "x+1", "x"] {
+        let token = Lexer::new(src).next().unwrap().unwrap();
+        assert_eq!(token.typ, TokenTyp::Identifier, "{src}");
+    }
+}
+
+#[test]
+fn perms_literals() {
+    use fruticose_vm::capability::Permissions;
+
+    for (src, perms) in [
+        (
+            "%rwx",
+            Permissions::READ | Permissions::WRITE | Permissions::EXEC,
+        ),
+        ("%r--", Permissions::READ),
+        (
+            "%r-x-----g",
+            Permissions::READ | Permissions::EXEC | Permissions::GLOBAL,
+        ),
+        ("%rwxsuRWLg", Permissions::all()),
+        ("%", Permissions::empty()),
+    ] {
+        let token = Lexer::new(src).next().unwrap().unwrap();
+        assert_eq!(
+            token.typ,
+            TokenTyp::UnsignedInt(perms.bits().into()),
+            "{src}"
+        );
+        // literals match how permissions are displayed
+        assert_eq!(Permissions::from_display(&perms.to_string()), Some(perms));
+    }
+    for src in ["%xwr", "%rwxsuRWLgg", "%q"] {
+        let err = Lexer::new(src).next().unwrap().unwrap_err();
+        assert_eq!(err.typ, LexErrTyp::InvalidPermissions, "{src}");
+    }
+}
+
 mod crash {
     use fruticose_vm::op::OpKind;
 
@@ -915,3 +1394,167 @@ mod crash {
         }
     }
 }
+
+mod source {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::parse2::Parser2;
+    use crate::source::{IncludeErrTyp, Location, MacroErr, MacroErrTyp, SourceMap};
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "fruticose-asm-{name}-{pid}",
+            pid = std::process::id()
+        ));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn lookup() {
+        let mut map = SourceMap::default();
+        map.add("a", "loadi t0, 1\nsyscall");
+        map.add("b", "\n  add t0, t0, t0\n");
+        assert_eq!(map.text(), "loadi t0, 1\nsyscall\n\n  add t0, t0, t0\n");
+
+        let loc = |name, line, col| Some(Location { name, line, col });
+        assert_eq!(map.lookup(0), loc("a", 0, 0));
+        assert_eq!(map.lookup(6), loc("a", 0, 6));
+        assert_eq!(map.lookup(12), loc("a", 1, 0));
+        assert_eq!(map.lookup(19), loc("a", 1, 7));
+        assert_eq!(map.lookup(20), loc("b", 0, 0));
+        assert_eq!(map.lookup(23), loc("b", 1, 2));
+        assert_eq!(map.lookup(map.text().len()), loc("b", 2, 0));
+        assert_eq!(map.lookup(map.text().len() + 1), None);
+    }
+
+    #[test]
+    fn macro_expand() {
+        let mut map = SourceMap::anonymous(
+            ".macro addi2 dst, src, imm ; dst = src + 2 * imm
+            addi \\dst, \\src, \\imm
+            addi \\dst, \\dst, \\imm
+            .endm
+            addi2 t0, zero, 3
+            addi2 t1, t0, 4 ; comment
+            syscall
+",
+        );
+        map.expand_macros().unwrap();
+        let expanded: Vec<_> = Parser2::new(map.text()).map(Result::unwrap).collect();
+        let by_hand: Vec<_> = Parser2::new(
+            "addi t0, zero, 3
+            addi t0, t0, 3
+            addi t1, t0, 4
+            addi t1, t1, 4
+            syscall
+",
+        )
+        .map(Result::unwrap)
+        .collect();
+        assert_eq!(expanded, by_hand);
+
+        // expanded lines are attributed to the definition
+        let loc = |offset| map.lookup(offset).unwrap().line;
+        let nth_line = |n| map.text().split_inclusive('\n').take(n).map(str::len).sum();
+        assert_eq!(loc(nth_line(0)), 1);
+        assert_eq!(loc(nth_line(1)), 2);
+        assert_eq!(loc(nth_line(2)), 1);
+        assert_eq!(loc(nth_line(4)), 6);
+    }
+
+    #[test]
+    fn macro_errs() {
+        let expand = |src| SourceMap::anonymous(src).expand_macros();
+        let err = |typ, line| {
+            Err(MacroErr {
+                typ,
+                name: "<anonymous>".to_owned(),
+                line,
+            })
+        };
+        assert_eq!(
+            expand(".macro two a, b\nsyscall\n.endm\ntwo t0\n"),
+            err(
+                MacroErrTyp::ArgCount {
+                    expected: 2,
+                    found: 1
+                },
+                3
+            )
+        );
+        assert_eq!(
+            expand("\n.macro m\nsyscall\n"),
+            err(MacroErrTyp::Unterminated, 1)
+        );
+        assert_eq!(
+            expand("syscall\n.endm\n"),
+            err(MacroErrTyp::UnmatchedEnd, 1)
+        );
+        assert_eq!(
+            expand(".macro m\n.endm\n.macro m\n.endm\n"),
+            err(MacroErrTyp::Redefined, 2)
+        );
+        assert_eq!(
+            expand(".macro m\nm\n.endm\nm\n"),
+            err(MacroErrTyp::TooDeep, 1)
+        );
+    }
+
+    #[test]
+    fn include() {
+        let dir = scratch_dir("include");
+        fs::write(
+            dir.join("main.asm"),
+            "loadi t0, 1\n.include \"lib/exit.asm\" ; exits\nloadi t1, 2\n",
+        )
+        .unwrap();
+        fs::write(dir.join("lib/exit.asm"), "loadi a2, SYS_EXIT\nsyscall").unwrap();
+
+        let map = SourceMap::load(&dir.join("main.asm")).unwrap();
+        assert_eq!(
+            map.text(),
+            "loadi t0, 1\nloadi a2, SYS_EXIT\nsyscall\nloadi t1, 2\n"
+        );
+        assert_eq!(Parser2::new(map.text()).count(), 4);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_err_location() {
+        let dir = scratch_dir("include-err");
+        fs::write(
+            dir.join("main.asm"),
+            "loadi t0, 1\n.include \"lib/bad.asm\"\nbogus\n",
+        )
+        .unwrap();
+        fs::write(dir.join("lib/bad.asm"), "\nloadi t0, 1\nloadi\n").unwrap();
+
+        let map = SourceMap::load(&dir.join("main.asm")).unwrap();
+        let errs: Vec<_> = Parser2::new(map.text())
+            .filter_map(Result::err)
+            .map(|err| {
+                let loc = map.locate(err.span).unwrap();
+                (loc.name.to_owned(), loc.line)
+            })
+            .collect();
+        // errors are reported in source order
+        let name = |path: &str| dir.join(path).display().to_string();
+        assert_eq!(errs, [(name("lib/bad.asm"), 2), (name("main.asm"), 2)]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = scratch_dir("include-cycle");
+        fs::write(dir.join("main.asm"), ".include \"lib/a.asm\"\n").unwrap();
+        fs::write(dir.join("lib/a.asm"), ".include \"../main.asm\"\n").unwrap();
+
+        let err = SourceMap::load(&dir.join("main.asm")).unwrap_err();
+        assert!(matches!(err.typ, IncludeErrTyp::Cycle { .. }));
+        assert_eq!(err.file.as_deref(), Some(&*dir.join("lib/a.asm")));
+        assert_eq!(err.line, 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
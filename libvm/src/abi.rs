@@ -40,6 +40,17 @@ impl Align {
     pub const fn get(self) -> UAddr {
         (2 as UAddr).pow(self.0 as _)
     }
+
+    /// Returns the base 2 logarithm of the alignment, as encoded in memory.
+    pub const fn repr(self) -> u8 {
+        self.0
+    }
+
+    /// Returns whether the alignment fits in an address. Alignments read from
+    /// memory may not.
+    pub const fn is_valid(self) -> bool {
+        self.0 < UAddr::BITS as u8
+    }
 }
 
 impl Ty for Align {
@@ -64,7 +75,11 @@ impl Ty for Align {
 
 impl fmt::Debug for Align {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_tuple("Align").field(&self.get()).finish()
+        if self.is_valid() {
+            f.debug_tuple("Align").field(&self.get()).finish()
+        } else {
+            write!(f, "Align(2^{})", self.0)
+        }
     }
 }
 
@@ -100,14 +115,57 @@ impl Ty for Layout {
 
 impl Layout {
     const FIELDS: &'static [Layout] = &[UAddr::LAYOUT, Align::LAYOUT];
+
+    /// Returns the layout of an array of `count` elements with layout `self`,
+    /// or `None` if its size overflows. Each element is padded to its
+    /// alignment.
+    pub const fn array(self, count: UAddr) -> Option<Self> {
+        let stride = match self.size.checked_next_multiple_of(self.align.get()) {
+            Some(stride) => stride,
+            None => return None,
+        };
+        match stride.checked_mul(count) {
+            Some(size) => Some(Self {
+                size,
+                align: self.align,
+            }),
+            None => None,
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Layout {{ {size} byte{s}, align {align} }}",
+            size = self.size,
+            s = if self.size == 1 { "" } else { "s" },
+            align = self.align,
+        )
+    }
 }
 
 impl fmt::Display for Align {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.get())
+        if self.is_valid() {
+            write!(f, "{}", self.get())
+        } else {
+            write!(f, "2^{}", self.0)
+        }
     }
 }
 
+/// Returns the size of `T` in bytes.
+pub const fn size_of<T: Ty>() -> UAddr {
+    T::LAYOUT.size
+}
+
+/// Returns the alignment of `T`.
+pub const fn align_of<T: Ty>() -> Align {
+    T::LAYOUT.align
+}
+
 pub trait Ty: Copy + Sized + fmt::Debug {
     const LAYOUT: Layout;
 
@@ -119,7 +177,7 @@ pub trait Ty: Copy + Sized + fmt::Debug {
     ///
     /// - The length of `src` must equal `Self::LAYOUT.size`.
     /// - The length of `valid` must equal the number of granules which `src`
-    /// spans.
+    ///   spans.
     ///
     /// # Errors
     ///
@@ -136,7 +194,7 @@ pub trait Ty: Copy + Sized + fmt::Debug {
     ///
     /// - The length of `dst` must equal `Self::LAYOUT.size`.
     /// - The length of `valid` must equal the number of granules which `dst`
-    /// spans.
+    ///   spans.
     ///
     /// # Errors
     ///
@@ -192,10 +250,7 @@ struct FieldStep {
 impl FieldStep {
     const fn new(field: Layout, mut cur_offset: UAddr) -> FieldStep {
         // bump to aligned start of field
-        while cur_offset % field.align.get() != 0 {
-            // 2.next_multiple_of_two() == 2, so add 1 to always go up
-            cur_offset = (cur_offset + 1).next_power_of_two();
-        }
+        cur_offset = cur_offset.next_multiple_of(field.align.get());
         let field_offset = cur_offset;
         cur_offset += field.size; // bamf out
         FieldStep {
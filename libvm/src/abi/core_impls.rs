@@ -5,7 +5,6 @@ use core::mem;
 use super::{Align, Layout, Ty};
 use crate::capability::Address;
 use crate::exception::Exception;
-use crate::int::{SAddr, SGran, UAddr, UGran};
 
 /* boolean */
 
@@ -30,6 +29,9 @@ impl Ty for bool {
 
 /* integers */
 
+/* NOTE: integers are encoded little endian regardless of the host, and so is
+ * everything built on them (including capabilities, via to_ugran) */
+
 macro_rules! int_impl {
     ($type:ty) => {
         impl Ty for $type {
@@ -66,12 +68,16 @@ macro_rules! int_impl {
     };
 }
 
-int_impl!(UGran);
-int_impl!(UAddr);
+// NOTE: concrete widths rather than aliases, so every width-specific load and
+// store has a backing type no matter what UGran and UAddr are defined as
 int_impl!(u8);
+int_impl!(u16);
 int_impl!(u32);
+int_impl!(u64);
+int_impl!(u128);
 
-int_impl!(SGran);
-int_impl!(SAddr);
 int_impl!(i8);
+int_impl!(i16);
 int_impl!(i32);
+int_impl!(i64);
+int_impl!(i128);
@@ -45,7 +45,7 @@ impl<'fields, 'src, 'valid> StructRef<'fields, 'src, 'valid> {
 
         let src = &self.src[offset as usize..][..size as usize];
         let addr = self.addr.add(offset);
-        let valid = &self.valid[abi::gran_span(self.addr, offset)..][..=abi::gran_span(addr, size)];
+        let valid = &self.valid[field_gran(self.addr, addr)..][..=abi::gran_span(addr, size)];
 
         T::read(src, addr, valid)
     }
@@ -73,13 +73,18 @@ impl<'fields, 'dst, 'valid> StructMut<'fields, 'dst, 'valid> {
 
         let dst = &mut self.dst[offset as usize..][..size as usize];
         let addr = self.addr.add(offset);
-        let valid =
-            &mut self.valid[abi::gran_span(self.addr, offset)..][..=abi::gran_span(addr, size)];
+        let valid = &mut self.valid[field_gran(self.addr, addr)..][..=abi::gran_span(addr, size)];
 
         src.write(dst, addr, valid)
     }
 }
 
+/// Index of the granule holding the field at `field`, counted from the granule
+/// holding the start of the struct at `base`.
+fn field_gran(base: Address, field: Address) -> usize {
+    usize::from(field.gran().0 - base.gran().0)
+}
+
 #[derive(Debug)]
 pub struct StructLogic<'fields> {
     fields: slice::Iter<'fields, Layout>,
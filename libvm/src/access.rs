@@ -37,6 +37,8 @@ pub enum MemAccessKind {
     Read,
     Write,
     Execute,
+    /// Write of a valid capability.
+    StoreCap,
 }
 
 impl fmt::Display for MemAccessKind {
@@ -45,6 +47,7 @@ impl fmt::Display for MemAccessKind {
             Self::Read => "read",
             Self::Write => "write",
             Self::Execute => "execute",
+            Self::StoreCap => "capability store",
         };
         f.write_str(name)
     }
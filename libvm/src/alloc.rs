@@ -1,24 +1,31 @@
 mod bump;
+mod stack;
 
 use bitflags::bitflags;
 use bitvec::slice::BitSlice;
 
+use core::fmt;
+use core::ops::Range;
+
 use crate::abi::{self, Align, CustomFields, Layout, StructMut, StructRef, Ty};
 use crate::access::MemAccessKind;
 use crate::capability::{Address, OType, Permissions, TaggedCapability};
 use crate::exception::Exception;
-use crate::int::{UAddr, UNINIT_BYTE};
+use crate::int::UAddr;
 use crate::mem::Memory;
 use crate::revoke;
 
 use bump::BumpAlloc;
+use stack::StackAlloc;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Strategy {
     Bump = 1,
     // LinkedList,
-    // Stack,
+    /// Allocations are freed in the reverse order they were made.
+    Stack = 2,
 }
 
 impl Strategy {
@@ -29,11 +36,21 @@ impl Strategy {
     pub const fn from_byte(byte: u8) -> Result<Self, Exception> {
         match byte {
             1 => Ok(Self::Bump),
+            2 => Ok(Self::Stack),
             _ => Err(Exception::InvalidAllocStrategy { byte }),
         }
     }
 }
 
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bump => "bump",
+            Self::Stack => "stack",
+        })
+    }
+}
+
 impl Ty for Strategy {
     const LAYOUT: Layout = Layout {
         size: 1,
@@ -56,9 +73,36 @@ impl Ty for Strategy {
 
 bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct InitFlags: u8 {
         const INIT_ON_ALLOC = 0b00000001;
         const INIT_ON_FREE = 0b00000010;
+        /// Quarantine freed memory rather than revoking capabilities to it
+        /// immediately. See [`revoke::Quarantine`].
+        const QUARANTINE = 0b00000100;
+        /// Poison the bytes added when an allocation grows through [`realloc`].
+        const INIT_ON_REALLOC = 0b00001000;
+    }
+}
+
+impl fmt::Display for InitFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no flags");
+        }
+        for (idx, flag) in self.iter().enumerate() {
+            if idx != 0 {
+                f.write_str("|")?;
+            }
+            f.write_str(match flag {
+                Self::INIT_ON_ALLOC => "init-on-alloc",
+                Self::INIT_ON_FREE => "init-on-free",
+                Self::QUARANTINE => "quarantine",
+                Self::INIT_ON_REALLOC => "init-on-realloc",
+                _ => unreachable!("unnamed flag {flag:?}"),
+            })?;
+        }
+        Ok(())
     }
 }
 
@@ -69,7 +113,8 @@ impl Ty for InitFlags {
     };
 
     fn read(src: &[u8], addr: Address, valid: &BitSlice<u8>) -> Result<Self, Exception> {
-        Ok(Self::from_bits_truncate(u8::read(src, addr, valid)?))
+        let flags = u8::read(src, addr, valid)?;
+        Self::from_bits(flags).ok_or(Exception::InvalidAllocInitFlags { flags })
     }
 
     fn write(
@@ -84,14 +129,43 @@ impl Ty for InitFlags {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stats {
     pub strategy: Strategy,
     pub flags: InitFlags,
     pub bytes_free: UAddr,
+    /// First address which is free to allocate from.
+    pub free_start: Address,
 }
 
 impl Stats {
-    const FIELDS: &'static [Layout] = &[Strategy::LAYOUT, InitFlags::LAYOUT, UAddr::LAYOUT];
+    const FIELDS: &'static [Layout] = &[
+        Strategy::LAYOUT,
+        InitFlags::LAYOUT,
+        UAddr::LAYOUT,
+        Address::LAYOUT,
+    ];
+
+    /// Returns the size of the largest allocation with alignment `align` which
+    /// could currently succeed. This is less than
+    /// [`bytes_free`](Self::bytes_free) when padding is needed to align the
+    /// allocation.
+    pub const fn largest_free(&self, align: Align) -> UAddr {
+        let endb = self.free_start.get() as u32 + self.bytes_free as u32;
+        let (start, align) = match self.strategy {
+            Strategy::Bump => (self.free_start.get() as u32, align.get()),
+            Strategy::Stack => (
+                self.free_start.get() as u32 + stack::LINK.size as u32,
+                if align.get() > stack::LINK.align.get() {
+                    align.get()
+                } else {
+                    stack::LINK.align.get()
+                },
+            ),
+        };
+        let start = start.next_multiple_of(align as u32);
+        endb.saturating_sub(start) as UAddr
+    }
 }
 
 impl Ty for Stats {
@@ -103,6 +177,7 @@ impl Ty for Stats {
             strategy: fields.read_next::<Strategy>()?,
             flags: fields.read_next::<InitFlags>()?,
             bytes_free: fields.read_next::<UAddr>()?,
+            free_start: fields.read_next::<Address>()?,
         })
     }
 
@@ -116,10 +191,24 @@ impl Ty for Stats {
         fields.write_next(self.strategy)?;
         fields.write_next(self.flags)?;
         fields.write_next(self.bytes_free)?;
+        fields.write_next(self.free_start)?;
         Ok(())
     }
 }
 
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Stats {{ {strategy}, {flags}, {bytes_free} byte{s} free }}",
+            strategy = self.strategy,
+            flags = self.flags,
+            bytes_free = self.bytes_free,
+            s = if self.bytes_free == 1 { "" } else { "s" },
+        )
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct AllocErr {
     pub stats: Stats,
@@ -134,6 +223,17 @@ pub enum AllocErrKind {
 
     /// The allocator reports 0 bytes free.
     Oom,
+
+    /// The requested layout's alignment doesn't fit in an address.
+    InvalidLayout,
+
+    /// The allocation being freed isn't the most recent one which is still
+    /// live, and the allocator can only free in reverse order.
+    OutOfOrderFree,
+
+    /// The allocator's strategy doesn't support the request, such as a bump
+    /// allocator freeing a single allocation.
+    Unsupported,
 }
 
 impl From<AllocErr> for Exception {
@@ -186,6 +286,27 @@ fn magic_unseal(cap: TaggedCapability) -> TaggedCapability {
     cap.unseal(cap.set_perms(cap.perms() | Permissions::UNSEAL))
 }
 
+/// Sweep the quarantine if any of it lies within `region`, since quarantined
+/// memory must not be reused until capabilities to it are revoked.
+fn sweep_overlapping(region: TaggedCapability, mem: &mut Memory) -> Result<(), Exception> {
+    if mem.quarantine.overlaps(region.start(), region.endb()) {
+        revoke::sweep_quarantine(mem)?;
+    }
+    Ok(())
+}
+
+fn check_layout(ator: TaggedCapability, layout: Layout, mem: &Memory) -> Result<(), Exception> {
+    if !layout.align.is_valid() {
+        return Err(AllocErr {
+            stats: stat(ator, mem)?,
+            requested: layout,
+            kind: AllocErrKind::InvalidLayout,
+        }
+        .into());
+    }
+    Ok(())
+}
+
 /// Initialize an allocator.
 ///
 /// Pass ownership of `region` to a new allocator with the specified
@@ -197,7 +318,12 @@ pub fn init(
     region: TaggedCapability,
     mem: &mut Memory,
 ) -> Result<TaggedCapability, Exception> {
-    // TODO: if sealed, set_addr invalidates region which leads to confusing error message
+    if region.is_valid() && region.is_sealed() {
+        // set_addr would invalidate it, leading to a confusing error
+        return Err(Exception::CapabilitySealed {
+            otype: region.otype(),
+        });
+    }
     let region = region.set_addr(region.start()); // reset address to region start
     region.check_access(
         MemAccessKind::Write,
@@ -208,6 +334,7 @@ pub fn init(
     /* NOTE: invalidate all capabilities matching 'region' before returning to
      * prevent caller from saving the capability and using it to mess with the
      * allocator */
+    sweep_overlapping(region, mem)?;
     revoke::by_bounds(mem, region.start(), region.endb())?;
 
     let mut fields = CustomFields::new(region);
@@ -219,6 +346,11 @@ pub fn init(
             let ator = BumpAlloc::new(region.set_bounds(ator_cap.endb(), region.endb()));
             fields.write_next(ator, mem)?;
         }
+        Strategy::Stack => {
+            let ator_cap = fields.peek::<StackAlloc>();
+            let ator = StackAlloc::new(region.set_bounds(ator_cap.endb(), region.endb()));
+            fields.write_next(ator, mem)?;
+        }
     }
     /* NOTE: the sealing capability is not constructable by userspace because we
      * revoke capabilities pointing into the region */
@@ -234,6 +366,7 @@ pub fn deinit(ator: TaggedCapability, mem: &mut Memory) -> Result<TaggedCapabili
     let ator = magic_unseal(ator);
     let mut fields = CustomFields::new(ator);
     let header: Header = fields.read_next(mem)?;
+    sweep_overlapping(ator, mem)?;
     match header.strat {
         Strategy::Bump => {
             let bump_cap = fields.peek::<BumpAlloc>();
@@ -241,22 +374,34 @@ pub fn deinit(ator: TaggedCapability, mem: &mut Memory) -> Result<TaggedCapabili
             bump.free_all();
             mem.write(bump_cap, bump)?;
         }
+        Strategy::Stack => {
+            let stack_cap = fields.peek::<StackAlloc>();
+            let mut stack: StackAlloc = fields.read_next(mem)?;
+            stack.free_all();
+            mem.write(stack_cap, stack)?;
+        }
     }
     if header.flags.contains(InitFlags::INIT_ON_FREE) {
-        mem.memset(ator, ator.span_len(), UNINIT_BYTE)?;
+        mem.poison(ator)?;
     }
     revoke::by_bounds(mem, ator.start(), ator.endb())?;
     Ok(ator)
 }
 
+/// Request an allocation with the given `layout` from an allocator.
+///
+/// Zero sized requests are allowed, and yield a valid capability spanning no
+/// bytes.
 pub fn alloc(
     ator: TaggedCapability,
     layout: Layout,
     mem: &mut Memory,
 ) -> Result<TaggedCapability, Exception> {
+    check_layout(ator, layout, mem)?;
     let ator = magic_unseal(ator);
     let mut fields = CustomFields::new(ator);
     let header: Header = fields.read_next(mem)?;
+    sweep_overlapping(ator, mem)?;
     let ation = match header.strat {
         Strategy::Bump => {
             let bump_cap = fields.peek::<BumpAlloc>();
@@ -265,20 +410,153 @@ pub fn alloc(
             mem.write(bump_cap, bump)?;
             ation
         }
+        Strategy::Stack => {
+            let stack_cap = fields.peek::<StackAlloc>();
+            let mut stack: StackAlloc = fields.read_next(mem)?;
+            let ation = stack.alloc(header, layout, mem)?;
+            mem.write(stack_cap, stack)?;
+            ation
+        }
     };
     if header.flags.contains(InitFlags::INIT_ON_ALLOC) {
-        mem.memset(ation, ation.span_len(), UNINIT_BYTE)?;
+        mem.poison(ation)?;
     }
     Ok(ation)
 }
 
+/// Resize `ation` to `layout`, preserving its contents up to the smaller of the
+/// two sizes.
+///
+/// The allocation is resized in place if it's the most recent one and there is
+/// room, else it moves to a new allocation. The old allocation isn't freed
+/// when moving. Shrinking in place releases the tail as [`free`] would, which
+/// invalidates the old capability.
+pub fn realloc(
+    ator: TaggedCapability,
+    ation: TaggedCapability,
+    layout: Layout,
+    mem: &mut Memory,
+) -> Result<TaggedCapability, Exception> {
+    check_layout(ator, layout, mem)?;
+    let sealed = ator;
+    let ator = magic_unseal(ator);
+    let mut fields = CustomFields::new(ator);
+    let header: Header = fields.read_next(mem)?;
+    sweep_overlapping(ator, mem)?;
+    let resized = match header.strat {
+        Strategy::Bump => {
+            let bump_cap = fields.peek::<BumpAlloc>();
+            let mut bump: BumpAlloc = fields.read_next(mem)?;
+            let resized = resize_top(&mut bump.inner, ation, layout);
+            if let Some(tail) = resized.and_then(|new| shrunk_tail(bump.inner, ation, new)) {
+                release(header, ator.start()..bump.inner.start(), tail, mem)?;
+            }
+            mem.write(bump_cap, bump)?;
+            resized
+        }
+        Strategy::Stack => {
+            let stack_cap = fields.peek::<StackAlloc>();
+            let mut stack: StackAlloc = fields.read_next(mem)?;
+            // the link before the allocation must stay where it is
+            let resized = (ation.start() == stack.top)
+                .then(|| resize_top(&mut stack.inner, ation, layout))
+                .flatten();
+            if let Some(tail) = resized.and_then(|new| shrunk_tail(stack.inner, ation, new)) {
+                release(header, ator.start()..stack.inner.start(), tail, mem)?;
+            }
+            mem.write(stack_cap, stack)?;
+            resized
+        }
+    };
+
+    let old_size = ation.span_len();
+    let new = match resized {
+        Some(new) => new,
+        None => {
+            let new = alloc(sealed, layout, mem)?;
+            mem.memcpy(
+                new,
+                ation.set_addr(ation.start()),
+                old_size.min(layout.size),
+            )?;
+            new
+        }
+    };
+    if header.flags.contains(InitFlags::INIT_ON_REALLOC) && layout.size > old_size {
+        let tail = new.start().add(old_size);
+        mem.poison(new.set_addr(tail).set_bounds(tail, new.endb()))?;
+    }
+    Ok(new)
+}
+
+/// Resize `ation` in place if it ends at the top of `inner`, the free span of
+/// a bump or stack allocator, and the resized allocation fits.
+fn resize_top(
+    inner: &mut TaggedCapability,
+    ation: TaggedCapability,
+    layout: Layout,
+) -> Option<TaggedCapability> {
+    let start = ation.start();
+    let fits = ation.is_valid()
+        && ation.endb() == inner.addr()
+        && inner.capability().contains(&ation.capability())
+        && start.is_aligned_to(layout.align)
+        && u32::from(start.get()) + u32::from(layout.size) <= u32::from(inner.endb().get());
+    if !fits {
+        return None;
+    }
+    let resized = inner
+        .set_addr(start)
+        .set_bounds(start, start.add(layout.size));
+    *inner = inner.set_addr(resized.endb());
+    Some(resized)
+}
+
+/// The span given up by shrinking `ation` in place to `resized`, if any. The
+/// old capability still grants it, so it must be released like a free.
+fn shrunk_tail(
+    inner: TaggedCapability,
+    ation: TaggedCapability,
+    resized: TaggedCapability,
+) -> Option<TaggedCapability> {
+    (resized.endb() < ation.endb()).then(|| {
+        inner
+            .set_addr(resized.endb())
+            .set_bounds(resized.endb(), ation.endb())
+    })
+}
+
 pub fn free(
     ator: TaggedCapability,
-    _ation: TaggedCapability,
-    _mem: &mut Memory,
+    ation: TaggedCapability,
+    mem: &mut Memory,
 ) -> Result<(), Exception> {
-    let _ator = magic_unseal(ator);
-    todo!()
+    let ator = magic_unseal(ator);
+    let mut fields = CustomFields::new(ator);
+    let header: Header = fields.read_next(mem)?;
+    match header.strat {
+        Strategy::Bump => {
+            // allocations can only be freed all at once
+            let bump: BumpAlloc = fields.read_next(mem)?;
+            return Err(AllocErr {
+                stats: bump.stat(header),
+                requested: Layout {
+                    size: ation.span_len(),
+                    align: Align::new(1).unwrap(),
+                },
+                kind: AllocErrKind::Unsupported,
+            }
+            .into());
+        }
+        Strategy::Stack => {
+            let stack_cap = fields.peek::<StackAlloc>();
+            let mut stack: StackAlloc = fields.read_next(mem)?;
+            stack.free(header, ation, mem)?;
+            release(header, ator.start()..stack.inner.start(), ation, mem)?;
+            mem.write(stack_cap, stack)?;
+        }
+    }
+    Ok(())
 }
 
 pub fn free_all(ator: TaggedCapability, mem: &mut Memory) -> Result<(), Exception> {
@@ -290,16 +568,48 @@ pub fn free_all(ator: TaggedCapability, mem: &mut Memory) -> Result<(), Exceptio
             let bump_cap = fields.peek::<BumpAlloc>();
             let mut bump: BumpAlloc = fields.read_next(mem)?;
             bump.free_all();
-            revoke::by_bounds(mem, bump.inner.start(), bump.inner.endb())?;
-            if header.flags.contains(InitFlags::INIT_ON_FREE) {
-                mem.memset(bump.inner, bump.inner.span_len(), UNINIT_BYTE)?;
-            }
+            release(header, ator.start()..bump.inner.start(), bump.inner, mem)?;
             mem.write(bump_cap, bump)?;
         }
+        Strategy::Stack => {
+            let stack_cap = fields.peek::<StackAlloc>();
+            let mut stack: StackAlloc = fields.read_next(mem)?;
+            stack.free_all();
+            release(header, ator.start()..stack.inner.start(), stack.inner, mem)?;
+            mem.write(stack_cap, stack)?;
+        }
     }
     Ok(())
 }
 
+/// Poison the freed `span` if configured to, then revoke capabilities to it
+/// (or quarantine it). `meta` spans the allocator's bookkeeping.
+///
+/// Poisoning is the step which may fail, so it goes first: a failed free
+/// leaves the allocation live and untouched. Revoking may invalidate the
+/// allocator's own capabilities, so they must be written back afterwards.
+fn release(
+    header: Header,
+    meta: Range<Address>,
+    span: TaggedCapability,
+    mem: &mut Memory,
+) -> Result<(), Exception> {
+    if header.flags.contains(InitFlags::INIT_ON_FREE) {
+        // the address may have been moved within bounds
+        mem.poison(span.set_addr(span.start()))?;
+    }
+    if header.flags.contains(InitFlags::QUARANTINE) {
+        mem.quarantine.quarantine(meta, span.start()..span.endb());
+        if mem.quarantine.is_full() {
+            revoke::sweep_quarantine(mem)?;
+        }
+    } else {
+        revoke::by_bounds(mem, span.start(), span.endb())?;
+    }
+    mem.record_free(span.start()..span.endb());
+    Ok(())
+}
+
 pub fn stat(ator: TaggedCapability, mem: &Memory) -> Result<Stats, Exception> {
     let ator = magic_unseal(ator);
     let mut fields = CustomFields::new(ator);
@@ -309,6 +619,10 @@ pub fn stat(ator: TaggedCapability, mem: &Memory) -> Result<Stats, Exception> {
             let bump: BumpAlloc = fields.read_next(mem)?;
             bump.stat(header)
         }
+        Strategy::Stack => {
+            let stack: StackAlloc = fields.read_next(mem)?;
+            stack.stat(header)
+        }
     };
     Ok(stat)
 }
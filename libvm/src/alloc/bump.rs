@@ -28,6 +28,7 @@ impl BumpAlloc {
             strategy: header.strat,
             flags: header.flags,
             bytes_free: self.bytes_free(),
+            free_start: self.inner.addr(),
         }
     }
 
@@ -58,6 +59,10 @@ impl BumpAlloc {
         let mut ation = self.inner;
         ation = ation.set_addr(ation.addr().align_to(layout.align));
         ation = ation.set_bounds(ation.addr(), ation.addr().add(layout.size));
+        // alignment padding may push the allocation out of the region
+        if !self.inner.capability().contains(&ation.capability()) {
+            return Err(err(AllocErrKind::NotEnoughMem));
+        }
         debug_assert!(ation.is_valid());
         self.inner = self.inner.set_addr(ation.endb());
         Ok(ation)
@@ -0,0 +1,211 @@
+use bitvec::slice::BitSlice;
+
+use super::{AllocErr, AllocErrKind, Header, Stats};
+use crate::abi::{self, Align, Layout, StructMut, StructRef, Ty};
+use crate::capability::{Address, TaggedCapability};
+use crate::exception::Exception;
+use crate::int::UAddr;
+use crate::mem::Memory;
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct StackAlloc {
+    // start: region start
+    // addr: top of the stack (grows upward) or endb
+    // endb: region endb
+    pub(super) inner: TaggedCapability,
+    /// Start of the most recent live allocation, or the region start if there
+    /// is none. Only this exact allocation may be freed, since a capability
+    /// with narrowed bounds could otherwise pass off its own memory as a link.
+    pub(super) top: Address,
+}
+
+/* NOTE: each allocation is immediately preceded by a link holding the stack as
+ * it was before the allocation was made, so freeing it can restore the top
+ * regardless of alignment padding. links lie outside every allocation, so
+ * only the allocator can reach them */
+pub(super) const LINK: Layout = Link::LAYOUT;
+
+#[derive(Clone, Copy, Debug)]
+struct Link {
+    prev_top: Address,
+    prev_start: Address,
+}
+
+impl Link {
+    const FIELDS: &'static [Layout] = &[Address::LAYOUT, Address::LAYOUT];
+}
+
+impl StackAlloc {
+    const FIELDS: &'static [Layout] = &[TaggedCapability::LAYOUT, Address::LAYOUT];
+
+    pub const fn new(region: TaggedCapability) -> Self {
+        Self {
+            inner: region.set_addr(region.start()),
+            top: region.start(),
+        }
+    }
+
+    pub const fn stat(&self, header: Header) -> Stats {
+        Stats {
+            strategy: header.strat,
+            flags: header.flags,
+            bytes_free: self.bytes_free(),
+            free_start: self.inner.addr(),
+        }
+    }
+
+    pub const fn bytes_free(&self) -> UAddr {
+        // reading the allocator rejects a top past endb
+        self.inner
+            .endb()
+            .get()
+            .saturating_sub(self.inner.addr().get())
+    }
+
+    pub const fn is_full(&self) -> bool {
+        self.inner.addr().get() == self.inner.endb().get()
+    }
+
+    pub fn alloc(
+        &mut self,
+        header: Header,
+        layout: Layout,
+        mem: &mut Memory,
+    ) -> Result<TaggedCapability, Exception> {
+        let err = |kind: AllocErrKind| AllocErr {
+            stats: self.stat(header),
+            requested: layout,
+            kind,
+        };
+        if self.is_full() {
+            return Err(err(AllocErrKind::Oom).into());
+        }
+        if layout.size > self.bytes_free() {
+            return Err(err(AllocErrKind::NotEnoughMem).into());
+        }
+        // the link must be aligned too, and directly precedes the allocation
+        let align = layout.align.max(LINK.align);
+        let start = self.inner.addr().add(LINK.size).align_to(align);
+        let mut ation = self.inner.set_addr(start);
+        ation = ation.set_bounds(start, start.add(layout.size));
+        // the link and alignment padding may push the allocation out of the
+        // region
+        if start < self.inner.addr() || !self.inner.capability().contains(&ation.capability()) {
+            return Err(err(AllocErrKind::NotEnoughMem).into());
+        }
+        debug_assert!(ation.is_valid());
+        let link = Link {
+            prev_top: self.inner.addr(),
+            prev_start: self.top,
+        };
+        mem.write(self.inner.set_addr(start.sub(LINK.size)), link)?;
+        self.inner = self.inner.set_addr(ation.endb());
+        self.top = start;
+        Ok(ation)
+    }
+
+    /// Release `ation`, which must be the most recent allocation still live.
+    pub fn free(
+        &mut self,
+        header: Header,
+        ation: TaggedCapability,
+        mem: &Memory,
+    ) -> Result<(), Exception> {
+        // live allocations end at or below the top, and freeing one moves the
+        // top below its start
+        if ation.start() > self.inner.addr()
+            && self.inner.capability().contains(&ation.capability())
+        {
+            return Err(Exception::DoubleFree {
+                addr: ation.start(),
+            });
+        }
+        let is_top = ation.is_valid()
+            && self.top != self.inner.start()
+            && ation.start() == self.top
+            && ation.endb() == self.inner.addr()
+            && self.inner.capability().contains(&ation.capability());
+        if !is_top {
+            return Err(AllocErr {
+                stats: self.stat(header),
+                requested: Layout {
+                    size: ation.span_len(),
+                    align: Align::new(1).unwrap(),
+                },
+                kind: AllocErrKind::OutOfOrderFree,
+            }
+            .into());
+        }
+        let link_addr = ation.start().sub(LINK.size);
+        let link: Link = mem.read(self.inner.set_addr(link_addr))?;
+        let prev_in_region = self.inner.start() <= link.prev_start
+            && link.prev_start <= link.prev_top
+            && link.prev_top <= link_addr;
+        if !prev_in_region {
+            return Err(Exception::InvalidAllocState {
+                addr: link.prev_top,
+            });
+        }
+        self.inner = self.inner.set_addr(link.prev_top);
+        self.top = link.prev_start;
+        Ok(())
+    }
+
+    pub fn free_all(&mut self) {
+        self.inner = self.inner.set_addr(self.inner.start());
+        self.top = self.inner.start();
+    }
+}
+
+impl Ty for StackAlloc {
+    const LAYOUT: Layout = abi::layout(Self::FIELDS);
+
+    fn read(src: &[u8], addr: Address, valid: &BitSlice<u8>) -> Result<Self, Exception> {
+        let mut fields = StructRef::new(src, addr, valid, Self::FIELDS);
+        let stack = Self {
+            inner: fields.read_next::<TaggedCapability>()?,
+            top: fields.read_next::<Address>()?,
+        };
+        let inner = stack.inner;
+        if inner.addr() > inner.endb() || stack.top < inner.start() || stack.top > inner.addr() {
+            return Err(Exception::InvalidAllocState { addr: inner.addr() });
+        }
+        Ok(stack)
+    }
+
+    fn write(
+        self,
+        dst: &mut [u8],
+        addr: Address,
+        valid: &mut BitSlice<u8>,
+    ) -> Result<(), Exception> {
+        let mut fields = StructMut::new(dst, addr, valid, Self::FIELDS);
+        fields.write_next(self.inner)?;
+        fields.write_next(self.top)?;
+        Ok(())
+    }
+}
+
+impl Ty for Link {
+    const LAYOUT: Layout = abi::layout(Self::FIELDS);
+
+    fn read(src: &[u8], addr: Address, valid: &BitSlice<u8>) -> Result<Self, Exception> {
+        let mut fields = StructRef::new(src, addr, valid, Self::FIELDS);
+        Ok(Self {
+            prev_top: fields.read_next::<Address>()?,
+            prev_start: fields.read_next::<Address>()?,
+        })
+    }
+
+    fn write(
+        self,
+        dst: &mut [u8],
+        addr: Address,
+        valid: &mut BitSlice<u8>,
+    ) -> Result<(), Exception> {
+        let mut fields = StructMut::new(dst, addr, valid, Self::FIELDS);
+        fields.write_next(self.prev_top)?;
+        fields.write_next(self.prev_start)?;
+        Ok(())
+    }
+}
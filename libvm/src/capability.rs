@@ -7,14 +7,22 @@ use core::fmt::{self, Write};
 use crate::abi::{Align, Layout, Ty};
 use crate::access::{MemAccess, MemAccessKind};
 use crate::exception::Exception;
-use crate::int::{gran_sign, SAddr, UAddr, UGran, UGRAN_SIZE, UNINIT};
+use crate::int::{gran_sign, SAddr, UAddr, UGran, UGRAN_BITS, UGRAN_SIZE, UNINIT};
+
+// the fields of a capability must exactly fill a granule
+const _: () =
+    assert!(Address::BITS as u32 * 3 + Permissions::BITS as u32 + OType::BITS as u32 == UGRAN_BITS);
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 #[repr(transparent)]
 pub struct Address(pub UAddr);
 
 impl Address {
-    pub const BITS: u8 = 16;
+    /// Number of significant bits in an address. Addresses span all of
+    /// [`UAddr`], so memory can hold at most `2^BITS` bytes.
+    pub const BITS: u8 = UAddr::BITS as u8;
 
     pub const fn add(self, offset: UAddr) -> Self {
         Self(self.0.wrapping_add(offset))
@@ -37,7 +45,7 @@ impl Address {
     }
 
     pub const fn is_aligned_to(self, align: Align) -> bool {
-        self.get() % align.get() == 0
+        self.get().is_multiple_of(align.get())
     }
 
     pub const fn align_to(self, align: Align) -> Self {
@@ -109,17 +117,22 @@ impl fmt::Debug for Address {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
 pub struct Granule(pub UAddr);
 
 impl Granule {
-    pub const fn addr(self) -> Address {
-        Address(self.0.checked_add(UGRAN_SIZE as UAddr).unwrap())
+    /// Address of the granule's first byte, or `None` if it isn't addressable.
+    pub const fn addr(self) -> Option<Address> {
+        match self.0.checked_mul(UGRAN_SIZE as UAddr) {
+            Some(addr) => Some(Address(addr)),
+            None => None,
+        }
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Capability {
     addr: Address,
     start: Address,
@@ -225,6 +238,12 @@ impl Capability {
         self.endb().get().saturating_sub(self.start().get())
     }
 
+    /// Returns whether the bounds of `other` lie within the bounds of `self`.
+    pub const fn contains(&self, other: &Self) -> bool {
+        // HACK: address should be const comparable
+        other.start().get() >= self.start().get() && other.endb().get() <= self.endb().get()
+    }
+
     pub const fn is_bounded(&self) -> bool {
         // HACK: addr should be const comparable
         let addr = self.addr().get();
@@ -238,6 +257,16 @@ impl Capability {
         }
         self.is_bounded() && self.set_addr(self.addr().add(len)).is_bounded()
     }
+
+    /// Returns whether the bounds are in order, the address lies within them
+    /// (or at the end bound), and only defined permissions are set.
+    /// Capabilities which aren't well formed are never valid in memory.
+    pub const fn is_well_formed(&self) -> bool {
+        // HACK: addr should be const comparable
+        self.start().get() <= self.endb().get()
+            && self.is_bounded()
+            && Permissions::all().contains(self.perms)
+    }
 }
 
 impl Ty for Capability {
@@ -261,6 +290,7 @@ impl Ty for Capability {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[must_use]
 pub struct TaggedCapability {
     capa: Capability,
@@ -309,7 +339,7 @@ impl TaggedCapability {
     pub const fn set_addr(self, new: Address) -> Self {
         Self {
             capa: self.capa.set_addr(new),
-            valid: self.valid && self.otype().is_unsealed(),
+            valid: self.valid && self.is_unsealed(),
         }
     }
 
@@ -322,21 +352,16 @@ impl TaggedCapability {
     }
 
     pub const fn set_bounds(self, start: Address, endb: Address) -> Self {
+        let capa = Capability {
+            addr: self.addr(),
+            start,
+            endb,
+            perms: self.perms(),
+            otype: self.otype(),
+        };
         // HACK: address should be const comparable
-        let valid = self.otype().is_unsealed()
-            && start.get() >= self.start().get()
-            && endb.get() <= self.endb().get()
-            && start.get() <= endb.get();
-        Self {
-            capa: Capability {
-                addr: self.addr(),
-                start,
-                endb,
-                perms: self.perms(),
-                otype: self.otype(),
-            },
-            valid,
-        }
+        let valid = self.is_unsealed() && self.capa.contains(&capa) && start.get() <= endb.get();
+        Self { capa, valid }
     }
 
     pub const fn perms(self) -> Permissions {
@@ -352,7 +377,7 @@ impl TaggedCapability {
                 perms,
                 otype: self.capa.otype,
             },
-            valid: self.is_valid() && self.otype().is_unsealed() && self.perms().contains(perms),
+            valid: self.is_valid() && self.is_unsealed() && self.perms().contains(perms),
         }
     }
 
@@ -365,15 +390,65 @@ impl TaggedCapability {
         root
     }
 
+    /// Derive a capability from `self` with the given address, bounds and
+    /// permissions. The result is invalid if it would grant more authority
+    /// than `self`.
+    pub const fn derive(
+        self,
+        addr: Address,
+        start: Address,
+        endb: Address,
+        perms: Permissions,
+    ) -> Self {
+        let derived = self.set_bounds(start, endb).set_addr(addr).set_perms(perms);
+        Self {
+            capa: derived.capa,
+            valid: self.is_valid() && derived.is_valid(),
+        }
+    }
+
+    /// Like [`derive`](Self::derive), but returns why the derivation failed.
+    pub const fn try_derive(
+        self,
+        addr: Address,
+        start: Address,
+        endb: Address,
+        perms: Permissions,
+    ) -> Result<Self, DeriveErr> {
+        // HACK: address should be const comparable
+        if !self.is_valid() {
+            Err(DeriveErr::Invalid)
+        } else if self.is_sealed() {
+            Err(DeriveErr::Sealed)
+        } else if start.get() > endb.get()
+            || start.get() < self.start().get()
+            || endb.get() > self.endb().get()
+        {
+            Err(DeriveErr::Bounds)
+        } else if !self.perms().contains(perms) {
+            Err(DeriveErr::Perms)
+        } else {
+            Ok(self.derive(addr, start, endb, perms))
+        }
+    }
+
     pub const fn otype(self) -> OType {
         self.capa.otype()
     }
 
+    pub const fn is_sealed(&self) -> bool {
+        self.otype().is_sealed()
+    }
+
+    pub const fn is_unsealed(&self) -> bool {
+        self.otype().is_unsealed()
+    }
+
     pub const fn seal(mut self, with: Self) -> Self {
         let mut valid = self.is_valid()
-            && self.otype().is_unsealed()
+            && self.is_unsealed()
             && with.is_valid()
-            && with.otype().is_unsealed()
+            && with.is_unsealed()
             && with.is_bounded()
             && with.perms().contains(Permissions::SEAL);
         if let Some(otype) = OType::from_addr(with.addr()) {
@@ -387,9 +462,9 @@ impl TaggedCapability {
 
     pub const fn unseal(mut self, with: Self) -> Self {
         self.valid = self.is_valid()
-            && self.otype().is_sealed()
+            && self.is_sealed()
             && with.is_valid()
-            && with.otype().is_unsealed()
+            && with.is_unsealed()
             && with.is_bounded()
             && with.perms().contains(Permissions::UNSEAL)
             && self.otype().get_addr().get() == with.addr().get();
@@ -419,15 +494,20 @@ impl TaggedCapability {
     }
 
     pub const fn check_given_access(&self, access: MemAccess) -> Result<(), Exception> {
+        if self.is_valid() && self.is_sealed() {
+            return Err(Exception::CapabilitySealed {
+                otype: self.otype(),
+            });
+        }
         if self.is_valid()
-            && self.otype().is_unsealed()
+            && self.is_unsealed()
             && access.is_bounded()
             && access.perms_grant()
             && access.is_aligned()
         {
             Ok(())
         } else {
-            Err(Exception::InvalidMemAccess { access })
+            Err(Exception::InvalidMemAccess { access, pc: None })
         }
     }
 
@@ -441,6 +521,19 @@ impl TaggedCapability {
     }
 }
 
+/// Reason [`TaggedCapability::try_derive`] failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeriveErr {
+    /// The parent capability is invalid.
+    Invalid,
+    /// The parent capability is sealed.
+    Sealed,
+    /// The bounds are reversed or exceed the parent's bounds.
+    Bounds,
+    /// The permissions exceed the parent's permissions.
+    Perms,
+}
+
 impl Ty for TaggedCapability {
     const LAYOUT: Layout = Layout {
         size: Capability::LAYOUT.size,
@@ -450,7 +543,7 @@ impl Ty for TaggedCapability {
     fn read(src: &[u8], addr: Address, valid: &BitSlice<u8>) -> Result<Self, Exception> {
         debug_assert_eq!(valid.len(), 1);
         let capa = Capability::read(src, addr, valid)?;
-        let valid = valid[0];
+        let valid = valid[0] && capa.is_well_formed();
         Ok(Self::new(capa, valid))
     }
 
@@ -462,11 +555,29 @@ impl Ty for TaggedCapability {
     ) -> Result<(), Exception> {
         debug_assert_eq!(valid.len(), 1);
         self.capa.write(dst, addr, valid)?;
-        *valid.get_mut(0).unwrap() = self.is_valid();
+        *valid.get_mut(0).unwrap() = self.is_valid() && self.capa.is_well_formed();
         Ok(())
     }
 }
 
+impl fmt::Display for TaggedCapability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.valid {
+            write!(
+                f,
+                "cap[{start}..{endb} @{addr} {perms} {otype}]",
+                start = self.start(),
+                endb = self.endb(),
+                addr = self.addr(),
+                perms = self.perms(),
+                otype = self.otype(),
+            )
+        } else {
+            write!(f, "0x{:016x}", self.to_ugran())
+        }
+    }
+}
+
 impl fmt::Debug for TaggedCapability {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.valid {
@@ -490,25 +601,26 @@ impl fmt::Debug for TaggedCapability {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct OType(u8);
 
 impl OType {
-    pub const BITS: u8 = 8;
+    pub const BITS: u8 = 6;
 
     /// Addresses with this alignment are representable as object types.
     pub const VALID_ALIGN: Align = Align::from_repr(Address::BITS - Self::BITS).unwrap();
 
-    const ALIGNED_COUNT: Align = Align::from_repr(Self::BITS as _).unwrap();
-
-    pub const UNSEALED: Self = Self(u8::MAX);
+    pub const UNSEALED: Self = Self(u8::MAX >> (u8::BITS - Self::BITS as u32));
 
+    /// Only the low [`OType::BITS`] bits of `repr` are kept.
     pub const fn new(repr: u8) -> Self {
-        Self(repr)
+        Self(repr & Self::UNSEALED.0)
     }
 
     pub const fn from_addr(addr: Address) -> Option<Self> {
         if addr.is_aligned_to(Self::VALID_ALIGN) {
-            Some(Self::new((addr.get() / Self::ALIGNED_COUNT.get()) as u8))
+            Some(Self::new((addr.get() / Self::VALID_ALIGN.get()) as u8))
         } else {
             None
         }
@@ -519,7 +631,7 @@ impl OType {
     }
 
     pub const fn get_addr(self) -> Address {
-        Address(self.get() as UAddr * Self::ALIGNED_COUNT.get())
+        Address(self.get() as UAddr * Self::VALID_ALIGN.get())
     }
 
     pub const fn is_sealed(self) -> bool {
@@ -548,6 +660,16 @@ impl Ty for OType {
     }
 }
 
+impl fmt::Display for OType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_sealed() {
+            write!(f, "sealed {}", self.get_addr())
+        } else {
+            write!(f, "unsealed")
+        }
+    }
+}
+
 impl fmt::Debug for OType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut dbg = f.debug_tuple("OType");
@@ -564,32 +686,79 @@ impl fmt::Debug for OType {
 
 bitflags! {
     #[derive(Clone, Copy, PartialEq, Eq)]
-    pub struct Permissions: u8 {
-        const READ = 0b00000001;
-        const WRITE = 0b00000010;
-        const EXEC = 0b00000100;
-        const SEAL = 0b00001000;
-        const UNSEAL = 0b00010000;
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Permissions: u16 {
+        const READ = 0b000000001;
+        const WRITE = 0b000000010;
+        const EXEC = 0b000000100;
+        const SEAL = 0b000001000;
+        const UNSEAL = 0b000010000;
+        const LOAD_CAP = 0b000100000;
+        const STORE_CAP = 0b001000000;
+        /// Capabilities lacking this permission are local, and may only be
+        /// stored through capabilities with [`Permissions::STORE_LOCAL`].
+        const GLOBAL = 0b010000000;
+        /// Local capabilities may be stored through capabilities with this
+        /// permission. Storing them through any other capability clears their
+        /// tag.
+        const STORE_LOCAL = 0b100000000;
     }
 }
 
 impl Permissions {
-    pub const BITS: u8 = 8;
+    /// Width of the permission field, which has room for more permissions
+    /// than are defined.
+    pub const BITS: u8 = 10;
+
+    /// Characters shown for each permission when granted, in display order.
+    const CHARS: [(Self, char); 8] = [
+        (Self::READ, 'r'),
+        (Self::WRITE, 'w'),
+        (Self::EXEC, 'x'),
+        (Self::SEAL, 's'),
+        (Self::UNSEAL, 'u'),
+        (Self::LOAD_CAP, 'R'),
+        (Self::STORE_CAP, 'W'),
+        (Self::STORE_LOCAL, 'L'),
+    ];
+    const NOPE: char = '-';
+
+    /// Parse permissions in the format they are displayed in, such as
+    /// `rwxsuRWLg`. Trailing permissions may be omitted, so `r-x` is read and
+    /// execute, and is local.
+    pub fn from_display(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        let mut perms = Self::empty();
+        for (perm, chr) in Self::CHARS {
+            match chars.next() {
+                Some(c) if c == chr => perms |= perm,
+                Some(Self::NOPE) | None => (),
+                Some(_) => return None,
+            }
+        }
+        match chars.next() {
+            Some('g') => perms |= Self::GLOBAL,
+            Some('l') | None => (),
+            Some(_) => return None,
+        }
+        chars.next().is_none().then_some(perms)
+    }
 
     pub const fn grants_access(&self, kind: MemAccessKind) -> bool {
         match kind {
             MemAccessKind::Read => self.contains(Self::READ),
             MemAccessKind::Write => self.contains(Self::WRITE),
             MemAccessKind::Execute => self.contains(Self::EXEC),
+            MemAccessKind::StoreCap => self.contains(Self::WRITE.union(Self::STORE_CAP)),
         }
     }
 }
 
 impl Ty for Permissions {
-    const LAYOUT: Layout = u8::LAYOUT;
+    const LAYOUT: Layout = u16::LAYOUT;
 
     fn read(src: &[u8], addr: Address, valid: &BitSlice<u8>) -> Result<Self, Exception> {
-        Ok(Self::from_bits_truncate(u8::read(src, addr, valid)?))
+        Ok(Self::from_bits_retain(u16::read(src, addr, valid)?))
     }
 
     fn write(
@@ -598,23 +767,21 @@ impl Ty for Permissions {
         addr: Address,
         valid: &mut BitSlice<u8>,
     ) -> Result<(), Exception> {
-        let repr: u8 = self.bits();
+        let repr: u16 = self.bits();
         repr.write(dst, addr, valid)
     }
 }
 
 impl fmt::Display for Permissions {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        const NOPE: char = '-';
-        for (perm, chr) in [
-            (Permissions::READ, 'r'),
-            (Permissions::WRITE, 'w'),
-            (Permissions::EXEC, 'x'),
-            (Permissions::SEAL, 's'),
-            (Permissions::UNSEAL, 'u'),
-        ] {
-            f.write_char(if self.contains(perm) { chr } else { NOPE })?;
+        for (perm, chr) in Self::CHARS {
+            f.write_char(if self.contains(perm) { chr } else { Self::NOPE })?;
         }
+        f.write_char(if self.contains(Permissions::GLOBAL) {
+            'g'
+        } else {
+            'l'
+        })?;
         Ok(())
     }
 }
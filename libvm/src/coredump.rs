@@ -0,0 +1,94 @@
+use bitvec::bitbox;
+use bitvec::order::Lsb0;
+
+use std::io::{self, Read, Write};
+
+use crate::capability::{Capability, TaggedCapability};
+use crate::int::{UAddr, UGran, UGRAN_SIZE};
+use crate::mem::{Memory, TagController};
+use crate::registers::Registers;
+
+/// Identifies a core image, and its format version.
+const MAGIC: &[u8; 8] = b"FRUTCOR1";
+
+/* core image layout, integers are little endian:
+ *   magic          8 bytes
+ *   granules       UAddr
+ *   register count u8
+ *   memory         granules * UGRAN_SIZE bytes
+ *   tags           one bit per register then per granule, packed lsb first
+ *   registers      register count * UGran
+ */
+
+impl Memory {
+    /// Write the contents of memory, tags, and registers as a core image
+    /// which [`Memory::load_core`] can read back.
+    pub fn dump_core<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let granules = UAddr::try_from(self.mem.len() / usize::from(UGRAN_SIZE))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "memory too large"))?;
+        w.write_all(MAGIC)?;
+        w.write_all(&granules.to_le_bytes())?;
+        w.write_all(&[Registers::COUNT])?;
+        w.write_all(&self.mem)?;
+
+        let mut tags = vec![0_u8; self.tags.mem.len().div_ceil(8)];
+        for idx in self.tags.mem.iter_ones() {
+            tags[idx / 8] |= 1 << (idx % 8);
+        }
+        w.write_all(&tags)?;
+
+        for reg in 0..Registers::COUNT {
+            let val = self.regs.read_data(reg).unwrap();
+            w.write_all(&val.to_le_bytes())?;
+        }
+        w.flush()
+    }
+
+    /// Read a core image written by [`Memory::dump_core`]. Execution state
+    /// which isn't part of the image, such as hooks and budgets, is reset.
+    pub fn load_core<R: Read>(mut r: R) -> io::Result<Self> {
+        fn invalid(msg: &str) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        }
+
+        let mut magic = [0; MAGIC.len()];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a core image"));
+        }
+        let mut granules = [0; size_of::<UAddr>()];
+        r.read_exact(&mut granules)?;
+        let granules = UAddr::from_le_bytes(granules);
+        let mut reg_count = [0];
+        r.read_exact(&mut reg_count)?;
+        if reg_count[0] != Registers::COUNT {
+            return Err(invalid("core image has a different number of registers"));
+        }
+
+        let mem_len = granules
+            .checked_mul(UAddr::from(UGRAN_SIZE))
+            .ok_or_else(|| invalid("core image memory exceeds the address space"))?;
+        let mut bytes = vec![0; usize::from(mem_len)].into_boxed_slice();
+        r.read_exact(&mut bytes)?;
+
+        let mut tags = TagController::new(granules).map_err(|_| invalid("tag count overflow"))?;
+        let mut packed = vec![0_u8; tags.mem.len().div_ceil(8)];
+        r.read_exact(&mut packed)?;
+        let mut bits = bitbox![u8, Lsb0; 0; tags.mem.len()];
+        for (idx, mut bit) in bits.iter_mut().enumerate() {
+            *bit = packed[idx / 8] & (1 << (idx % 8)) != 0;
+        }
+        tags.mem = bits;
+
+        let mut mem = Self::from_parts(bytes, tags);
+        for reg in 0..Registers::COUNT {
+            let mut val = [0; UGRAN_SIZE as _];
+            r.read_exact(&mut val)?;
+            let valid = mem.tags.read_reg(reg).unwrap();
+            let tcap =
+                TaggedCapability::new(Capability::from_ugran(UGran::from_le_bytes(val)), valid);
+            mem.regs.write(&mut mem.tags, reg, tcap).unwrap();
+        }
+        Ok(mem)
+    }
+}
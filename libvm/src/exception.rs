@@ -1,25 +1,142 @@
 use core::fmt;
 
+use crate::abi::Layout;
 use crate::access::{MemAccess, RegAccess};
 use crate::alloc::{AllocErr, AllocErrKind};
+use crate::capability::{Address, OType};
+use crate::int::{UAddr, UGran};
+use crate::op::OpKind;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Exception {
-    InvalidOpKind { byte: u8 },
+    InvalidOpKind {
+        byte: u8,
+    },
 
-    InvalidSyscall { byte: u8 },
+    InvalidSyscall {
+        byte: u8,
+    },
 
-    InvalidAllocStrategy { byte: u8 },
+    InvalidAllocStrategy {
+        byte: u8,
+    },
 
-    InvalidMemAccess { access: MemAccess },
+    InvalidAllocInitFlags {
+        flags: u8,
+    },
 
-    InvalidRegAccess { access: RegAccess },
+    /// `pc` is the address of the operation which made the access, if it was
+    /// made while executing one.
+    InvalidMemAccess {
+        access: MemAccess,
+        pc: Option<Address>,
+    },
 
-    AllocErr { err: AllocErr },
+    InvalidRegAccess {
+        access: RegAccess,
+    },
+
+    AllocErr {
+        err: AllocErr,
+    },
+
+    IntegerOverflow {
+        op: OpKind,
+        a: UGran,
+        b: UGran,
+    },
+
+    LayoutOverflow {
+        layout: Layout,
+        count: UAddr,
+    },
+
+    CapabilitySealed {
+        otype: OType,
+    },
+
+    UninitializedRead {
+        addr: Address,
+    },
+
+    BudgetExceeded,
+
+    /// An allocation starting at `addr` was freed again.
+    DoubleFree {
+        addr: Address,
+    },
+
+    /// Memory at `addr` was accessed through a capability revoked when the
+    /// memory was freed.
+    UseAfterFree {
+        addr: Address,
+    },
+
+    /// An allocator's bookkeeping is inconsistent, such as the top of its
+    /// allocations lying at `addr`, outside its region.
+    InvalidAllocState {
+        addr: Address,
+    },
 
     ProcessExit,
 }
 
+impl Exception {
+    /// Stable code identifying the kind of exception, suitable for a process
+    /// exit status. Codes 0 and 1 are left for success and other failures.
+    ///
+    /// | code | exception                |
+    /// |------|--------------------------|
+    /// | 0    | `ProcessExit`            |
+    /// | 2    | `InvalidOpKind`          |
+    /// | 3    | `InvalidSyscall`         |
+    /// | 4    | `InvalidAllocStrategy`   |
+    /// | 5    | `InvalidMemAccess`       |
+    /// | 6    | `InvalidRegAccess`       |
+    /// | 7    | `AllocErr`               |
+    /// | 8    | `IntegerOverflow`        |
+    /// | 9    | `LayoutOverflow`         |
+    /// | 10   | `CapabilitySealed`       |
+    /// | 11   | `UninitializedRead`      |
+    /// | 12   | `BudgetExceeded`         |
+    /// | 13   | `InvalidAllocInitFlags`  |
+    /// | 14   | `DoubleFree`             |
+    /// | 15   | `UseAfterFree`           |
+    /// | 16   | `InvalidAllocState`      |
+    pub const fn to_code(&self) -> u8 {
+        match self {
+            Self::ProcessExit => 0,
+            Self::InvalidOpKind { .. } => 2,
+            Self::InvalidSyscall { .. } => 3,
+            Self::InvalidAllocStrategy { .. } => 4,
+            Self::InvalidMemAccess { .. } => 5,
+            Self::InvalidRegAccess { .. } => 6,
+            Self::AllocErr { .. } => 7,
+            Self::IntegerOverflow { .. } => 8,
+            Self::LayoutOverflow { .. } => 9,
+            Self::CapabilitySealed { .. } => 10,
+            Self::UninitializedRead { .. } => 11,
+            Self::BudgetExceeded => 12,
+            Self::InvalidAllocInitFlags { .. } => 13,
+            Self::DoubleFree { .. } => 14,
+            Self::UseAfterFree { .. } => 15,
+            Self::InvalidAllocState { .. } => 16,
+        }
+    }
+
+    /// Attribute a memory access fault to the operation at `pc`, unless it
+    /// already is.
+    pub(crate) const fn at_pc(self, pc: Address) -> Self {
+        match self {
+            Self::InvalidMemAccess { access, pc: None } => Self::InvalidMemAccess {
+                access,
+                pc: Some(pc),
+            },
+            other => other,
+        }
+    }
+}
+
 impl fmt::Display for Exception {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -35,7 +152,14 @@ impl fmt::Display for Exception {
                 write!(f, "invalid allocation strategy {byte}")?;
             }
 
-            Self::InvalidMemAccess { access } => {
+            Self::InvalidAllocInitFlags { flags } => {
+                write!(f, "invalid allocator init flags {flags:#010b}")?;
+            }
+
+            Self::InvalidMemAccess { access, pc } => {
+                if let Some(pc) = pc {
+                    write!(f, "at {pc}: ")?;
+                }
                 write!(
                     f,
                     "invalid memory access ({kind} of {perms}) of ",
@@ -64,7 +188,7 @@ impl fmt::Display for Exception {
                     write!(f, "data used where capability required")?;
                     return Ok(());
                 }
-                if access.tcap.otype().is_sealed() {
+                if access.tcap.is_sealed() {
                     write!(f, "sealed capabilities cannot be used")?;
                     return Ok(());
                 }
@@ -116,16 +240,58 @@ impl fmt::Display for Exception {
             Self::AllocErr { err } => {
                 write!(
                     f,
-                    "allocator reported error: stats = {stats:?}, requested = {requested:?}: ",
+                    "allocator reported error: {stats}, requested {requested}: ",
                     stats = err.stats,
                     requested = err.requested
                 )?;
                 match err.kind {
                     AllocErrKind::NotEnoughMem => write!(f, "not enough memory")?,
                     AllocErrKind::Oom => write!(f, "out of memory")?,
+                    AllocErrKind::InvalidLayout => write!(f, "invalid layout")?,
+                    AllocErrKind::OutOfOrderFree => {
+                        write!(f, "freed allocation is not the most recent")?;
+                    }
+                    AllocErrKind::Unsupported => {
+                        write!(f, "not supported by the allocation strategy")?;
+                    }
                 }
             }
 
+            Self::IntegerOverflow { op, a, b } => {
+                write!(f, "integer overflow in {op} with operands {a} and {b}")?;
+            }
+
+            Self::CapabilitySealed { otype } => {
+                write!(
+                    f,
+                    "capability is sealed with object type {addr} and must be unsealed before use",
+                    addr = otype.get_addr()
+                )?;
+            }
+
+            Self::LayoutOverflow { layout, count } => {
+                write!(
+                    f,
+                    "size of array of {count} elements with size {size} and alignment {align} overflows",
+                    size = layout.size,
+                    align = layout.align,
+                )?;
+            }
+
+            Self::UninitializedRead { addr } => {
+                write!(f, "read of uninitialized memory at {addr}")?;
+            }
+
+            Self::BudgetExceeded => write!(f, "instruction budget exceeded")?,
+
+            Self::DoubleFree { addr } => write!(f, "allocation at {addr} freed twice")?,
+
+            Self::UseAfterFree { addr } => write!(f, "use after free at {addr}")?,
+
+            Self::InvalidAllocState { addr } => {
+                write!(f, "allocator state is corrupt at address {addr}")?;
+            }
+
             Self::ProcessExit => write!(f, "process exited")?,
         }
         Ok(())
@@ -1,5 +1,8 @@
 use core::mem;
 
+/* NOTE: these aliases are the single place the width of a granule and an
+ * address are chosen. the encoding of capabilities is checked against them at
+ * compile time, see capability.rs */
 pub type UGran = u64;
 pub type SGran = i64;
 pub type UAddr = u16;
@@ -7,6 +10,7 @@ pub type SAddr = i16;
 
 pub const UADDR_SIZE: u8 = mem::size_of::<UAddr>() as _;
 pub const UGRAN_SIZE: u8 = mem::size_of::<UGran>() as _;
+pub const UGRAN_BITS: u32 = UGran::BITS;
 
 pub const UNINIT: UAddr = UAddr::from_le_bytes([UNINIT_BYTE; UADDR_SIZE as _]);
 pub const UNINIT_BYTE: u8 = 0x55;
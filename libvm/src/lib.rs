@@ -1,10 +1,12 @@
-#![feature(int_roundings, const_option)]
 #![deny(elided_lifetimes_in_paths)]
+// integer type aliases are expected to change, so keep conversions fallible
+#![allow(clippy::unnecessary_fallible_conversions)]
 
 pub mod abi;
 pub mod access;
 pub mod alloc;
 pub mod capability;
+pub mod coredump;
 pub mod exception;
 pub mod int;
 pub mod mem;
@@ -13,6 +15,8 @@ pub mod process;
 pub mod registers;
 pub mod revoke;
 pub mod syscall;
+#[cfg(feature = "viz")]
+pub mod viz;
 
 #[cfg(test)]
 pub mod tests;
@@ -5,21 +5,41 @@ use bitvec::order::Lsb0;
 use bitvec::slice::BitSlice;
 use tracing::{span, Level};
 
+use core::fmt;
+use core::ops::Range;
+use std::collections::VecDeque;
+
 use crate::abi::{self, Align, Layout, Ty};
 use crate::access::MemAccessKind;
 use crate::alloc::{self, InitFlags, Strategy};
 use crate::capability::{Address, Capability, Granule, OType, Permissions, TaggedCapability};
 use crate::exception::Exception;
-use crate::int::{UAddr, UGRAN_SIZE, UNINIT};
-use crate::op::Op;
+use crate::int::{UAddr, UGRAN_SIZE, UNINIT, UNINIT_BYTE};
+use crate::op::{Op, OpKind};
+use crate::process::Dispatch;
 use crate::registers::{Register, Registers};
+use crate::revoke::Quarantine;
+
+/// Callback invoked with the program counter address and operation before
+/// each operation is executed.
+pub type TraceHook = Box<dyn FnMut(Address, &Op)>;
 
-#[derive(Debug)]
 pub struct Memory {
     pub mem: Box<[u8]>,
     pub regs: Registers,
     pub tags: TagController,
     pub root: TaggedCapability,
+    pub(crate) trace_hook: Option<TraceHook>,
+    pub(crate) op_histogram: [u64; OpKind::COUNT as _],
+    pub(crate) dispatch: Dispatch,
+    /// Last decoded operation, and the program counter it was fetched with.
+    pub(crate) icache: Option<(TaggedCapability, Op)>,
+    pub(crate) op_budget: Option<u64>,
+    /// Memory freed by allocators which haven't revoked it yet.
+    pub(crate) quarantine: Quarantine,
+    /// Spans most recently freed by allocators, newest last, used to explain
+    /// why a capability is invalid.
+    pub(crate) recently_freed: VecDeque<Range<Address>>,
 }
 
 impl Memory {
@@ -27,27 +47,45 @@ impl Memory {
         granules: UAddr,
         stack_size: UAddr,
         init: I,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_data(granules, stack_size, init, [].iter())
+    }
+
+    /// Like [`Memory::new`], but also allocate a data segment after the
+    /// program holding the bytes of `data`. A capability to the start of the
+    /// segment is placed in [`Register::Z1`], which is left invalid if `data`
+    /// is empty.
+    pub fn new_with_data<
+        'op,
+        'd,
+        I: Iterator<Item = &'op Op> + ExactSizeIterator,
+        D: Iterator<Item = &'d u8> + ExactSizeIterator,
+    >(
+        granules: UAddr,
+        stack_size: UAddr,
+        init: I,
+        data: D,
     ) -> anyhow::Result<Self> {
         fn log_stats(ator: TaggedCapability, mem: &Memory) -> anyhow::Result<()> {
             let stats = alloc::stat(ator, mem).context("failed to stat allocator")?;
-            tracing::trace!(
-                stats.strategy = format_args!("{:?}", stats.strategy),
-                stats.flags = format_args!("{:?}", stats.flags),
-                stats.bytes_free,
-                "allocator reports stats"
-            );
+            tracing::trace!(stats = format_args!("{stats}"), "allocator reports stats");
             Ok(())
         }
 
         let mem_len = granules
             .checked_mul(UAddr::from(UGRAN_SIZE))
             .and_then(|len| usize::try_from(len).ok())
-            .ok_or(anyhow!("allocated bytes overflow"))?;
+            .ok_or(anyhow!(
+                "{granules} granules of {UGRAN_SIZE} bytes don't fit in the {}-bit address space",
+                Address::BITS
+            ))?;
         let init_elems =
             UAddr::try_from(init.len()).map_err(|_| anyhow!("program length overflow"))?;
         let init_bytes = init_elems
             .checked_mul(Op::LAYOUT.size as _)
             .ok_or(anyhow!("program size overflow"))?;
+        let data_bytes =
+            UAddr::try_from(data.len()).map_err(|_| anyhow!("data segment size overflow"))?;
 
         /* initialize components */
         let span = span!(
@@ -56,35 +94,17 @@ impl Memory {
             mem_len,
             init_program_len = init_elems,
             init_program_bytes = init_bytes,
+            data_bytes,
         );
         let _guard = span.enter();
 
         tracing::debug!("allocating vm memory");
         let bytes = vec![UNINIT as _; mem_len].into_boxed_slice();
-        tracing::debug!("initializing registers");
-        let regs = Registers::new();
         tracing::debug!("initializing tag controller");
         let tags = TagController::new(granules).context("failed to create tag controller")?;
-        let mut mem = Self {
-            mem: bytes,
-            regs,
-            tags,
-            root: TaggedCapability::INVALID,
-        };
+        let mut mem = Self::from_parts(bytes, tags);
 
         /* instantiate root allocator */
-        // set up root capability
-        tracing::debug!("acquiring root capability");
-        mem.root = TaggedCapability::new(
-            Capability::new(
-                Address(0),
-                Address(0),
-                Address(UAddr::try_from(mem_len).expect("converted from UAddr to usize at start of Memory::new, so converting back to UAddr is infallible")),
-                Permissions::all(),
-                OType::UNSEALED,
-            ),
-             true,
-        );
         tracing::debug!("initializing root allocator");
         let root_alloc = alloc::init(
             Strategy::Bump,
@@ -118,6 +138,28 @@ impl Memory {
             .write(&mut mem.tags, Register::Pc as _, pc)
             .unwrap();
 
+        /* write data segment */
+        if data_bytes > 0 {
+            tracing::debug!("allocating data segment");
+            let seg = alloc::alloc(
+                root_alloc,
+                Layout {
+                    size: data_bytes,
+                    align: TaggedCapability::LAYOUT.align,
+                },
+                &mut mem,
+            )
+            .context("failed to allocate data segment")?;
+            log_stats(root_alloc, &mem)?;
+            tracing::debug!(addr = seg.addr().get(), "writing data segment to memory");
+            mem.write_iter(seg, data)
+                .context("failed to write data segment")?;
+            let seg = seg.set_perms(seg.perms().difference(Permissions::EXEC));
+            mem.regs
+                .write(&mut mem.tags, Register::Z1 as _, seg)
+                .unwrap();
+        }
+
         /* instantiate call stack */
         // TODO: stack traditionally grows downward, but the way we do allocations neglects the need for this, since the heap & stack "can't" be shared in a nice way.
         tracing::debug!("allocating program call stack");
@@ -132,8 +174,11 @@ impl Memory {
         .context("failed to allocate init program call stack")?;
         log_stats(root_alloc, &mem)?;
 
-        // write to Sp
-        let sp = call_stack.set_addr(call_stack.endb());
+        // write to Sp. the call stack is local, and may hold local capabilities
+        // since it grants STORE_LOCAL.
+        let sp = call_stack
+            .set_addr(call_stack.endb())
+            .set_perms(call_stack.perms().difference(Permissions::GLOBAL));
         mem.regs
             .write(&mut mem.tags, Register::Sp as _, sp)
             .unwrap();
@@ -146,6 +191,98 @@ impl Memory {
         Ok(mem)
     }
 
+    /// Wrap memory and tags, with empty registers and a root capability
+    /// spanning all of `bytes`.
+    pub(crate) fn from_parts(bytes: Box<[u8]>, tags: TagController) -> Self {
+        tracing::debug!("acquiring root capability");
+        let endb = UAddr::try_from(bytes.len()).expect("memory length is checked to fit UAddr");
+        let root = TaggedCapability::new(
+            Capability::new(
+                Address(0),
+                Address(0),
+                Address(endb),
+                Permissions::all(),
+                OType::UNSEALED,
+            ),
+            true,
+        );
+        Self {
+            mem: bytes,
+            regs: Registers::new(),
+            tags,
+            root,
+            trace_hook: None,
+            op_histogram: [0; OpKind::COUNT as _],
+            dispatch: Dispatch::default(),
+            icache: None,
+            op_budget: None,
+            quarantine: Quarantine::default(),
+            recently_freed: VecDeque::new(),
+        }
+    }
+
+    /// Capability granting all permissions over all of memory, from which
+    /// embedders may derive capabilities to hand to the program.
+    ///
+    /// ```
+    /// use fruticose_vm::capability::{Address, TaggedCapability};
+    /// use fruticose_vm::mem::Memory;
+    /// use fruticose_vm::op::{Op, OpKind};
+    /// use fruticose_vm::registers::Register;
+    ///
+    /// let op = |kind, op1: Register, op2: u64| Op {
+    ///     kind,
+    ///     op1: TaggedCapability::from_ugran(op1 as _),
+    ///     op2: TaggedCapability::from_ugran(op2),
+    ///     op3: TaggedCapability::from_ugran(0),
+    /// };
+    /// let init = [
+    ///     op(OpKind::LoadU8, Register::T0, Register::S0 as _),
+    ///     op(OpKind::LoadI, Register::A2, 0 /* SYS_EXIT */),
+    ///     op(OpKind::Syscall, Register::Zero, 0),
+    /// ];
+    /// let mut mem = Memory::new(128, 0, init.iter())?;
+    ///
+    /// // load a buffer into memory and pass the program a capability to it
+    /// let buf = mem
+    ///     .root_capability()
+    ///     .set_addr(Address(512))
+    ///     .set_bounds(Address(512), Address(516));
+    /// mem.write_bytes(buf, &[42, 1, 2, 3])?;
+    /// mem.set_register(Register::S0, buf)?;
+    ///
+    /// assert!(mem.run(16)?.exited);
+    /// assert_eq!(mem.register(Register::T0)?, TaggedCapability::from_ugran(42));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub const fn root_capability(&self) -> TaggedCapability {
+        self.root
+    }
+
+    /// Read the tagged contents of `reg`.
+    pub fn register(&self, reg: Register) -> Result<TaggedCapability, Exception> {
+        self.regs.read(&self.tags, reg as _)
+    }
+
+    /// Write `tcap` to `reg`, keeping its tag.
+    pub fn set_register(&mut self, reg: Register, tcap: TaggedCapability) -> Result<(), Exception> {
+        self.regs.write(&mut self.tags, reg as _, tcap)
+    }
+
+    /// Validity tags of registers and memory granules.
+    pub const fn tags(&self) -> &TagController {
+        &self.tags
+    }
+
+    /// Fraction of memory granules holding valid capabilities, from 0 to 1.
+    pub fn tag_density(&self) -> f64 {
+        let granules = self.tags.mem.len() - usize::from(Registers::COUNT);
+        if granules == 0 {
+            return 0.0;
+        }
+        self.tags.count_valid() as f64 / granules as f64
+    }
+
     pub fn read<T: Ty>(&self, mut src: TaggedCapability) -> Result<T, Exception> {
         let layout = T::LAYOUT;
         let access = src.access(MemAccessKind::Read, layout.align, Some(layout.size));
@@ -155,11 +292,16 @@ impl Memory {
         src = src.set_bounds(src.addr(), src.addr().add(layout.size));
 
         let bytes = Self::slice_raw(&self.mem, src, layout)
-            .ok_or(Exception::InvalidMemAccess { access })?;
+            .ok_or(Exception::InvalidMemAccess { access, pc: None })?;
+        if let Some(gran) = self.tags.first_uninit(src.addr(), layout.size) {
+            return Err(Exception::UninitializedRead {
+                addr: gran.addr().map_or(src.addr(), |addr| src.addr().max(addr)),
+            });
+        }
         let tags = self
             .tags
             .grans(src.addr(), layout.size)
-            .ok_or(Exception::InvalidMemAccess { access })?;
+            .ok_or(Exception::InvalidMemAccess { access, pc: None })?;
         T::read(bytes, src.addr(), tags)
     }
 
@@ -172,11 +314,13 @@ impl Memory {
         dst = dst.set_bounds(dst.addr(), dst.addr().add(layout.size));
 
         let bytes = Self::slice_mut_raw(&mut self.mem, dst, layout)
-            .ok_or(Exception::InvalidMemAccess { access })?;
+            .ok_or(Exception::InvalidMemAccess { access, pc: None })?;
+        Self::invalidate_icache(&mut self.icache, dst.addr(), layout.size);
+        self.tags.set_init(dst.addr(), layout.size);
         let tags = self
             .tags
             .grans_mut(dst.addr(), layout.size)
-            .ok_or(Exception::InvalidMemAccess { access })?;
+            .ok_or(Exception::InvalidMemAccess { access, pc: None })?;
         val.write(bytes, dst.addr(), tags)
     }
 
@@ -191,7 +335,7 @@ impl Memory {
         let len = UAddr::try_from(vals.len())
             .ok()
             .and_then(|size| size.checked_mul(layout.size))
-            .ok_or(Exception::InvalidMemAccess { access })?;
+            .ok_or(Exception::InvalidMemAccess { access, pc: None })?;
         access.len = Some(len);
         dst.check_given_access(access)?;
 
@@ -209,14 +353,286 @@ impl Memory {
         count: UAddr,
         byte: u8,
     ) -> Result<(), Exception> {
-        dst.check_access(MemAccessKind::Write, u8::LAYOUT.align, Some(count))?;
+        let layout = Layout {
+            size: count,
+            align: u8::LAYOUT.align,
+        };
+        let access = dst.access(MemAccessKind::Write, layout.align, Some(layout.size));
+        dst.check_given_access(access)?;
 
-        // casts assume that bounds of capability lie within bounds of self.mem
-        let start_idx = dst.addr().get() as usize;
-        let dst_slice = &mut self.mem[start_idx..][..count as usize];
+        // capability bounds may still exceed the bounds of self.mem
+        let dst_slice = Self::slice_mut_raw(&mut self.mem, dst, layout)
+            .ok_or(Exception::InvalidMemAccess { access, pc: None })?;
         dst_slice.fill(byte);
+        Self::invalidate_icache(&mut self.icache, dst.addr(), layout.size);
+        self.tags.set_init(dst.addr(), layout.size);
+        Ok(())
+    }
+
+    /// Read `len` raw bytes starting at the address of `src`.
+    pub fn read_bytes(&self, src: TaggedCapability, len: UAddr) -> Result<Vec<u8>, Exception> {
+        let layout = Layout {
+            size: len,
+            align: u8::LAYOUT.align,
+        };
+        let access = src.access(MemAccessKind::Read, layout.align, Some(layout.size));
+        src.check_given_access(access)?;
+
+        // capability bounds may still exceed the bounds of self.mem
+        let bytes = Self::slice_raw(&self.mem, src, layout)
+            .ok_or(Exception::InvalidMemAccess { access, pc: None })?;
+        if let Some(gran) = self.tags.first_uninit(src.addr(), layout.size) {
+            return Err(Exception::UninitializedRead {
+                addr: gran.addr().map_or(src.addr(), |addr| src.addr().max(addr)),
+            });
+        }
+        Ok(bytes.to_vec())
+    }
+
+    /// Write `bytes` starting at the address of `dst`. Tags of granules written
+    /// to are cleared.
+    pub fn write_bytes(&mut self, dst: TaggedCapability, bytes: &[u8]) -> Result<(), Exception> {
+        let mut access = dst.access(MemAccessKind::Write, u8::LAYOUT.align, None);
+        let len = UAddr::try_from(bytes.len())
+            .map_err(|_| Exception::InvalidMemAccess { access, pc: None })?;
+        access.len = Some(len);
+        dst.check_given_access(access)?;
+        let layout = Layout {
+            size: len,
+            align: u8::LAYOUT.align,
+        };
+
+        // capability bounds may still exceed the bounds of self.mem
+        let dst_slice = Self::slice_mut_raw(&mut self.mem, dst, layout)
+            .ok_or(Exception::InvalidMemAccess { access, pc: None })?;
+        dst_slice.copy_from_slice(bytes);
+        Self::invalidate_icache(&mut self.icache, dst.addr(), len);
+        if len != 0 {
+            self.tags
+                .grans_mut(dst.addr(), len)
+                .ok_or(Exception::InvalidMemAccess { access, pc: None })?
+                .fill(false);
+        }
+        self.tags.set_init(dst.addr(), len);
+        Ok(())
+    }
+
+    /// Fill the memory spanned by `dst` with [`UNINIT_BYTE`], and forget that
+    /// the granules it fully covers were initialized.
+    pub(crate) fn poison(&mut self, dst: TaggedCapability) -> Result<(), Exception> {
+        self.memset(dst, dst.span_len(), UNINIT_BYTE)?;
+        let gran_align = TaggedCapability::LAYOUT.align;
+        let start = dst.addr().align_to(gran_align);
+        let endb = dst.addr().add(dst.span_len()).align_down(gran_align);
+        if start < endb {
+            self.tags.set_uninit(start, endb.get() - start.get());
+        }
         Ok(())
     }
+
+    /// Copy `count` bytes from `src` to `dst`, which may overlap.
+    ///
+    /// Tags are copied for granules of `dst` which are entirely overwritten,
+    /// provided `src` grants [`Permissions::LOAD_CAP`], `dst` grants
+    /// [`Permissions::STORE_CAP`], and local capabilities are only stored
+    /// through a `dst` granting [`Permissions::STORE_LOCAL`]. Tags of other granules written to are cleared.
+    pub fn memcpy(
+        &mut self,
+        dst: TaggedCapability,
+        src: TaggedCapability,
+        count: UAddr,
+    ) -> Result<(), Exception> {
+        let layout = Layout {
+            size: count,
+            align: u8::LAYOUT.align,
+        };
+        let src_access = src.access(MemAccessKind::Read, layout.align, Some(layout.size));
+        src.check_given_access(src_access)?;
+        let dst_access = dst.access(MemAccessKind::Write, layout.align, Some(layout.size));
+        dst.check_given_access(dst_access)?;
+
+        // capability bounds may still exceed the bounds of self.mem
+        Self::slice_raw(&self.mem, src, layout).ok_or(Exception::InvalidMemAccess {
+            access: src_access,
+            pc: None,
+        })?;
+        Self::slice_mut_raw(&mut self.mem, dst, layout).ok_or(Exception::InvalidMemAccess {
+            access: dst_access,
+            pc: None,
+        })?;
+
+        let gran_size = usize::from(UGRAN_SIZE);
+        let src_idx = usize::from(src.addr().get());
+        let dst_idx = usize::from(dst.addr().get());
+        let count_idx = usize::from(count);
+        let keep_tags = src_idx % gran_size == dst_idx % gran_size
+            && src.perms().contains(Permissions::LOAD_CAP)
+            && dst.perms().contains(Permissions::STORE_CAP);
+
+        /* decide destination tags before copying, since the source may be
+         * overwritten if the ranges overlap */
+        let first_gran = dst_idx / gran_size;
+        let endb_gran = (dst_idx + count_idx).div_ceil(gran_size);
+        let mut tags = Vec::with_capacity(endb_gran - first_gran);
+        for gran in first_gran..endb_gran {
+            let gran_start = gran * gran_size;
+            let covered = gran_start >= dst_idx && gran_start + gran_size <= dst_idx + count_idx;
+            let valid = if keep_tags && covered {
+                let src_start = gran_start - dst_idx + src_idx;
+                let src_addr =
+                    Address(UAddr::try_from(src_start).expect("source granule is within memory"));
+                let tags = self.tags.grans(src_addr, UGRAN_SIZE.into()).ok_or(
+                    Exception::InvalidMemAccess {
+                        access: src_access,
+                        pc: None,
+                    },
+                )?;
+                let tcap = TaggedCapability::read(
+                    &self.mem[src_start..src_start + gran_size],
+                    src_addr,
+                    tags,
+                )?;
+                tcap.is_valid()
+                    && (tcap.perms().contains(Permissions::GLOBAL)
+                        || dst.perms().contains(Permissions::STORE_LOCAL))
+            } else {
+                false
+            };
+            tags.push(valid);
+        }
+
+        self.mem.copy_within(src_idx..src_idx + count_idx, dst_idx);
+        Self::invalidate_icache(&mut self.icache, dst.addr(), count);
+        if count != 0 {
+            let dst_tags =
+                self.tags
+                    .grans_mut(dst.addr(), count)
+                    .ok_or(Exception::InvalidMemAccess {
+                        access: dst_access,
+                        pc: None,
+                    })?;
+            for (mut tag, valid) in dst_tags.iter_mut().zip(tags) {
+                *tag = valid;
+            }
+        }
+        self.tags.set_init(dst.addr(), count);
+        Ok(())
+    }
+}
+
+impl Memory {
+    /// Iterate over every valid capability held in registers or memory.
+    pub fn iter_tagged_caps(&self) -> impl Iterator<Item = (CapLocation, TaggedCapability)> + '_ {
+        self.tags.mem.iter_ones().filter_map(|idx| {
+            let loc = TagController::idx_to_loc(idx)?;
+            let tcap = self.cap_at(loc)?;
+            tcap.is_valid().then_some((loc, tcap))
+        })
+    }
+
+    /// Read the capability held at `loc`, or `None` if it can't be read.
+    pub(crate) fn cap_at(&self, loc: CapLocation) -> Option<TaggedCapability> {
+        match loc {
+            CapLocation::Register(reg) => self.regs.read(&self.tags, reg as _).ok(),
+            /* NOTE: it's okay to create magic tcap here because inspecting
+             * all capabilities is conceptually a privileged process */
+            CapLocation::Granule(gran) => self.read(self.root.set_addr(gran.addr()?)).ok(),
+        }
+    }
+}
+
+impl Memory {
+    /// Number of freed spans remembered for detecting use after free.
+    pub const RECENTLY_FREED: usize = 16;
+
+    pub(crate) fn record_free(&mut self, span: Range<Address>) {
+        if self.recently_freed.len() == Self::RECENTLY_FREED {
+            self.recently_freed.pop_front();
+        }
+        self.recently_freed.push_back(span);
+    }
+
+    /// Reports an invalid memory access through a capability which was
+    /// revoked by a recent free as [`Exception::UseAfterFree`] instead.
+    pub(crate) fn explain_invalid_access(&self, err: Exception) -> Exception {
+        let Exception::InvalidMemAccess { access, .. } = err else {
+            return err;
+        };
+        let tcap = access.tcap;
+        let freed = !tcap.is_valid()
+            && tcap.capability().is_well_formed()
+            && self
+                .recently_freed
+                .iter()
+                .any(|span| span.start <= tcap.start() && tcap.endb() <= span.end);
+        if freed {
+            Exception::UseAfterFree { addr: tcap.addr() }
+        } else {
+            err
+        }
+    }
+
+    /// Set (or with `None`, remove) the hook called before each operation is
+    /// executed.
+    pub fn set_trace_hook(&mut self, hook: Option<TraceHook>) {
+        self.trace_hook = hook;
+    }
+
+    /// Returns the number of times each kind of operation has been executed,
+    /// indexed by [`OpKind::to_byte`].
+    pub fn op_histogram(&self) -> &[u64] {
+        &self.op_histogram
+    }
+
+    /// Choose how operations are dispatched to their handlers. Behaviour is
+    /// identical either way.
+    pub fn set_dispatch(&mut self, dispatch: Dispatch) {
+        self.dispatch = dispatch;
+    }
+
+    /// Limit how many more operations may be executed before
+    /// [`Exception::BudgetExceeded`] is raised. `None` means unlimited.
+    pub fn set_op_budget(&mut self, budget: Option<u64>) {
+        self.op_budget = budget;
+    }
+
+    /// Returns how many more operations may be executed, or `None` if
+    /// unlimited.
+    pub fn op_budget(&self) -> Option<u64> {
+        self.op_budget
+    }
+
+    /// Enable or disable raising [`Exception::UninitializedRead`] when memory
+    /// is read before being written.
+    ///
+    /// Initialization is tracked per granule. When enabled, granules which are
+    /// untagged and entirely filled with [`UNINIT_BYTE`] start out
+    /// uninitialized.
+    pub fn set_track_uninit(&mut self, enabled: bool) {
+        self.tags.init = enabled.then(|| {
+            self.mem
+                .chunks(UGRAN_SIZE.into())
+                .zip(self.tags.mem[Registers::COUNT as _..].iter())
+                .map(|(gran, tag)| *tag || gran.iter().any(|byte| *byte != UNINIT_BYTE))
+                .collect()
+        });
+    }
+}
+
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Memory")
+            .field("mem", &self.mem)
+            .field("regs", &self.regs)
+            .field("tags", &self.tags)
+            .field("root", &self.root)
+            .field("trace_hook", &self.trace_hook.is_some())
+            .field("op_histogram", &self.op_histogram)
+            .field("dispatch", &self.dispatch)
+            .field("icache", &self.icache)
+            .field("op_budget", &self.op_budget)
+            .finish()
+    }
 }
 
 impl Memory {
@@ -235,13 +651,35 @@ impl Memory {
         let layout_size = usize::try_from(layout.size).ok()?;
         mem.get_mut(start_idx..)?.get_mut(..layout_size)
     }
+
+    /// Forget the cached operation if it overlaps the `size` bytes written at
+    /// `start`.
+    fn invalidate_icache(icache: &mut Option<(TaggedCapability, Op)>, start: Address, size: UAddr) {
+        if let Some((pc, _)) = icache {
+            let write = u32::from(start.get())..u32::from(start.get()) + u32::from(size);
+            let op =
+                u32::from(pc.addr().get())..u32::from(pc.addr().get()) + u32::from(Op::LAYOUT.size);
+            if write.start < op.end && op.start < write.end {
+                *icache = None;
+            }
+        }
+    }
+}
+
+/// Location of a tagged capability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapLocation {
+    Register(Register),
+    Granule(Granule),
 }
 
 #[derive(Debug)]
 pub struct TagController {
-    // 0..32 => registers
-    // 32.. => mem granules
+    // 0..Registers::COUNT => registers
+    // Registers::COUNT.. => mem granules
     pub(crate) mem: BitBox<u8, Lsb0>,
+    // one bit per mem granule, set once written. None unless tracking.
+    pub(crate) init: Option<BitBox<u8, Lsb0>>,
 }
 
 impl TagController {
@@ -254,7 +692,7 @@ impl TagController {
         debug_assert_eq!(mem.len(), elems);
         // initialize all as invalid
         mem[..].fill(false);
-        Ok(Self { mem })
+        Ok(Self { mem, init: None })
     }
 
     pub fn grans(&self, start: Address, size: UAddr) -> Option<&BitSlice<u8>> {
@@ -289,14 +727,70 @@ impl TagController {
         *self.mem.get_mut(reg)? = valid;
         Some(())
     }
+
+    /// Returns the first granule spanned by the `size` bytes at `start` which
+    /// hasn't been initialized, if initialization is being tracked.
+    pub fn first_uninit(&self, start: Address, size: UAddr) -> Option<Granule> {
+        let init = self.init.as_ref()?;
+        let first = usize::from(start.gran().0);
+        let span = init.get(first..)?.get(..=abi::gran_span(start, size))?;
+        span.first_zero()
+            .map(|idx| Granule(start.gran().0 + UAddr::try_from(idx).unwrap()))
+    }
+
+    pub(crate) fn set_init(&mut self, start: Address, size: UAddr) {
+        self.fill_init(start, size, true);
+    }
+
+    pub(crate) fn set_uninit(&mut self, start: Address, size: UAddr) {
+        self.fill_init(start, size, false);
+    }
+
+    /// Number of memory granules holding valid capabilities. Registers aren't
+    /// counted.
+    pub fn count_valid(&self) -> usize {
+        self.mem[usize::from(Registers::COUNT)..].count_ones()
+    }
+
+    pub fn invalidate(&mut self, loc: CapLocation) -> Option<()> {
+        let idx = match loc {
+            CapLocation::Register(reg) => Self::reg_to_idx(reg as _)?,
+            CapLocation::Granule(gran) => Self::gran_to_idx(gran)?,
+        };
+        *self.mem.get_mut(idx)? = false;
+        Some(())
+    }
 }
 
 impl TagController {
+    fn fill_init(&mut self, start: Address, size: UAddr, init: bool) {
+        if size == 0 {
+            return;
+        }
+        let first = usize::from(start.gran().0);
+        if let Some(span) = self
+            .init
+            .as_mut()
+            .and_then(|bits| bits.get_mut(first..))
+            .and_then(|bits| bits.get_mut(..=abi::gran_span(start, size)))
+        {
+            span.fill(init);
+        }
+    }
+
     fn gran_to_idx(gran: Granule) -> Option<usize> {
         let idx = gran.0.checked_add(Registers::COUNT as _)?;
         usize::try_from(idx).ok()
     }
 
+    pub(crate) fn idx_to_loc(idx: usize) -> Option<CapLocation> {
+        if let Some(reg) = u8::try_from(idx).ok().and_then(Register::from_byte) {
+            Some(CapLocation::Register(reg))
+        } else {
+            Self::idx_to_gran(idx).map(CapLocation::Granule)
+        }
+    }
+
     pub(crate) fn idx_to_gran(idx: usize) -> Option<Granule> {
         idx.checked_sub(Registers::COUNT as _)
             .and_then(|gran| UAddr::try_from(gran).ok())
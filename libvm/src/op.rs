@@ -5,10 +5,13 @@ use core::fmt;
 use crate::abi::{self, Align, Layout, StructMut, StructRef, Ty};
 use crate::capability::{Address, TaggedCapability};
 use crate::exception::Exception;
+use crate::int::{addr_sign, UAddr};
+use crate::registers::Register;
 
 // informally based on riscv but this is not by definition so could change anytime
 #[deny(missing_docs)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 /// Enumeration over all operations.
 pub enum OpKind {
@@ -240,11 +243,81 @@ pub enum OpKind {
     /// Perform a system call. The [kind](crate::syscall::SyscallKind) is
     /// determined by the value in register `a2`.
     Syscall,
+
+    /// Add registers `op3` to `op2` and store the result in register `op1`.
+    ///
+    /// Raises [`Exception::IntegerOverflow`] upon unsigned arithmetic overflow.
+    AddOvf,
+
+    /// Subtract registers `op3` from `op2` and store the result in register
+    /// `op1`.
+    ///
+    /// Raises [`Exception::IntegerOverflow`] upon unsigned arithmetic overflow.
+    SubOvf,
+
+    /// Multiply registers `op2` and `op3` and store the result in register
+    /// `op1`.
+    ///
+    /// Raises [`Exception::IntegerOverflow`] upon unsigned arithmetic overflow.
+    MulOvf,
+
+    /// Load 8-bit value from memory at register `op2` offset by `SAddr`
+    /// immediate `op3` and zero-extend before storing it in register `op1`.
+    LoadU8O,
+
+    /// Load 16-bit value from memory at register `op2` offset by `SAddr`
+    /// immediate `op3` and zero-extend before storing it in register `op1`.
+    LoadU16O,
+
+    /// Load 32-bit value from memory at register `op2` offset by `SAddr`
+    /// immediate `op3` and zero-extend before storing it in register `op1`.
+    LoadU32O,
+
+    /// Load 64-bit value from memory at register `op2` offset by `SAddr`
+    /// immediate `op3` into register `op1`.
+    LoadU64O,
+
+    /// Load capability from memory at register `op2` offset by `SAddr`
+    /// immediate `op3` into register `op1`.
+    LoadCO,
+
+    /// Store 8-bit value from the low bits of register `op2` to memory at
+    /// register `op1` offset by `SAddr` immediate `op3`.
+    Store8O,
+
+    /// Store 16-bit value from the low bits of register `op2` to memory at
+    /// register `op1` offset by `SAddr` immediate `op3`.
+    Store16O,
+
+    /// Store 32-bit value from the low bits of register `op2` to memory at
+    /// register `op1` offset by `SAddr` immediate `op3`.
+    Store32O,
+
+    /// Store 64-bit value from register `op2` to memory at register `op1`
+    /// offset by `SAddr` immediate `op3`.
+    Store64O,
+
+    /// Store capability from register `op2` to memory at register `op1`
+    /// offset by `SAddr` immediate `op3`.
+    StoreCO,
+
+    /// Place the value 1 in register `op1` if the capabilities at registers
+    /// `op2` and `op3` are both valid, and the bounds and permissions of `op2`
+    /// are within those of `op3`, else place 0.
+    CSubset,
+
+    /// Load capability from register `op2` to register `op1`, clearing its
+    /// tag so that only its value remains.
+    CClear,
 }
 
 impl OpKind {
     pub const MAX_OPERANDS: usize = 3;
 
+    /// Number of operation kinds. Every byte below this is a valid operation
+    /// kind.
+    pub const COUNT: u8 = 65;
+
     pub const fn to_byte(self) -> u8 {
         self as u8
     }
@@ -301,6 +374,21 @@ impl OpKind {
             47 => Ok(Self::Bltu),
             48 => Ok(Self::Bgeu),
             49 => Ok(Self::Syscall),
+            50 => Ok(Self::AddOvf),
+            51 => Ok(Self::SubOvf),
+            52 => Ok(Self::MulOvf),
+            53 => Ok(Self::LoadU8O),
+            54 => Ok(Self::LoadU16O),
+            55 => Ok(Self::LoadU32O),
+            56 => Ok(Self::LoadU64O),
+            57 => Ok(Self::LoadCO),
+            58 => Ok(Self::Store8O),
+            59 => Ok(Self::Store16O),
+            60 => Ok(Self::Store32O),
+            61 => Ok(Self::Store64O),
+            62 => Ok(Self::StoreCO),
+            63 => Ok(Self::CSubset),
+            64 => Ok(Self::CClear),
             _ => Err(Exception::InvalidOpKind { byte }),
         }
     }
@@ -357,6 +445,201 @@ impl OpKind {
             Self::Bltu => 3,
             Self::Bgeu => 3,
             Self::Syscall => 0,
+            Self::AddOvf => 3,
+            Self::SubOvf => 3,
+            Self::MulOvf => 3,
+            Self::LoadU8O => 3,
+            Self::LoadU16O => 3,
+            Self::LoadU32O => 3,
+            Self::LoadU64O => 3,
+            Self::LoadCO => 3,
+            Self::Store8O => 3,
+            Self::Store16O => 3,
+            Self::Store32O => 3,
+            Self::Store64O => 3,
+            Self::StoreCO => 3,
+            Self::CSubset => 3,
+            Self::CClear => 2,
+        }
+    }
+
+    /// Whether the operation conditionally offsets the program counter.
+    pub const fn is_branch(self) -> bool {
+        matches!(
+            self,
+            Self::Beq | Self::Bne | Self::Blts | Self::Bges | Self::Bltu | Self::Bgeu
+        )
+    }
+
+    /// Whether the operation unconditionally sets the program counter.
+    pub const fn is_jump(self) -> bool {
+        matches!(self, Self::Jal | Self::Jalr)
+    }
+
+    /// Whether the operation loads from or stores to memory.
+    pub const fn is_memory(self) -> bool {
+        matches!(
+            self,
+            Self::LoadU8
+                | Self::LoadU16
+                | Self::LoadU32
+                | Self::LoadU64
+                | Self::LoadC
+                | Self::Store8
+                | Self::Store16
+                | Self::Store32
+                | Self::Store64
+                | Self::StoreC
+                | Self::LoadU8O
+                | Self::LoadU16O
+                | Self::LoadU32O
+                | Self::LoadU64O
+                | Self::LoadCO
+                | Self::Store8O
+                | Self::Store16O
+                | Self::Store32O
+                | Self::Store64O
+                | Self::StoreCO
+        )
+    }
+
+    /// Whether the operation inspects or manipulates capability fields.
+    pub const fn is_cap(self) -> bool {
+        matches!(
+            self,
+            Self::CGetValid
+                | Self::CGetAddr
+                | Self::CSetAddr
+                | Self::CGetBound
+                | Self::CSetBound
+                | Self::CGetPerm
+                | Self::CSetPerm
+                | Self::CGetType
+                | Self::CSeal
+                | Self::CUnseal
+                | Self::CSubset
+                | Self::CClear
+        )
+    }
+
+    /// Whether the operation does arithmetic, logic, comparison or shifting on
+    /// register values.
+    pub const fn is_alu(self) -> bool {
+        matches!(
+            self,
+            Self::AddI
+                | Self::Add
+                | Self::Sub
+                | Self::AddOvf
+                | Self::SubOvf
+                | Self::MulOvf
+                | Self::SltsI
+                | Self::SltuI
+                | Self::Slts
+                | Self::Sltu
+                | Self::XorI
+                | Self::Xor
+                | Self::OrI
+                | Self::Or
+                | Self::AndI
+                | Self::And
+                | Self::SllI
+                | Self::Sll
+                | Self::SrlI
+                | Self::Srl
+                | Self::SraI
+                | Self::Sra
+        )
+    }
+
+    /// Role of each operand, or `None` for unused operands.
+    pub const fn operand_roles(self) -> [Option<OperandRole>; Self::MAX_OPERANDS] {
+        const fn sig<const N: usize>(
+            op: OpKind,
+            sig: [OperandRole; N],
+        ) -> [Option<OperandRole>; OpKind::MAX_OPERANDS] {
+            assert!(N <= OpKind::MAX_OPERANDS);
+            if sig.len() != op.operand_count() as usize {
+                panic!("signature must have correct operand count");
+            }
+            let mut out = [None; 3];
+
+            let mut idx = 0;
+            while idx < sig.len() {
+                out[idx] = Some(sig[idx]);
+                idx += 1;
+            }
+            out
+        }
+
+        use OperandRole::*;
+
+        match self {
+            Self::CGetValid => sig(self, [Register, Register]),
+            Self::CGetAddr => sig(self, [Register, Register]),
+            Self::CSetAddr => sig(self, [Register, Register]),
+            Self::CGetBound => sig(self, [Register, Register, Register]),
+            Self::CSetBound => sig(self, [Register, Register, Register]),
+            Self::CGetPerm => sig(self, [Register, Register]),
+            Self::CSetPerm => sig(self, [Register, Register]),
+            Self::CGetType => sig(self, [Register, Register]),
+            Self::CSeal => sig(self, [Register, Register, Register]),
+            Self::CUnseal => sig(self, [Register, Register, Register]),
+            Self::Cpy => sig(self, [Register, Register]),
+            Self::LoadI => sig(self, [Register, Immediate]),
+            Self::LoadU8 => sig(self, [Register, Register]),
+            Self::LoadU16 => sig(self, [Register, Register]),
+            Self::LoadU32 => sig(self, [Register, Register]),
+            Self::LoadU64 => sig(self, [Register, Register]),
+            Self::LoadC => sig(self, [Register, Register]),
+            Self::Store8 => sig(self, [Register, Register]),
+            Self::Store16 => sig(self, [Register, Register]),
+            Self::Store32 => sig(self, [Register, Register]),
+            Self::Store64 => sig(self, [Register, Register]),
+            Self::StoreC => sig(self, [Register, Register]),
+            Self::AddI => sig(self, [Register, Register, Immediate]),
+            Self::Add => sig(self, [Register, Register, Register]),
+            Self::Sub => sig(self, [Register, Register, Register]),
+            Self::SltsI => sig(self, [Register, Register, Immediate]),
+            Self::SltuI => sig(self, [Register, Register, Immediate]),
+            Self::Slts => sig(self, [Register, Register, Register]),
+            Self::Sltu => sig(self, [Register, Register, Register]),
+            Self::XorI => sig(self, [Register, Register, Immediate]),
+            Self::Xor => sig(self, [Register, Register, Register]),
+            Self::OrI => sig(self, [Register, Register, Immediate]),
+            Self::Or => sig(self, [Register, Register, Register]),
+            Self::AndI => sig(self, [Register, Register, Immediate]),
+            Self::And => sig(self, [Register, Register, Register]),
+            Self::SllI => sig(self, [Register, Register, Immediate]),
+            Self::Sll => sig(self, [Register, Register, Register]),
+            Self::SrlI => sig(self, [Register, Register, Immediate]),
+            Self::Srl => sig(self, [Register, Register, Register]),
+            Self::SraI => sig(self, [Register, Register, Immediate]),
+            Self::Sra => sig(self, [Register, Register, Register]),
+            Self::Jal => sig(self, [Register, Offset]),
+            Self::Jalr => sig(self, [Register, Register, Offset]),
+            Self::Beq => sig(self, [Register, Register, Offset]),
+            Self::Bne => sig(self, [Register, Register, Offset]),
+            Self::Blts => sig(self, [Register, Register, Offset]),
+            Self::Bges => sig(self, [Register, Register, Offset]),
+            Self::Bltu => sig(self, [Register, Register, Offset]),
+            Self::Bgeu => sig(self, [Register, Register, Offset]),
+            Self::Syscall => sig(self, []),
+            Self::AddOvf => sig(self, [Register, Register, Register]),
+            Self::SubOvf => sig(self, [Register, Register, Register]),
+            Self::MulOvf => sig(self, [Register, Register, Register]),
+            Self::LoadU8O => sig(self, [Register, Register, Immediate]),
+            Self::LoadU16O => sig(self, [Register, Register, Immediate]),
+            Self::LoadU32O => sig(self, [Register, Register, Immediate]),
+            Self::LoadU64O => sig(self, [Register, Register, Immediate]),
+            Self::LoadCO => sig(self, [Register, Register, Immediate]),
+            Self::Store8O => sig(self, [Register, Register, Immediate]),
+            Self::Store16O => sig(self, [Register, Register, Immediate]),
+            Self::Store32O => sig(self, [Register, Register, Immediate]),
+            Self::Store64O => sig(self, [Register, Register, Immediate]),
+            Self::StoreCO => sig(self, [Register, Register, Immediate]),
+            Self::CSubset => sig(self, [Register, Register, Register]),
+            Self::CClear => sig(self, [Register, Register]),
         }
     }
 
@@ -412,9 +695,25 @@ impl OpKind {
             Self::Bltu => "bltu",
             Self::Bgeu => "bgeu",
             Self::Syscall => "syscall",
+            Self::AddOvf => "addovf",
+            Self::SubOvf => "subovf",
+            Self::MulOvf => "mulovf",
+            Self::LoadU8O => "loadu8o",
+            Self::LoadU16O => "loadu16o",
+            Self::LoadU32O => "loadu32o",
+            Self::LoadU64O => "loadu64o",
+            Self::LoadCO => "loadco",
+            Self::Store8O => "store8o",
+            Self::Store16O => "store16o",
+            Self::Store32O => "store32o",
+            Self::Store64O => "store64o",
+            Self::StoreCO => "storeco",
+            Self::CSubset => "csubset",
+            Self::CClear => "cclear",
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "cgetvalid" => Some(Self::CGetValid),
@@ -467,11 +766,37 @@ impl OpKind {
             "bltu" => Some(Self::Bltu),
             "bgeu" => Some(Self::Bgeu),
             "syscall" => Some(Self::Syscall),
+            "addovf" => Some(Self::AddOvf),
+            "subovf" => Some(Self::SubOvf),
+            "mulovf" => Some(Self::MulOvf),
+            "loadu8o" => Some(Self::LoadU8O),
+            "loadu16o" => Some(Self::LoadU16O),
+            "loadu32o" => Some(Self::LoadU32O),
+            "loadu64o" => Some(Self::LoadU64O),
+            "loadco" => Some(Self::LoadCO),
+            "store8o" => Some(Self::Store8O),
+            "store16o" => Some(Self::Store16O),
+            "store32o" => Some(Self::Store32O),
+            "store64o" => Some(Self::Store64O),
+            "storeco" => Some(Self::StoreCO),
+            "csubset" => Some(Self::CSubset),
+            "cclear" => Some(Self::CClear),
             _ => None,
         }
     }
 }
 
+/// What an operand of an operation refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperandRole {
+    /// Index of a register.
+    Register,
+    /// Value used as is.
+    Immediate,
+    /// Signed `SAddr` offset from an address.
+    Offset,
+}
+
 impl Ty for OpKind {
     const LAYOUT: Layout = Layout {
         size: 1,
@@ -502,6 +827,7 @@ impl fmt::Display for OpKind {
  * ops cant be tagged. their validity must be rebuilt from some sort of root
  * capability passed to the program. */
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Op {
     pub kind: OpKind,
     pub op1: TaggedCapability,
@@ -546,22 +872,49 @@ impl Ty for Op {
     }
 }
 
+impl Op {
+    /// Used operands, formatted according to their role.
+    fn operands(&self) -> impl Iterator<Item = OperandFmt> {
+        self.kind
+            .operand_roles()
+            .into_iter()
+            .zip([self.op1, self.op2, self.op3])
+            .filter_map(|(role, val)| Some(OperandFmt { role: role?, val }))
+    }
+}
+
+/// Operand which displays as it would be written in assembly.
+struct OperandFmt {
+    role: OperandRole,
+    val: TaggedCapability,
+}
+
+impl fmt::Display for OperandFmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let val = self.val;
+        if val.is_valid() {
+            return write!(f, "{val:?}");
+        }
+        match self.role {
+            OperandRole::Register => match u8::try_from(val.to_ugran())
+                .ok()
+                .and_then(Register::from_byte)
+            {
+                Some(reg) => write!(f, "{reg}"),
+                None => write!(f, "{val:?}"),
+            },
+            OperandRole::Immediate => write!(f, "{val:?}"),
+            OperandRole::Offset => write!(f, "{:+}", addr_sign(val.to_ugran() as UAddr)),
+        }
+    }
+}
+
 impl fmt::Display for Op {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.kind)?;
-        let op_count = self.kind.operand_count();
-        if op_count > 0 {
-            write!(f, " ")?;
-        }
-        for (i, op) in [self.op1, self.op2, self.op3]
-            .into_iter()
-            .take(op_count.into())
-            .enumerate()
-        {
-            if i > 0 {
-                write!(f, ", ")?;
-            }
-            write!(f, "{op:?}")?;
+        for (i, op) in self.operands().enumerate() {
+            let sep = if i > 0 { ", " } else { " " };
+            write!(f, "{sep}{op}")?;
         }
         Ok(())
     }
@@ -571,12 +924,8 @@ impl fmt::Debug for Op {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut dbg = f.debug_struct("Op");
         dbg.field("kind", &self.kind);
-        for (i, op) in [self.op1, self.op2, self.op3]
-            .into_iter()
-            .take(self.kind.operand_count().into())
-            .enumerate()
-        {
-            dbg.field(&format!("op{i}", i = i + 1), &op);
+        for (i, op) in self.operands().enumerate() {
+            dbg.field(&format!("op{i}", i = i + 1), &format_args!("{op}"));
         }
         dbg.finish()
     }
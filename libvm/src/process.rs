@@ -2,24 +2,24 @@ use tracing::{span, Level};
 
 use crate::abi::{Layout, Ty};
 use crate::access::MemAccessKind;
-use crate::alloc::{self, InitFlags, Strategy};
+use crate::alloc::{self, InitFlags, Stats, Strategy};
 use crate::capability::{Address, Permissions, TaggedCapability};
 use crate::exception::Exception;
-use crate::int::{addr_sign, gran_sign, SAddr, SGran, UAddr, UGran};
+use crate::int::{addr_sign, gran_sign, SAddr, SGran, UAddr, UGran, UGRAN_BITS, UGRAN_SIZE};
 use crate::mem::Memory;
 use crate::op::{Op, OpKind};
 use crate::registers::Register;
-use crate::syscall::SyscallKind;
+use crate::syscall::{CapInfo, SyscallKind};
 
 impl Memory {
     pub fn execute_next(&mut self) -> Result<(), Exception> {
-        let pc = self.regs.read(&self.tags, Register::Pc as _).unwrap();
-        let op: Op = self.read(pc)?;
-        pc.check_access(
-            MemAccessKind::Execute,
-            Op::LAYOUT.align,
-            Some(Op::LAYOUT.size),
-        )?;
+        self.step()?;
+        Ok(())
+    }
+
+    /// Execute the operation at the program counter, and return it.
+    pub fn step(&mut self) -> Result<Op, Exception> {
+        let (pc, op) = self.fetch()?;
 
         let span = span!(
             Level::INFO,
@@ -34,7 +34,54 @@ impl Memory {
 
         self.execute_op(op, Some(pc), true)?;
 
-        Ok(())
+        Ok(op)
+    }
+
+    /// Execute up to `max_ops` operations, stopping early if an exception is
+    /// raised. The process exiting is not treated as an error, and the exit
+    /// syscall is counted as executed.
+    pub fn run(&mut self, max_ops: u64) -> Result<RunOutcome, Exception> {
+        let span = span!(Level::INFO, "run", max_ops);
+        let _guard = span.enter();
+
+        let mut outcome = RunOutcome {
+            ops: 0,
+            exited: false,
+        };
+        while outcome.ops < max_ops {
+            let (pc, op) = self.fetch()?;
+            outcome.ops += 1;
+            match self.execute_op(op, Some(pc), true) {
+                Ok(()) => (),
+                Err(Exception::ProcessExit) => {
+                    outcome.exited = true;
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(outcome)
+    }
+
+    fn fetch(&mut self) -> Result<(TaggedCapability, Op), Exception> {
+        let pc = self.regs.read(&self.tags, Register::Pc as _).unwrap();
+        /* NOTE: the cache is keyed by the whole program counter capability,
+         * not just its address, so a hit implies the access checks below
+         * would pass again */
+        let op: Op = match self.icache {
+            Some((cached_pc, op)) if cached_pc == pc => op,
+            _ => {
+                let op = self.read(pc)?;
+                pc.check_access(
+                    MemAccessKind::Execute,
+                    Op::LAYOUT.align,
+                    Some(Op::LAYOUT.size),
+                )?;
+                self.icache = Some((pc, op));
+                op
+            }
+        };
+        Ok((pc, op))
     }
 
     pub fn execute_op(
@@ -48,7 +95,13 @@ impl Memory {
         } else {
             self.regs.read(&self.tags, Register::Pc as _)?
         };
-        let mut return_address = None; // override return address
+        if let Some(budget) = &mut self.op_budget {
+            *budget = budget.checked_sub(1).ok_or(Exception::BudgetExceeded)?;
+        }
+        if let Some(hook) = &mut self.trace_hook {
+            hook(pc.addr(), &op);
+        }
+        self.op_histogram[usize::from(op.kind.to_byte())] += 1;
         let inc_pc = if bump_pc {
             pc.set_addr(pc.addr().add(Op::LAYOUT.size))
         } else {
@@ -62,471 +115,889 @@ impl Memory {
 
         tracing::trace!("executing {op}");
 
-        match op.kind {
-            OpKind::CGetValid => {
-                let dst = reg(op.op1);
-                let tcap = self.regs.read(&self.tags, reg(op.op2))?;
-                self.regs.write_ty(&mut self.tags, dst, tcap.is_valid())?;
-            }
+        let ctx = OpCtx { op, pc, inc_pc };
+        let return_address = match self.dispatch {
+            Dispatch::Match => self.dispatch_match(ctx),
+            Dispatch::Table => DISPATCH_TABLE[usize::from(op.kind.to_byte())](self, ctx),
+        }
+        .map_err(|err| self.explain_invalid_access(err).at_pc(pc.addr()))?;
 
-            OpKind::CGetAddr => {
-                let dst = reg(op.op1);
-                let tcap = self.regs.read(&self.tags, reg(op.op2))?;
-                let addr = tcap.addr();
-                self.regs.write_ty(&mut self.tags, dst, addr)?;
-            }
+        // return address was overridden
+        if let Some(ra) = return_address {
+            self.regs.write(&mut self.tags, Register::Pc as _, ra)?;
+        }
 
-            OpKind::CSetAddr => {
-                let tcap_reg = reg(op.op1);
-                let mut tcap = self.regs.read(&self.tags, tcap_reg)?;
-                let addr: Address = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                tcap = tcap.set_addr(addr);
-                self.regs.write(&mut self.tags, tcap_reg, tcap)?;
-            }
+        Ok(())
+    }
+}
 
-            OpKind::CGetBound => {
-                let start_dst = reg(op.op1);
-                let endb_dst = reg(op.op2);
-                let tcap = self.regs.read(&self.tags, reg(op.op3))?;
-                let start = tcap.start();
-                let endb = tcap.endb();
-                self.regs.write_ty(&mut self.tags, start_dst, start)?;
-                self.regs.write_ty(&mut self.tags, endb_dst, endb)?;
-            }
+impl Memory {
+    fn dispatch_match(&mut self, ctx: OpCtx) -> OpResult {
+        match ctx.op.kind {
+            OpKind::CGetValid => self.exec_cgetvalid(ctx),
+            OpKind::CGetAddr => self.exec_cgetaddr(ctx),
+            OpKind::CSetAddr => self.exec_csetaddr(ctx),
+            OpKind::CGetBound => self.exec_cgetbound(ctx),
+            OpKind::CSetBound => self.exec_csetbound(ctx),
+            OpKind::CGetPerm => self.exec_cgetperm(ctx),
+            OpKind::CSetPerm => self.exec_csetperm(ctx),
+            OpKind::CGetType => self.exec_cgettype(ctx),
+            OpKind::CSeal => self.exec_cseal(ctx),
+            OpKind::CUnseal => self.exec_cunseal(ctx),
+            OpKind::Cpy => self.exec_cpy(ctx),
+            OpKind::LoadI => self.exec_loadi(ctx),
+            OpKind::LoadU8 => self.exec_loadu8(ctx),
+            OpKind::LoadU16 => self.exec_loadu16(ctx),
+            OpKind::LoadU32 => self.exec_loadu32(ctx),
+            OpKind::LoadU64 => self.exec_loadu64(ctx),
+            OpKind::LoadC => self.exec_loadc(ctx),
+            OpKind::Store8 => self.exec_store8(ctx),
+            OpKind::Store16 => self.exec_store16(ctx),
+            OpKind::Store32 => self.exec_store32(ctx),
+            OpKind::Store64 => self.exec_store64(ctx),
+            OpKind::StoreC => self.exec_storec(ctx),
+            OpKind::AddI => self.exec_addi(ctx),
+            OpKind::Add => self.exec_add(ctx),
+            OpKind::Sub => self.exec_sub(ctx),
+            OpKind::SltsI => self.exec_sltsi(ctx),
+            OpKind::SltuI => self.exec_sltui(ctx),
+            OpKind::Slts => self.exec_slts(ctx),
+            OpKind::Sltu => self.exec_sltu(ctx),
+            OpKind::XorI => self.exec_xori(ctx),
+            OpKind::Xor => self.exec_xor(ctx),
+            OpKind::OrI => self.exec_ori(ctx),
+            OpKind::Or => self.exec_or(ctx),
+            OpKind::AndI => self.exec_andi(ctx),
+            OpKind::And => self.exec_and(ctx),
+            OpKind::SllI => self.exec_slli(ctx),
+            OpKind::Sll => self.exec_sll(ctx),
+            OpKind::SrlI => self.exec_srli(ctx),
+            OpKind::Srl => self.exec_srl(ctx),
+            OpKind::SraI => self.exec_srai(ctx),
+            OpKind::Sra => self.exec_sra(ctx),
+            OpKind::Jal => self.exec_jal(ctx),
+            OpKind::Jalr => self.exec_jalr(ctx),
+            OpKind::Beq => self.exec_beq(ctx),
+            OpKind::Bne => self.exec_bne(ctx),
+            OpKind::Blts => self.exec_blts(ctx),
+            OpKind::Bges => self.exec_bges(ctx),
+            OpKind::Bltu => self.exec_bltu(ctx),
+            OpKind::Bgeu => self.exec_bgeu(ctx),
+            OpKind::Syscall => self.exec_syscall(ctx),
+            OpKind::AddOvf => self.exec_addovf(ctx),
+            OpKind::SubOvf => self.exec_subovf(ctx),
+            OpKind::MulOvf => self.exec_mulovf(ctx),
+            OpKind::LoadU8O => self.exec_loadu8(ctx),
+            OpKind::LoadU16O => self.exec_loadu16(ctx),
+            OpKind::LoadU32O => self.exec_loadu32(ctx),
+            OpKind::LoadU64O => self.exec_loadu64(ctx),
+            OpKind::LoadCO => self.exec_loadc(ctx),
+            OpKind::Store8O => self.exec_store8(ctx),
+            OpKind::Store16O => self.exec_store16(ctx),
+            OpKind::Store32O => self.exec_store32(ctx),
+            OpKind::Store64O => self.exec_store64(ctx),
+            OpKind::StoreCO => self.exec_storec(ctx),
+            OpKind::CSubset => self.exec_csubset(ctx),
+            OpKind::CClear => self.exec_cclear(ctx),
+        }
+    }
 
-            OpKind::CSetBound => {
-                let tcap_reg = reg(op.op1);
-                let mut tcap = self.regs.read(&self.tags, tcap_reg)?;
-                let start: Address = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                let endb: Address = self.regs.read_ty(&self.tags, reg(op.op3))?;
-                tcap = tcap.set_bounds(start, endb);
-                self.regs.write(&mut self.tags, tcap_reg, tcap)?;
-            }
+    fn exec_cgetvalid(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let tcap = self.regs.read(&self.tags, reg(op.op2))?;
+        self.regs.write_ty(&mut self.tags, dst, tcap.is_valid())?;
+        Ok(None)
+    }
 
-            OpKind::CGetPerm => {
-                let dst = reg(op.op1);
-                let tcap = self.regs.read(&self.tags, reg(op.op2))?;
-                let perms = tcap.perms();
-                self.regs.write_ty(&mut self.tags, dst, perms)?;
-            }
+    fn exec_cgetaddr(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let tcap = self.regs.read(&self.tags, reg(op.op2))?;
+        let addr = tcap.addr();
+        self.regs.write_ty(&mut self.tags, dst, addr)?;
+        Ok(None)
+    }
 
-            OpKind::CSetPerm => {
-                let tcap_reg = reg(op.op1);
-                let mut tcap = self.regs.read(&self.tags, tcap_reg)?;
-                let perms: Permissions = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                tcap = tcap.set_perms(perms);
-                self.regs.write(&mut self.tags, tcap_reg, tcap)?;
-            }
+    fn exec_csetaddr(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let tcap_reg = reg(op.op1);
+        let mut tcap = self.regs.read(&self.tags, tcap_reg)?;
+        let addr: Address = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        tcap = tcap.set_addr(addr);
+        self.regs.write(&mut self.tags, tcap_reg, tcap)?;
+        Ok(None)
+    }
 
-            OpKind::CGetType => {
-                let dst = reg(op.op1);
-                let tcap = self.regs.read(&self.tags, reg(op.op2))?;
-                let otype = tcap.otype();
-                self.regs.write_ty(&mut self.tags, dst, otype)?;
-            }
+    fn exec_cgetbound(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let start_dst = reg(op.op1);
+        let endb_dst = reg(op.op2);
+        let tcap = self.regs.read(&self.tags, reg(op.op3))?;
+        let start = tcap.start();
+        let endb = tcap.endb();
+        self.regs.write_ty(&mut self.tags, start_dst, start)?;
+        self.regs.write_ty(&mut self.tags, endb_dst, endb)?;
+        Ok(None)
+    }
 
-            OpKind::CSeal => {
-                let dst = reg(op.op1);
-                let src = self.regs.read(&self.tags, reg(op.op2))?;
-                let with = self.regs.read(&self.tags, reg(op.op3))?;
-                let sealed = src.seal(with);
-                self.regs.write(&mut self.tags, dst, sealed)?;
-            }
+    fn exec_csetbound(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let tcap_reg = reg(op.op1);
+        let mut tcap = self.regs.read(&self.tags, tcap_reg)?;
+        let start: Address = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        let endb: Address = self.regs.read_ty(&self.tags, reg(op.op3))?;
+        tcap = tcap.set_bounds(start, endb);
+        self.regs.write(&mut self.tags, tcap_reg, tcap)?;
+        Ok(None)
+    }
 
-            OpKind::CUnseal => {
-                let dst = reg(op.op1);
-                let src = self.regs.read(&self.tags, reg(op.op2))?;
-                let with = self.regs.read(&self.tags, reg(op.op3))?;
-                let unsealed = src.unseal(with);
-                self.regs.write(&mut self.tags, dst, unsealed)?;
-            }
+    fn exec_cgetperm(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let tcap = self.regs.read(&self.tags, reg(op.op2))?;
+        let perms = tcap.perms();
+        self.regs.write_ty(&mut self.tags, dst, perms)?;
+        Ok(None)
+    }
 
-            OpKind::Cpy => {
-                let dst = reg(op.op1);
-                let src = reg(op.op2);
-                let val = self.regs.read(&self.tags, src)?;
-                self.regs.write(&mut self.tags, dst, val)?;
-            }
+    fn exec_csetperm(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let tcap_reg = reg(op.op1);
+        let mut tcap = self.regs.read(&self.tags, tcap_reg)?;
+        let perms: Permissions = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        tcap = tcap.set_perms(perms);
+        self.regs.write(&mut self.tags, tcap_reg, tcap)?;
+        Ok(None)
+    }
 
-            OpKind::LoadI => {
-                let dst = reg(op.op1);
-                let imm = op.op2;
-                self.regs.write(&mut self.tags, dst, imm)?;
-            }
+    fn exec_cgettype(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let tcap = self.regs.read(&self.tags, reg(op.op2))?;
+        let otype = tcap.otype();
+        self.regs.write_ty(&mut self.tags, dst, otype)?;
+        Ok(None)
+    }
 
-            OpKind::LoadU8 => {
-                let dst = reg(op.op1);
-                let src = self.regs.read(&self.tags, reg(op.op2))?;
-                let val: u8 = self.read(src)?;
-                self.regs.write_ty(&mut self.tags, dst, val)?;
-            }
+    fn exec_cseal(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let src = self.regs.read(&self.tags, reg(op.op2))?;
+        let with = self.regs.read(&self.tags, reg(op.op3))?;
+        let sealed = src.seal(with);
+        self.regs.write(&mut self.tags, dst, sealed)?;
+        Ok(None)
+    }
 
-            OpKind::LoadU16 => {
-                let dst = reg(op.op1);
-                let src = self.regs.read(&self.tags, reg(op.op2))?;
-                let val: u16 = self.read(src)?;
-                self.regs.write_ty(&mut self.tags, dst, val)?;
-            }
+    fn exec_cunseal(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let src = self.regs.read(&self.tags, reg(op.op2))?;
+        let with = self.regs.read(&self.tags, reg(op.op3))?;
+        let unsealed = src.unseal(with);
+        self.regs.write(&mut self.tags, dst, unsealed)?;
+        Ok(None)
+    }
 
-            OpKind::LoadU32 => {
-                let dst = reg(op.op1);
-                let src = self.regs.read(&self.tags, reg(op.op2))?;
-                let val: u32 = self.read(src)?;
-                self.regs.write_ty(&mut self.tags, dst, val)?;
-            }
+    fn exec_csubset(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let inner = self.regs.read(&self.tags, reg(op.op2))?;
+        let outer = self.regs.read(&self.tags, reg(op.op3))?;
+        let subset = inner.is_valid()
+            && outer.is_valid()
+            && outer.capability().contains(&inner.capability())
+            && outer.perms().contains(inner.perms());
+        self.regs.write_ty(&mut self.tags, dst, subset)?;
+        Ok(None)
+    }
 
-            OpKind::LoadU64 => {
-                let dst = reg(op.op1);
-                let src = self.regs.read(&self.tags, reg(op.op2))?;
-                let val: u64 = self.read(src)?;
-                self.regs.write_ty(&mut self.tags, dst, val)?;
-            }
+    fn exec_cclear(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let val = self.regs.read_data(reg(op.op2))?;
+        self.regs.write_data(&mut self.tags, dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::LoadC => {
-                let dst = reg(op.op1);
-                let src = self.regs.read(&self.tags, reg(op.op2))?;
-                let val = self.read(src)?;
-                self.regs.write(&mut self.tags, dst, val)?;
-            }
+    fn exec_cpy(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let src = reg(op.op2);
+        let val = self.regs.read(&self.tags, src)?;
+        self.regs.write(&mut self.tags, dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::Store8 => {
-                let dst = self.regs.read(&self.tags, reg(op.op1))?;
-                let src = reg(op.op2);
-                let val: u8 = self.regs.read_data(src)? as _;
-                self.write(dst, val)?;
+    /// Read the capability through which a load or store accesses memory. The
+    /// offset variants add `SAddr` immediate `op3` to its address.
+    fn mem_operand(&self, op: Op, base: TaggedCapability) -> Result<TaggedCapability, Exception> {
+        let tcap = self.regs.read(&self.tags, reg(base))?;
+        Ok(match op.kind {
+            OpKind::LoadU8O
+            | OpKind::LoadU16O
+            | OpKind::LoadU32O
+            | OpKind::LoadU64O
+            | OpKind::LoadCO
+            | OpKind::Store8O
+            | OpKind::Store16O
+            | OpKind::Store32O
+            | OpKind::Store64O
+            | OpKind::StoreCO => {
+                let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
+                tcap.set_addr(tcap.addr().offset(offset))
             }
+            _ => tcap,
+        })
+    }
 
-            OpKind::Store16 => {
-                let dst = self.regs.read(&self.tags, reg(op.op1))?;
-                let src = reg(op.op2);
-                let val: u16 = self.regs.read_data(src)? as _;
-                self.write(dst, val)?;
-            }
+    fn exec_loadi(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let imm = op.op2;
+        self.regs.write(&mut self.tags, dst, imm)?;
+        Ok(None)
+    }
 
-            OpKind::Store32 => {
-                let dst = self.regs.read(&self.tags, reg(op.op1))?;
-                let src = reg(op.op2);
-                let val: u32 = self.regs.read_data(src)? as _;
-                self.write(dst, val)?;
-            }
+    fn exec_loadu8(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let src = self.mem_operand(op, op.op2)?;
+        let val: u8 = self.read(src)?;
+        self.regs.write_ty(&mut self.tags, dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::Store64 => {
-                let dst = self.regs.read(&self.tags, reg(op.op1))?;
-                let src = reg(op.op2);
-                let val: u64 = self.regs.read_data(src)? as _;
-                self.write(dst, val)?;
-            }
+    fn exec_loadu16(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let src = self.mem_operand(op, op.op2)?;
+        let val: u16 = self.read(src)?;
+        self.regs.write_ty(&mut self.tags, dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::StoreC => {
-                let dst = self.regs.read(&self.tags, reg(op.op1))?;
-                let src = reg(op.op2);
-                let cap = self.regs.read(&self.tags, src)?;
-                self.write(dst, cap)?;
-            }
+    fn exec_loadu32(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let src = self.mem_operand(op, op.op2)?;
+        let val: u32 = self.read(src)?;
+        self.regs.write_ty(&mut self.tags, dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::AddI => {
-                let dst = reg(op.op1);
-                let addend: UGran = self.regs.read_data(reg(op.op2))?;
-                let imm: UGran = op.op3.to_ugran();
-                let sum = addend.wrapping_add(imm);
-                self.regs.write_data(&mut self.tags, dst, sum)?;
-            }
+    fn exec_loadu64(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let src = self.mem_operand(op, op.op2)?;
+        let val: u64 = self.read(src)?;
+        self.regs.write_ty(&mut self.tags, dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::Add => {
-                let dst = reg(op.op1);
-                let add1: UGran = self.regs.read_data(reg(op.op2))?;
-                let add2: UGran = self.regs.read_data(reg(op.op3))?;
-                let sum = add1.wrapping_add(add2);
-                self.regs.write_data(&mut self.tags, dst, sum)?;
-            }
+    fn exec_loadc(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let src = self.mem_operand(op, op.op2)?;
+        let mut val: TaggedCapability = self.read(src)?;
+        if !src.perms().contains(Permissions::LOAD_CAP) {
+            val = TaggedCapability::new(val.capability(), false);
+        }
+        self.regs.write(&mut self.tags, dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::Sub => {
-                let dst = reg(op.op1);
-                let add1: UGran = self.regs.read_data(reg(op.op2))?;
-                let add2: UGran = self.regs.read_data(reg(op.op3))?;
-                let sum = add1.wrapping_sub(add2);
-                self.regs.write_data(&mut self.tags, dst, sum)?;
-            }
+    fn exec_store8(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = self.mem_operand(op, op.op1)?;
+        let src = reg(op.op2);
+        let val: u8 = self.regs.read_data(src)? as _;
+        self.write(dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::SltsI => {
-                let dst = reg(op.op1);
-                let op2: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                let op3: SGran = gran_sign(op.op3.to_ugran());
-                self.regs.write_ty(&mut self.tags, dst, op2 < op3)?;
-            }
+    fn exec_store16(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = self.mem_operand(op, op.op1)?;
+        let src = reg(op.op2);
+        let val: u16 = self.regs.read_data(src)? as _;
+        self.write(dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::SltuI => {
-                let dst = reg(op.op1);
-                let op2: UGran = self.regs.read_data(reg(op.op2))?;
-                let op3: UGran = op.op3.to_ugran();
-                self.regs.write_ty(&mut self.tags, dst, op2 < op3)?;
-            }
+    fn exec_store32(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = self.mem_operand(op, op.op1)?;
+        let src = reg(op.op2);
+        let val: u32 = self.regs.read_data(src)? as _;
+        self.write(dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::Slts => {
-                let dst = reg(op.op1);
-                let op2: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                let op3: SGran = self.regs.read_ty(&self.tags, reg(op.op3))?;
-                self.regs.write_ty(&mut self.tags, dst, op2 < op3)?;
-            }
+    fn exec_store64(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = self.mem_operand(op, op.op1)?;
+        let src = reg(op.op2);
+        let val: u64 = self.regs.read_data(src)? as _;
+        self.write(dst, val)?;
+        Ok(None)
+    }
 
-            OpKind::Sltu => {
-                let dst = reg(op.op1);
-                let op2: UGran = self.regs.read_data(reg(op.op2))?;
-                let op3: UGran = self.regs.read_data(reg(op.op3))?;
-                self.regs.write_ty(&mut self.tags, dst, op2 < op3)?;
+    fn exec_storec(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = self.mem_operand(op, op.op1)?;
+        let src = reg(op.op2);
+        let mut cap = self.regs.read(&self.tags, src)?;
+        if cap.is_valid() {
+            dst.check_access(
+                MemAccessKind::StoreCap,
+                TaggedCapability::LAYOUT.align,
+                Some(TaggedCapability::LAYOUT.size),
+            )?;
+            if !cap.perms().contains(Permissions::GLOBAL)
+                && !dst.perms().contains(Permissions::STORE_LOCAL)
+            {
+                cap = TaggedCapability::new(cap.capability(), false);
             }
+        }
+        self.write(dst, cap)?;
+        Ok(None)
+    }
 
-            OpKind::XorI => {
-                let dst = reg(op.op1);
-                let op2: UGran = self.regs.read_data(reg(op.op2))?;
-                let op3: UGran = op.op3.to_ugran();
-                self.regs.write_data(&mut self.tags, dst, op2 ^ op3)?;
-            }
+    fn exec_addi(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let addend: UGran = self.regs.read_data(reg(op.op2))?;
+        let imm: UGran = op.op3.to_ugran();
+        let sum = addend.wrapping_add(imm);
+        self.regs.write_data(&mut self.tags, dst, sum)?;
+        Ok(None)
+    }
 
-            OpKind::Xor => {
-                let dst = reg(op.op1);
-                let op2: UGran = self.regs.read_data(reg(op.op2))?;
-                let op3: UGran = self.regs.read_data(reg(op.op3))?;
-                self.regs.write_data(&mut self.tags, dst, op2 ^ op3)?;
-            }
+    fn exec_add(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let add1: UGran = self.regs.read_data(reg(op.op2))?;
+        let add2: UGran = self.regs.read_data(reg(op.op3))?;
+        let sum = add1.wrapping_add(add2);
+        self.regs.write_data(&mut self.tags, dst, sum)?;
+        Ok(None)
+    }
 
-            OpKind::OrI => {
-                let dst = reg(op.op1);
-                let op2: UGran = self.regs.read_data(reg(op.op2))?;
-                let op3: UGran = op.op3.to_ugran();
-                self.regs.write_data(&mut self.tags, dst, op2 | op3)?;
-            }
+    fn exec_sub(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let add1: UGran = self.regs.read_data(reg(op.op2))?;
+        let add2: UGran = self.regs.read_data(reg(op.op3))?;
+        let sum = add1.wrapping_sub(add2);
+        self.regs.write_data(&mut self.tags, dst, sum)?;
+        Ok(None)
+    }
 
-            OpKind::Or => {
-                let dst = reg(op.op1);
-                let op2: UGran = self.regs.read_data(reg(op.op2))?;
-                let op3: UGran = self.regs.read_data(reg(op.op3))?;
-                self.regs.write_data(&mut self.tags, dst, op2 | op3)?;
-            }
+    fn exec_addovf(&mut self, ctx: OpCtx) -> OpResult {
+        self.exec_checked(ctx, UGran::checked_add)
+    }
 
-            OpKind::AndI => {
-                let dst = reg(op.op1);
-                let op2: UGran = self.regs.read_data(reg(op.op2))?;
-                let op3: UGran = op.op3.to_ugran();
-                self.regs.write_data(&mut self.tags, dst, op2 & op3)?;
-            }
+    fn exec_subovf(&mut self, ctx: OpCtx) -> OpResult {
+        self.exec_checked(ctx, UGran::checked_sub)
+    }
 
-            OpKind::And => {
-                let dst = reg(op.op1);
-                let op2: UGran = self.regs.read_data(reg(op.op2))?;
-                let op3: UGran = self.regs.read_data(reg(op.op3))?;
-                self.regs.write_data(&mut self.tags, dst, op2 & op3)?;
-            }
+    fn exec_mulovf(&mut self, ctx: OpCtx) -> OpResult {
+        self.exec_checked(ctx, UGran::checked_mul)
+    }
 
-            OpKind::SllI => {
-                let dst = reg(op.op1);
-                let val: UGran = self.regs.read_data(reg(op.op2))?;
-                let amount: UGran = op.op3.to_ugran();
-                self.regs.write_data(&mut self.tags, dst, val << amount)?;
-            }
+    fn exec_checked(&mut self, ctx: OpCtx, f: fn(UGran, UGran) -> Option<UGran>) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let a: UGran = self.regs.read_data(reg(op.op2))?;
+        let b: UGran = self.regs.read_data(reg(op.op3))?;
+        let res = f(a, b).ok_or(Exception::IntegerOverflow { op: op.kind, a, b })?;
+        self.regs.write_data(&mut self.tags, dst, res)?;
+        Ok(None)
+    }
 
-            OpKind::Sll => {
-                let dst = reg(op.op1);
-                let val: UGran = self.regs.read_data(reg(op.op2))?;
-                let amount: UGran = self.regs.read_data(reg(op.op3))?;
-                self.regs.write_data(&mut self.tags, dst, val << amount)?;
-            }
+    fn exec_sltsi(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        let op3: SGran = gran_sign(op.op3.to_ugran());
+        self.regs.write_ty(&mut self.tags, dst, op2 < op3)?;
+        Ok(None)
+    }
 
-            OpKind::SrlI => {
-                let dst = reg(op.op1);
-                let val: UGran = self.regs.read_data(reg(op.op2))?;
-                let amount: UGran = op.op3.to_ugran();
-                self.regs.write_data(&mut self.tags, dst, val >> amount)?;
-            }
+    fn exec_sltui(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: UGran = self.regs.read_data(reg(op.op2))?;
+        let op3: UGran = op.op3.to_ugran();
+        self.regs.write_ty(&mut self.tags, dst, op2 < op3)?;
+        Ok(None)
+    }
 
-            OpKind::Srl => {
-                let dst = reg(op.op1);
-                let val: UGran = self.regs.read_data(reg(op.op2))?;
-                let amount: UGran = self.regs.read_data(reg(op.op3))?;
-                self.regs.write_data(&mut self.tags, dst, val >> amount)?;
-            }
+    fn exec_slts(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        let op3: SGran = self.regs.read_ty(&self.tags, reg(op.op3))?;
+        self.regs.write_ty(&mut self.tags, dst, op2 < op3)?;
+        Ok(None)
+    }
 
-            OpKind::SraI => {
-                let dst = reg(op.op1);
-                let val: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                let amount: UGran = op.op3.to_ugran();
-                self.regs.write_ty(&mut self.tags, dst, val >> amount)?;
-            }
+    fn exec_sltu(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: UGran = self.regs.read_data(reg(op.op2))?;
+        let op3: UGran = self.regs.read_data(reg(op.op3))?;
+        self.regs.write_ty(&mut self.tags, dst, op2 < op3)?;
+        Ok(None)
+    }
 
-            OpKind::Sra => {
-                let dst = reg(op.op1);
-                let val: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                let amount: UGran = self.regs.read_data(reg(op.op3))?;
-                self.regs.write_ty(&mut self.tags, dst, val >> amount)?;
-            }
+    fn exec_xori(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: UGran = self.regs.read_data(reg(op.op2))?;
+        let op3: UGran = op.op3.to_ugran();
+        self.regs.write_data(&mut self.tags, dst, op2 ^ op3)?;
+        Ok(None)
+    }
 
-            OpKind::Jal => {
-                let ra_dst = reg(op.op1);
-                let offset: SAddr = addr_sign(op.op2.to_ugran() as UAddr);
-                self.regs.write(&mut self.tags, ra_dst, inc_pc)?;
-                return_address = Some(pc.set_addr(pc.addr().offset(offset)));
-            }
+    fn exec_xor(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: UGran = self.regs.read_data(reg(op.op2))?;
+        let op3: UGran = self.regs.read_data(reg(op.op3))?;
+        self.regs.write_data(&mut self.tags, dst, op2 ^ op3)?;
+        Ok(None)
+    }
 
-            OpKind::Jalr => {
-                let ra_dst = reg(op.op1);
-                let base: UAddr = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                let offset_imm: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
-                self.regs.write(&mut self.tags, ra_dst, inc_pc)?;
-                return_address = Some(pc.set_addr(Address(base).offset(offset_imm)));
-            }
+    fn exec_ori(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: UGran = self.regs.read_data(reg(op.op2))?;
+        let op3: UGran = op.op3.to_ugran();
+        self.regs.write_data(&mut self.tags, dst, op2 | op3)?;
+        Ok(None)
+    }
 
-            OpKind::Beq => {
-                let cmp1: UGran = self.regs.read_data(reg(op.op1))?;
-                let cmp2: UGran = self.regs.read_data(reg(op.op2))?;
-                let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
-                if cmp1 == cmp2 {
-                    return_address = Some(pc.set_addr(pc.addr().offset(offset)));
-                }
-            }
+    fn exec_or(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: UGran = self.regs.read_data(reg(op.op2))?;
+        let op3: UGran = self.regs.read_data(reg(op.op3))?;
+        self.regs.write_data(&mut self.tags, dst, op2 | op3)?;
+        Ok(None)
+    }
 
-            OpKind::Bne => {
-                let cmp1: UGran = self.regs.read_data(reg(op.op1))?;
-                let cmp2: UGran = self.regs.read_data(reg(op.op2))?;
-                let offset = addr_sign(op.op3.to_ugran() as UAddr);
-                if cmp1 != cmp2 {
-                    return_address = Some(pc.set_addr(pc.addr().offset(offset)));
-                }
-            }
+    fn exec_andi(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: UGran = self.regs.read_data(reg(op.op2))?;
+        let op3: UGran = op.op3.to_ugran();
+        self.regs.write_data(&mut self.tags, dst, op2 & op3)?;
+        Ok(None)
+    }
 
-            OpKind::Blts => {
-                let cmp1: SGran = self.regs.read_ty(&self.tags, reg(op.op1))?;
-                let cmp2: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
-                if cmp1 < cmp2 {
-                    return_address = Some(pc.set_addr(pc.addr().offset(offset)));
-                }
-            }
+    fn exec_and(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let op2: UGran = self.regs.read_data(reg(op.op2))?;
+        let op3: UGran = self.regs.read_data(reg(op.op3))?;
+        self.regs.write_data(&mut self.tags, dst, op2 & op3)?;
+        Ok(None)
+    }
 
-            OpKind::Bges => {
-                let cmp1: SGran = self.regs.read_ty(&self.tags, reg(op.op1))?;
-                let cmp2: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
-                let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
-                if cmp1 >= cmp2 {
-                    return_address = Some(pc.set_addr(pc.addr().offset(offset)));
-                }
-            }
+    fn exec_slli(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let val: UGran = self.regs.read_data(reg(op.op2))?;
+        let amount: UGran = op.op3.to_ugran() % UGran::from(UGRAN_BITS);
+        self.regs.write_data(&mut self.tags, dst, val << amount)?;
+        Ok(None)
+    }
 
-            OpKind::Bltu => {
-                let cmp1: UGran = self.regs.read_data(reg(op.op1))?;
-                let cmp2: UGran = self.regs.read_data(reg(op.op2))?;
-                let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
-                if cmp1 < cmp2 {
-                    return_address = Some(pc.set_addr(pc.addr().offset(offset)));
-                }
-            }
+    fn exec_sll(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let val: UGran = self.regs.read_data(reg(op.op2))?;
+        let amount: UGran = self.regs.read_data(reg(op.op3))? % UGran::from(UGRAN_BITS);
+        self.regs.write_data(&mut self.tags, dst, val << amount)?;
+        Ok(None)
+    }
 
-            OpKind::Bgeu => {
-                let cmp1: UGran = self.regs.read_data(reg(op.op1))?;
-                let cmp2: UGran = self.regs.read_data(reg(op.op2))?;
-                let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
-                if cmp1 >= cmp2 {
-                    return_address = Some(pc.set_addr(pc.addr().offset(offset)));
-                }
-            }
+    fn exec_srli(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let val: UGran = self.regs.read_data(reg(op.op2))?;
+        let amount: UGran = op.op3.to_ugran() % UGran::from(UGRAN_BITS);
+        self.regs.write_data(&mut self.tags, dst, val >> amount)?;
+        Ok(None)
+    }
 
-            OpKind::Syscall => {
-                let kind: SyscallKind = self.regs.read_ty(&self.tags, Register::A2 as _)?;
-
-                let span = span!(Level::INFO, "syscall", kind = format_args!("{kind}"));
-                let _enter = span.enter();
-
-                tracing::debug!("executing syscall {kind}");
-
-                /* TODOO: allocation failures are currently fatal, but that's
-                 * silly. how should a userspace asm program handle allocation
-                 * failure, and what does that mean for the allocator api? */
-
-                match kind {
-                    SyscallKind::Exit => return Err(Exception::ProcessExit),
-
-                    SyscallKind::AllocInit => {
-                        let strategy: Strategy =
-                            self.regs.read_ty(&self.tags, Register::A3 as _)?;
-                        let flags: InitFlags = self.regs.read_ty(&self.tags, Register::A4 as _)?;
-                        let region = self.regs.read(&self.tags, Register::A5 as _)?;
-                        tracing::trace!(
-                            strategy = format_args!("{strategy:?}"),
-                            flags = format_args!("{flags:?}"),
-                            region = format_args!("{region:?}"),
-                            "initializing allocator"
-                        );
-                        let ator = alloc::init(strategy, flags, region, self)?;
-                        tracing::trace!(ator = format_args!("{ator:?}"), "init ok");
-                        self.regs.write(&mut self.tags, Register::A0 as _, ator)?;
-                    }
-
-                    SyscallKind::AllocDeInit => {
-                        let ator = self.regs.read(&self.tags, Register::A3 as _)?;
-                        tracing::trace!(ator = format_args!("{ator:?}"), "requesting de-init");
-                        let region = alloc::deinit(ator, self)?;
-                        tracing::trace!(
-                            region = format_args!("{region:?}"),
-                            "de-init ok, reclaimed region"
-                        );
-                        self.regs.write(&mut self.tags, Register::A0 as _, region)?;
-                    }
-
-                    SyscallKind::AllocAlloc => {
-                        let ator = self.regs.read(&self.tags, Register::A3 as _)?;
-                        let layout: Layout = self.regs.read_ty(&self.tags, Register::A4 as _)?;
-                        tracing::trace!(
-                            ator = format_args!("{ator:?}"),
-                            layout = format_args!("{layout:?}"),
-                            "requesting allocation"
-                        );
-                        let ation = alloc::alloc(ator, layout, self)?;
-                        tracing::trace!(ation = format_args!("{ation:?}"), "allocation ok");
-                        self.regs.write(&mut self.tags, Register::A0 as _, ation)?;
-                    }
-
-                    SyscallKind::AllocFree => {
-                        let ator = self.regs.read(&self.tags, Register::A3 as _)?;
-                        let ation = self.regs.read(&self.tags, Register::A4 as _)?;
-                        tracing::trace!(
-                            ator = format_args!("{ator:?}"),
-                            ation = format_args!("{ation:?}"),
-                            "requesting allocation free"
-                        );
-                        alloc::free(ator, ation, self)?;
-                        tracing::trace!("freeing ok");
-                    }
-
-                    SyscallKind::AllocFreeAll => {
-                        let ator = self.regs.read(&self.tags, Register::A3 as _)?;
-                        tracing::trace!(
-                            ator = format_args!("{ator:?}"),
-                            "requesting allocator free all"
-                        );
-                        alloc::free_all(ator, self)?;
-                        tracing::trace!("freeing all ok");
-                    }
-
-                    SyscallKind::AllocStat => {
-                        let ator = self.regs.read(&self.tags, Register::A3 as _)?;
-                        tracing::trace!(ator = format_args!("{ator:?}"), "statting allocator");
-                        let stats = alloc::stat(ator, self)?;
-                        tracing::trace!(stats = format_args!("{stats:?}"), "statting ok");
-                        self.regs
-                            .write_ty(&mut self.tags, Register::A0 as _, stats)?;
-                    }
-                }
-            }
+    fn exec_srl(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let val: UGran = self.regs.read_data(reg(op.op2))?;
+        let amount: UGran = self.regs.read_data(reg(op.op3))? % UGran::from(UGRAN_BITS);
+        self.regs.write_data(&mut self.tags, dst, val >> amount)?;
+        Ok(None)
+    }
+
+    fn exec_srai(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let val: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        let amount: UGran = op.op3.to_ugran() % UGran::from(UGRAN_BITS);
+        self.regs.write_ty(&mut self.tags, dst, val >> amount)?;
+        Ok(None)
+    }
+
+    fn exec_sra(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let dst = reg(op.op1);
+        let val: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        let amount: UGran = self.regs.read_data(reg(op.op3))? % UGran::from(UGRAN_BITS);
+        self.regs.write_ty(&mut self.tags, dst, val >> amount)?;
+        Ok(None)
+    }
+
+    fn exec_jal(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let pc = ctx.pc;
+        let inc_pc = ctx.inc_pc;
+        let ra_dst = reg(op.op1);
+        let offset: SAddr = addr_sign(op.op2.to_ugran() as UAddr);
+        self.regs.write(&mut self.tags, ra_dst, inc_pc)?;
+        Ok(Some(pc.set_addr(pc.addr().offset(offset))))
+    }
+
+    fn exec_jalr(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let pc = ctx.pc;
+        let inc_pc = ctx.inc_pc;
+        let ra_dst = reg(op.op1);
+        let base: UAddr = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        let offset_imm: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
+        self.regs.write(&mut self.tags, ra_dst, inc_pc)?;
+        Ok(Some(pc.set_addr(Address(base).offset(offset_imm))))
+    }
+
+    fn exec_beq(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let pc = ctx.pc;
+        let cmp1: UGran = self.regs.read_data(reg(op.op1))?;
+        let cmp2: UGran = self.regs.read_data(reg(op.op2))?;
+        let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
+        if cmp1 == cmp2 {
+            return Ok(Some(pc.set_addr(pc.addr().offset(offset))));
         }
+        Ok(None)
+    }
 
-        // return address was overridden
-        if let Some(ra) = return_address {
-            self.regs.write(&mut self.tags, Register::Pc as _, ra)?;
+    fn exec_bne(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let pc = ctx.pc;
+        let cmp1: UGran = self.regs.read_data(reg(op.op1))?;
+        let cmp2: UGran = self.regs.read_data(reg(op.op2))?;
+        let offset = addr_sign(op.op3.to_ugran() as UAddr);
+        if cmp1 != cmp2 {
+            return Ok(Some(pc.set_addr(pc.addr().offset(offset))));
+        }
+        Ok(None)
+    }
+
+    fn exec_blts(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let pc = ctx.pc;
+        let cmp1: SGran = self.regs.read_ty(&self.tags, reg(op.op1))?;
+        let cmp2: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
+        if cmp1 < cmp2 {
+            return Ok(Some(pc.set_addr(pc.addr().offset(offset))));
         }
+        Ok(None)
+    }
 
-        Ok(())
+    fn exec_bges(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let pc = ctx.pc;
+        let cmp1: SGran = self.regs.read_ty(&self.tags, reg(op.op1))?;
+        let cmp2: SGran = self.regs.read_ty(&self.tags, reg(op.op2))?;
+        let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
+        if cmp1 >= cmp2 {
+            return Ok(Some(pc.set_addr(pc.addr().offset(offset))));
+        }
+        Ok(None)
+    }
+
+    fn exec_bltu(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let pc = ctx.pc;
+        let cmp1: UGran = self.regs.read_data(reg(op.op1))?;
+        let cmp2: UGran = self.regs.read_data(reg(op.op2))?;
+        let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
+        if cmp1 < cmp2 {
+            return Ok(Some(pc.set_addr(pc.addr().offset(offset))));
+        }
+        Ok(None)
+    }
+
+    fn exec_bgeu(&mut self, ctx: OpCtx) -> OpResult {
+        let op = ctx.op;
+        let pc = ctx.pc;
+        let cmp1: UGran = self.regs.read_data(reg(op.op1))?;
+        let cmp2: UGran = self.regs.read_data(reg(op.op2))?;
+        let offset: SAddr = addr_sign(op.op3.to_ugran() as UAddr);
+        if cmp1 >= cmp2 {
+            return Ok(Some(pc.set_addr(pc.addr().offset(offset))));
+        }
+        Ok(None)
     }
+
+    fn exec_syscall(&mut self, _ctx: OpCtx) -> OpResult {
+        let kind: SyscallKind = self.regs.read_ty(&self.tags, Register::A2 as _)?;
+
+        let span = span!(Level::INFO, "syscall", kind = format_args!("{kind}"));
+        let _enter = span.enter();
+
+        tracing::debug!("executing syscall {kind}");
+
+        /* TODOO: allocation failures are currently fatal, but that's
+         * silly. how should a userspace asm program handle allocation
+         * failure, and what does that mean for the allocator api? */
+
+        match kind {
+            SyscallKind::Exit => return Err(Exception::ProcessExit),
+
+            SyscallKind::AllocInit => {
+                let strategy: Strategy = self.regs.read_ty(&self.tags, Register::A3 as _)?;
+                let flags: InitFlags = self.regs.read_ty(&self.tags, Register::A4 as _)?;
+                let region = self.regs.read(&self.tags, Register::A5 as _)?;
+                tracing::trace!(
+                    strategy = format_args!("{strategy:?}"),
+                    flags = format_args!("{flags:?}"),
+                    region = format_args!("{region:?}"),
+                    "initializing allocator"
+                );
+                let ator = alloc::init(strategy, flags, region, self)?;
+                tracing::trace!(ator = format_args!("{ator:?}"), "init ok");
+                self.regs.write(&mut self.tags, Register::A0 as _, ator)?;
+            }
+
+            SyscallKind::AllocDeInit => {
+                let ator = self.regs.read(&self.tags, Register::A3 as _)?;
+                tracing::trace!(ator = format_args!("{ator:?}"), "requesting de-init");
+                let region = alloc::deinit(ator, self)?;
+                tracing::trace!(
+                    region = format_args!("{region:?}"),
+                    "de-init ok, reclaimed region"
+                );
+                self.regs.write(&mut self.tags, Register::A0 as _, region)?;
+            }
+
+            SyscallKind::AllocAlloc => {
+                let ator = self.regs.read(&self.tags, Register::A3 as _)?;
+                let layout: Layout = self.regs.read_ty(&self.tags, Register::A4 as _)?;
+                tracing::trace!(
+                    ator = format_args!("{ator:?}"),
+                    layout = format_args!("{layout:?}"),
+                    "requesting allocation"
+                );
+                let ation = alloc::alloc(ator, layout, self)?;
+                tracing::trace!(ation = format_args!("{ation:?}"), "allocation ok");
+                self.regs.write(&mut self.tags, Register::A0 as _, ation)?;
+            }
+
+            SyscallKind::AllocFree => {
+                let ator = self.regs.read(&self.tags, Register::A3 as _)?;
+                let ation = self.regs.read(&self.tags, Register::A4 as _)?;
+                tracing::trace!(
+                    ator = format_args!("{ator:?}"),
+                    ation = format_args!("{ation:?}"),
+                    "requesting allocation free"
+                );
+                alloc::free(ator, ation, self)?;
+                tracing::trace!("freeing ok");
+            }
+
+            SyscallKind::AllocFreeAll => {
+                let ator = self.regs.read(&self.tags, Register::A3 as _)?;
+                tracing::trace!(
+                    ator = format_args!("{ator:?}"),
+                    "requesting allocator free all"
+                );
+                alloc::free_all(ator, self)?;
+                tracing::trace!("freeing all ok");
+            }
+
+            SyscallKind::AllocStat => {
+                let ator = self.regs.read(&self.tags, Register::A3 as _)?;
+                tracing::trace!(ator = format_args!("{ator:?}"), "statting allocator");
+                let stats = alloc::stat(ator, self)?;
+                tracing::trace!(stats = format_args!("{stats}"), "statting ok");
+                // registers hold a granule of data
+                const _: () = assert!(Stats::LAYOUT.size <= UGRAN_SIZE as UAddr);
+                self.regs
+                    .write_ty(&mut self.tags, Register::A0 as _, stats)?;
+            }
+
+            SyscallKind::CapInfo => {
+                let tcap = self.regs.read(&self.tags, Register::A3 as _)?;
+                let dst = self.regs.read(&self.tags, Register::A4 as _)?;
+                self.write(dst, CapInfo::new(tcap))?;
+            }
+
+            SyscallKind::LayoutArray => {
+                let layout: Layout = self.regs.read_ty(&self.tags, Register::A3 as _)?;
+                let count: UAddr = self.regs.read_ty(&self.tags, Register::A4 as _)?;
+                let array = layout
+                    .array(count)
+                    .ok_or(Exception::LayoutOverflow { layout, count })?;
+                self.regs
+                    .write_ty(&mut self.tags, Register::A0 as _, array)?;
+            }
+
+            SyscallKind::MemSet => {
+                let dst = self.regs.read(&self.tags, Register::A3 as _)?;
+                let count: UAddr = self.regs.read_ty(&self.tags, Register::A4 as _)?;
+                let byte: u8 = self.regs.read_ty(&self.tags, Register::A5 as _)?;
+                self.memset(dst, count, byte)?;
+            }
+
+            SyscallKind::MemCpy => {
+                let dst = self.regs.read(&self.tags, Register::A3 as _)?;
+                let src = self.regs.read(&self.tags, Register::A4 as _)?;
+                let count: UAddr = self.regs.read_ty(&self.tags, Register::A5 as _)?;
+                self.memcpy(dst, src, count)?;
+            }
+
+            SyscallKind::AllocStatInto => {
+                let ator = self.regs.read(&self.tags, Register::A3 as _)?;
+                let dst = self.regs.read(&self.tags, Register::A4 as _)?;
+                tracing::trace!(ator = format_args!("{ator:?}"), "statting allocator");
+                let stats = alloc::stat(ator, self)?;
+                tracing::trace!(stats = format_args!("{stats}"), "statting ok");
+                self.write(dst, stats)?;
+            }
+
+            SyscallKind::AllocRealloc => {
+                let ator = self.regs.read(&self.tags, Register::A3 as _)?;
+                let ation = self.regs.read(&self.tags, Register::A4 as _)?;
+                let layout: Layout = self.regs.read_ty(&self.tags, Register::A5 as _)?;
+                tracing::trace!(
+                    ator = format_args!("{ator:?}"),
+                    ation = format_args!("{ation:?}"),
+                    layout = format_args!("{layout}"),
+                    "requesting reallocation"
+                );
+                let ation = alloc::realloc(ator, ation, layout, self)?;
+                tracing::trace!(ation = format_args!("{ation:?}"), "reallocation ok");
+                self.regs.write(&mut self.tags, Register::A0 as _, ation)?;
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Result of [`Memory::run`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunOutcome {
+    /// Number of operations executed.
+    pub ops: u64,
+    /// Whether the process exited.
+    pub exited: bool,
 }
 
+/// How [`Memory::execute_op`] selects the handler for an operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dispatch {
+    /// Match on the operation kind.
+    #[default]
+    Match,
+    /// Index a table of handlers by the operation kind's byte.
+    Table,
+}
+
+#[derive(Clone, Copy)]
+struct OpCtx {
+    op: Op,
+    pc: TaggedCapability,
+    inc_pc: TaggedCapability,
+}
+
+/// Returns the overridden program counter, if any.
+type OpResult = Result<Option<TaggedCapability>, Exception>;
+
+type OpHandler = fn(&mut Memory, OpCtx) -> OpResult;
+
+const DISPATCH_TABLE: [OpHandler; OpKind::COUNT as _] = [
+    Memory::exec_cgetvalid,
+    Memory::exec_cgetaddr,
+    Memory::exec_csetaddr,
+    Memory::exec_cgetbound,
+    Memory::exec_csetbound,
+    Memory::exec_cgetperm,
+    Memory::exec_csetperm,
+    Memory::exec_cgettype,
+    Memory::exec_cseal,
+    Memory::exec_cunseal,
+    Memory::exec_cpy,
+    Memory::exec_loadi,
+    Memory::exec_loadu8,
+    Memory::exec_loadu16,
+    Memory::exec_loadu32,
+    Memory::exec_loadu64,
+    Memory::exec_loadc,
+    Memory::exec_store8,
+    Memory::exec_store16,
+    Memory::exec_store32,
+    Memory::exec_store64,
+    Memory::exec_storec,
+    Memory::exec_addi,
+    Memory::exec_add,
+    Memory::exec_sub,
+    Memory::exec_sltsi,
+    Memory::exec_sltui,
+    Memory::exec_slts,
+    Memory::exec_sltu,
+    Memory::exec_xori,
+    Memory::exec_xor,
+    Memory::exec_ori,
+    Memory::exec_or,
+    Memory::exec_andi,
+    Memory::exec_and,
+    Memory::exec_slli,
+    Memory::exec_sll,
+    Memory::exec_srli,
+    Memory::exec_srl,
+    Memory::exec_srai,
+    Memory::exec_sra,
+    Memory::exec_jal,
+    Memory::exec_jalr,
+    Memory::exec_beq,
+    Memory::exec_bne,
+    Memory::exec_blts,
+    Memory::exec_bges,
+    Memory::exec_bltu,
+    Memory::exec_bgeu,
+    Memory::exec_syscall,
+    Memory::exec_addovf,
+    Memory::exec_subovf,
+    Memory::exec_mulovf,
+    Memory::exec_loadu8,
+    Memory::exec_loadu16,
+    Memory::exec_loadu32,
+    Memory::exec_loadu64,
+    Memory::exec_loadc,
+    Memory::exec_store8,
+    Memory::exec_store16,
+    Memory::exec_store32,
+    Memory::exec_store64,
+    Memory::exec_storec,
+    Memory::exec_csubset,
+    Memory::exec_cclear,
+];
+
 fn reg(tcap: TaggedCapability) -> u8 {
     tcap.to_ugran() as u8
 }
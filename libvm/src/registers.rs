@@ -13,7 +13,7 @@ pub struct Registers {
 }
 
 impl Registers {
-    pub const COUNT: u8 = 32;
+    pub const COUNT: u8 = 33;
 
     pub const fn new() -> Self {
         Self {
@@ -23,7 +23,7 @@ impl Registers {
 
     pub fn read(&self, tags: &TagController, reg: u8) -> Result<TaggedCapability, Exception> {
         let gran = self.read_data(reg)?;
-        let valid = if reg == Register::Zero as _ {
+        let valid = if reg == Register::Zero as u8 {
             false
         } else {
             tags.read_reg(reg).unwrap()
@@ -38,7 +38,7 @@ impl Registers {
         };
         access.check_reg()?;
         debug_assert!(access.check_len().is_ok());
-        if reg == Register::Zero as _ {
+        if reg == Register::Zero as u8 {
             return Ok(0);
         }
         let idx = Self::reg_to_idx(reg);
@@ -110,7 +110,7 @@ impl Registers {
     }
 
     pub const fn is_reg_valid(reg: u8) -> bool {
-        (reg & Self::MASK) == reg
+        reg < Self::COUNT
     }
 
     pub const fn is_len_valid(len: UAddr) -> bool {
@@ -118,14 +118,25 @@ impl Registers {
     }
 }
 
-impl Registers {
-    const MASK: u8 = 0b0001_1111;
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
+impl Registers {
     const fn reg_to_idx(reg: u8) -> usize {
-        (reg & Self::MASK) as usize
+        reg as usize
     }
 }
 
+/// Register names, in order of their index.
+///
+/// By convention, `t*` registers are temporaries which callees may clobber,
+/// `a*` registers hold arguments (`a2` selects the system call), and `s*`
+/// registers are saved by callees. `z*` registers are set up by the VM before
+/// the init program runs: `z0` holds the root allocator, and `z1` the data
+/// segment.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Register {
@@ -163,11 +174,53 @@ pub enum Register {
 
     // reserved, but currently used as magic place to find parent allocator
     Z0,
+
+    // reserved, holds the start of the data segment if there is one
+    Z1,
 }
 
 impl Register {
     pub const LAYOUT: Layout = UGran::LAYOUT;
 
+    pub const fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Zero),
+            1 => Some(Self::Pc),
+            2 => Some(Self::Ra),
+            3 => Some(Self::Sp),
+            4 => Some(Self::T0),
+            5 => Some(Self::T1),
+            6 => Some(Self::T2),
+            7 => Some(Self::T3),
+            8 => Some(Self::T4),
+            9 => Some(Self::T5),
+            10 => Some(Self::T6),
+            11 => Some(Self::A0),
+            12 => Some(Self::A1),
+            13 => Some(Self::A2),
+            14 => Some(Self::A3),
+            15 => Some(Self::A4),
+            16 => Some(Self::A5),
+            17 => Some(Self::A6),
+            18 => Some(Self::A7),
+            19 => Some(Self::S0),
+            20 => Some(Self::S1),
+            21 => Some(Self::S2),
+            22 => Some(Self::S3),
+            23 => Some(Self::S4),
+            24 => Some(Self::S5),
+            25 => Some(Self::S6),
+            26 => Some(Self::S7),
+            27 => Some(Self::S8),
+            28 => Some(Self::S9),
+            29 => Some(Self::S10),
+            30 => Some(Self::S11),
+            31 => Some(Self::Z0),
+            32 => Some(Self::Z1),
+            _ => None,
+        }
+    }
+
     pub const fn display(self) -> &'static str {
         match self {
             Self::Zero => "zero",
@@ -202,9 +255,11 @@ impl Register {
             Self::S10 => "s10",
             Self::S11 => "s11",
             Self::Z0 => "z0",
+            Self::Z1 => "z1",
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "zero" => Some(Self::Zero),
@@ -239,8 +294,21 @@ impl Register {
             "s10" => Some(Self::S10),
             "s11" => Some(Self::S11),
             "z0" => Some(Self::Z0),
-            _ => None,
+            "z1" => Some(Self::Z1),
+            _ => Self::from_numeric(s),
+        }
+    }
+
+    /// Parse a numeric alias, `x0` through `x32`, which names registers by
+    /// their byte representation.
+    fn from_numeric(s: &str) -> Option<Self> {
+        let digits = s.strip_prefix('x')?;
+        if !digits.bytes().all(|b| b.is_ascii_digit())
+            || (digits.len() > 1 && digits.starts_with('0'))
+        {
+            return None;
         }
+        Self::from_byte(digits.parse().ok()?)
     }
 }
 
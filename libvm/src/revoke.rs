@@ -1,41 +1,142 @@
-use crate::capability::{Address, Capability};
+use core::ops::Range;
+
+use crate::capability::{Address, Capability, OType, Permissions};
 use crate::exception::Exception;
-use crate::mem::{Memory, TagController};
+use crate::mem::{CapLocation, Memory, TagController};
 
 pub fn by_bounds(mem: &mut Memory, start: Address, endb: Address) -> Result<(), Exception> {
-    /* iterate through every bit in the tag controller. if it's valid, check if
-     * it matches the pattern. if it does, invalidate it. */
-    let root = mem.root;
-    for idx in 0..mem.tags.mem.len() {
-        if mem.tags.mem[idx] {
-            let cap: Capability = {
-                let mut cap = None;
-                if let Ok(reg) = u8::try_from(idx) {
-                    if let Ok(gran) = mem.regs.read_data(reg) {
-                        cap = Some(Capability::from_ugran(gran));
+    revoke_matching(mem, |cap| overlaps(cap, start, endb))
+}
+
+/// Invalidate all capabilities sealed with the object type `otype`.
+pub fn by_otype(mem: &mut Memory, otype: OType) -> Result<(), Exception> {
+    revoke_matching(mem, |cap| cap.otype() == otype)
+}
+
+/// Invalidate all capabilities granting any of the permissions in `mask`.
+pub fn by_perms(mem: &mut Memory, mask: Permissions) -> Result<(), Exception> {
+    revoke_matching(mem, |cap| cap.perms().intersects(mask))
+}
+
+/// Invalidate every capability, in registers and memory alike.
+pub fn sweep_all(mem: &mut Memory) -> Result<(), Exception> {
+    // no need to read what the tags guard when they're all cleared
+    mem.tags.mem.fill(false);
+    mem.icache = None;
+    Ok(())
+}
+
+/// Invalidate all capabilities to memory in the quarantine, emptying it.
+pub fn sweep_quarantine(mem: &mut Memory) -> Result<(), Exception> {
+    let mut quarantine = core::mem::take(&mut mem.quarantine);
+    let swept = quarantine.sweep(mem);
+    mem.quarantine = quarantine;
+    swept
+}
+
+/// Freed spans of memory whose capabilities are yet to be invalidated, so
+/// that many frees can be revoked in a single scan of memory.
+#[derive(Clone, Debug, Default)]
+pub struct Quarantine {
+    pending: Vec<Quarantined>,
+}
+
+#[derive(Clone, Debug)]
+struct Quarantined {
+    /// The allocator's bookkeeping, where capabilities are spared so the
+    /// allocator keeps working after a sweep.
+    meta: Range<Address>,
+    span: Range<Address>,
+}
+
+impl Quarantine {
+    /// Number of freed spans held before they are swept.
+    pub const CAPACITY: usize = 8;
+
+    /// Defer revoking capabilities to `span`, which was freed by the allocator
+    /// whose bookkeeping lies at `meta`.
+    pub fn quarantine(&mut self, meta: Range<Address>, span: Range<Address>) {
+        self.pending.push(Quarantined { meta, span });
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= Self::CAPACITY
+    }
+
+    /// Whether any quarantined span overlaps `start..endb`.
+    pub fn overlaps(&self, start: Address, endb: Address) -> bool {
+        self.pending
+            .iter()
+            .any(|freed| freed.span.start < endb && start < freed.span.end)
+    }
+
+    /// Invalidate all capabilities to quarantined memory in one pass, then
+    /// forget the quarantined spans.
+    pub fn sweep(&mut self, mem: &mut Memory) -> Result<(), Exception> {
+        let pending = core::mem::take(&mut self.pending);
+        revoke_matching_at(mem, |loc, cap| {
+            pending.iter().any(|freed| {
+                let in_meta = match loc {
+                    CapLocation::Granule(gran) => {
+                        gran.addr().is_some_and(|addr| freed.meta.contains(&addr))
                     }
-                }
-                if let Some(cap) = cap {
-                    cap
-                } else {
-                    let gran = TagController::idx_to_gran(idx)
-                        .expect("enumerated tag controller index is valid");
-                    /* NOTE: it's okay to create magic tcap here because
-                     * revoking capabilities is conceptually a privileged
-                     * process */
-                    mem.read(root.set_addr(gran.addr()))?
-                }
+                    CapLocation::Register(_) => false,
+                };
+                !in_meta && overlaps(cap, freed.span.start, freed.span.end)
+            })
+        })
+    }
+}
+
+fn overlaps(cap: Capability, start: Address, endb: Address) -> bool {
+    // NOTE: if a start equals an endb, that's not dangerous. it's "end by"
+    // after all, and start is the minimum address that *isn't* within the
+    // span.
+    (cap.start() >= start && cap.start() < endb) || (cap.endb() > start && cap.endb() <= endb)
+}
+
+fn revoke_matching<F: FnMut(Capability) -> bool>(
+    mem: &mut Memory,
+    mut pattern: F,
+) -> Result<(), Exception> {
+    revoke_matching_at(mem, |_, cap| pattern(cap))
+}
+
+fn revoke_matching_at<F: FnMut(CapLocation, Capability) -> bool>(
+    mem: &mut Memory,
+    mut pattern: F,
+) -> Result<(), Exception> {
+    /* find every tag matching the pattern, then clear them all at once. a
+     * tag which doesn't guard a readable, valid capability can't be checked
+     * against the pattern, so it's cleared too rather than left behind */
+    let matching: Vec<usize> = mem
+        .tags
+        .mem
+        .iter_ones()
+        .filter(|&idx| {
+            let Some(loc) = TagController::idx_to_loc(idx) else {
+                return true;
             };
-            // NOTE: if a start equals an endb, that's not dangerous. it's "end
-            // by" after all, and start is the minimum address that *isn't*
-            // within the span.
-            if (cap.start() >= start && cap.start() < endb)
-                || (cap.endb() > start && cap.endb() <= endb)
-            {
-                // this capability would have been able to access the pattern
-                *mem.tags.mem.get_mut(idx).unwrap() = false;
+            match mem.cap_at(loc) {
+                Some(tcap) if tcap.is_valid() => pattern(loc, tcap.capability()),
+                _ => true,
             }
-        }
+        })
+        .collect();
+    if !matching.is_empty() {
+        // the cached operation's operands may have been revoked
+        mem.icache = None;
+    }
+    for idx in matching {
+        mem.tags.mem.set(idx, false);
     }
     Ok(())
 }
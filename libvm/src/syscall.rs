@@ -2,8 +2,8 @@ use bitvec::slice::BitSlice;
 
 use core::fmt;
 
-use crate::abi::{Layout, Ty};
-use crate::capability::Address;
+use crate::abi::{self, Layout, StructMut, StructRef, Ty};
+use crate::capability::{Address, OType, Permissions, TaggedCapability};
 use crate::exception::Exception;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -30,9 +30,10 @@ pub enum SyscallKind {
     /// capability to the allocation is written to register `a0`.
     AllocAlloc,
 
-    // TODO: not implemented, docs subject to change
     /// Free a previously requested allocation from the allocator at register
-    /// `a3` with the allocation capability at register `a4`.
+    /// `a3` with the allocation capability at register `a4`. Bump allocators
+    /// can't free single allocations, and report
+    /// [`AllocErrKind::Unsupported`](crate::alloc::AllocErrKind::Unsupported).
     AllocFree,
 
     /// Free all allocations yielded by the allocator at register `a3`.
@@ -41,6 +42,36 @@ pub enum SyscallKind {
     /// Request [`Stats`](crate::alloc::Stats) on the allocator at register
     /// `a3`. On success, the `Stats` are written to register `a0`.
     AllocStat,
+
+    /// Write a [`CapInfo`] describing the capability at register `a3` to
+    /// memory at the capability in register `a4`.
+    CapInfo,
+
+    /// Compute the [`Layout`] of an array with `a4` elements, each with the
+    /// `Layout` at register `a3`. On success, the array's `Layout` is written
+    /// to register `a0`.
+    LayoutArray,
+
+    /// Fill `a4` bytes of memory at the capability in register `a3` with the
+    /// byte at register `a5`.
+    MemSet,
+
+    /// Copy `a5` bytes of memory from the capability in register `a4` to the
+    /// capability in register `a3`. The regions may overlap. Capabilities
+    /// within fully copied granules keep their tags, subject to
+    /// [`Memory::memcpy`](crate::mem::Memory::memcpy).
+    MemCpy,
+
+    /// Request [`Stats`](crate::alloc::Stats) on the allocator at register
+    /// `a3`, like [`SyscallKind::AllocStat`]. On success, the `Stats` are
+    /// written to memory at the capability in register `a4`.
+    AllocStatInto,
+
+    /// Resize the allocation at register `a4` from the allocator at register
+    /// `a3` to the [`Layout`] at register `a5`. On success, a capability to
+    /// the resized allocation is written to register `a0`. See
+    /// [`alloc::realloc`](crate::alloc::realloc).
+    AllocRealloc,
 }
 
 impl SyscallKind {
@@ -58,6 +89,12 @@ impl SyscallKind {
             4 => Ok(Self::AllocFree),
             5 => Ok(Self::AllocFreeAll),
             6 => Ok(Self::AllocStat),
+            7 => Ok(Self::CapInfo),
+            8 => Ok(Self::LayoutArray),
+            9 => Ok(Self::MemSet),
+            10 => Ok(Self::MemCpy),
+            11 => Ok(Self::AllocStatInto),
+            12 => Ok(Self::AllocRealloc),
             _ => Err(Exception::InvalidSyscall { byte }),
         }
     }
@@ -90,7 +127,78 @@ impl fmt::Display for SyscallKind {
             Self::AllocFree => "AllocFree",
             Self::AllocFreeAll => "AllocFreeAll",
             Self::AllocStat => "AllocStat",
+            Self::CapInfo => "CapInfo",
+            Self::LayoutArray => "LayoutArray",
+            Self::MemSet => "MemSet",
+            Self::MemCpy => "MemCpy",
+            Self::AllocStatInto => "AllocStatInto",
+            Self::AllocRealloc => "AllocRealloc",
         };
         f.write_str(s)
     }
 }
+
+/// Every field of a capability, as written by [`SyscallKind::CapInfo`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapInfo {
+    pub valid: bool,
+    pub addr: Address,
+    pub start: Address,
+    pub endb: Address,
+    pub perms: Permissions,
+    pub otype: OType,
+}
+
+impl CapInfo {
+    const FIELDS: &'static [Layout] = &[
+        bool::LAYOUT,
+        Address::LAYOUT,
+        Address::LAYOUT,
+        Address::LAYOUT,
+        Permissions::LAYOUT,
+        OType::LAYOUT,
+    ];
+
+    pub const fn new(tcap: TaggedCapability) -> Self {
+        Self {
+            valid: tcap.is_valid(),
+            addr: tcap.addr(),
+            start: tcap.start(),
+            endb: tcap.endb(),
+            perms: tcap.perms(),
+            otype: tcap.otype(),
+        }
+    }
+}
+
+impl Ty for CapInfo {
+    const LAYOUT: Layout = abi::layout(Self::FIELDS);
+
+    fn read(src: &[u8], addr: Address, valid: &BitSlice<u8>) -> Result<Self, Exception> {
+        let mut fields = StructRef::new(src, addr, valid, Self::FIELDS);
+        Ok(Self {
+            valid: fields.read_next::<bool>()?,
+            addr: fields.read_next::<Address>()?,
+            start: fields.read_next::<Address>()?,
+            endb: fields.read_next::<Address>()?,
+            perms: fields.read_next::<Permissions>()?,
+            otype: fields.read_next::<OType>()?,
+        })
+    }
+
+    fn write(
+        self,
+        dst: &mut [u8],
+        addr: Address,
+        valid: &mut BitSlice<u8>,
+    ) -> Result<(), Exception> {
+        let mut fields = StructMut::new(dst, addr, valid, Self::FIELDS);
+        fields.write_next(self.valid)?;
+        fields.write_next(self.addr)?;
+        fields.write_next(self.start)?;
+        fields.write_next(self.endb)?;
+        fields.write_next(self.perms)?;
+        fields.write_next(self.otype)?;
+        Ok(())
+    }
+}
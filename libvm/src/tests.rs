@@ -1,6 +1,8 @@
 mod serde {
     use crate::capability::{Address, Capability, OType, Permissions};
-    use crate::int::UGran;
+    use crate::int::{UGran, UGRAN_SIZE};
+    use crate::mem::Memory;
+    use crate::op::OpKind;
     use nanorand::{Pcg64, Rng};
 
     #[test]
@@ -26,14 +28,132 @@ mod serde {
             }
         }
     }
+
+    #[test]
+    fn capability_le_bytes() -> anyhow::Result<()> {
+        // NOTE: all encodings are little endian
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let tcap = mem
+            .root
+            .set_addr(Address(0x0024))
+            .set_bounds(Address(0x0010), Address(0x0040))
+            .set_perms(Permissions::READ | Permissions::GLOBAL);
+        let dst = mem.root.set_addr(Address(64));
+        mem.write(dst, tcap)?;
+        let bytes = mem.read_bytes(dst, UGRAN_SIZE.into())?;
+        assert_eq!(bytes, tcap.to_ugran().to_le_bytes());
+        assert_eq!(bytes[..2], [0x24, 0x00]); // addr is the low field
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() -> anyhow::Result<()> {
+        use crate::alloc::{InitFlags, Stats, Strategy};
+        use crate::capability::TaggedCapability;
+        use crate::op::Op;
+
+        let mem = Memory::new(16, 0, [].iter())?;
+        let op = Op {
+            kind: OpKind::CSeal,
+            op1: TaggedCapability::from_ugran(4),
+            op2: mem
+                .root
+                .set_bounds(Address(8), Address(64))
+                .set_perms(Permissions::READ | Permissions::LOAD_CAP),
+            op3: TaggedCapability::INVALID,
+        };
+        let json = serde_json::to_string(&op)?;
+        let back: Op = serde_json::from_str(&json)?;
+        assert_eq!(back, op);
+        assert!(back.op2.is_valid());
+        assert_eq!(back.op2.perms(), op.op2.perms());
+
+        let stats = Stats {
+            strategy: Strategy::Bump,
+            flags: InitFlags::INIT_ON_FREE,
+            bytes_free: 1234,
+            free_start: Address(64),
+        };
+        let json = serde_json::to_string(&stats)?;
+        assert_eq!(serde_json::from_str::<Stats>(&json)?, stats);
+        Ok(())
+    }
+
+    #[test]
+    fn op_kind_count() {
+        for byte in 0..OpKind::COUNT {
+            assert_eq!(OpKind::from_byte(byte).unwrap().to_byte(), byte);
+        }
+        assert!(OpKind::from_byte(OpKind::COUNT).is_err());
+        // new operations are appended, so existing bytecode keeps decoding
+        assert_eq!(OpKind::Syscall.to_byte(), 49);
+        assert_eq!(OpKind::AddOvf.to_byte(), 50);
+    }
+
+    #[test]
+    fn op_kind_classes() {
+        assert!(OpKind::Bgeu.is_branch());
+        assert!(OpKind::Jalr.is_jump());
+        assert!(OpKind::LoadU8.is_memory());
+        assert!(OpKind::StoreCO.is_memory());
+        assert!(OpKind::CSeal.is_cap());
+        assert!(OpKind::SraI.is_alu());
+
+        // moves and system calls don't belong to any class
+        for kind in [OpKind::Cpy, OpKind::LoadI, OpKind::Syscall] {
+            assert!(
+                !(kind.is_branch()
+                    || kind.is_jump()
+                    || kind.is_memory()
+                    || kind.is_cap()
+                    || kind.is_alu())
+            );
+        }
+
+        // every other op belongs to exactly one
+        for byte in 0..OpKind::COUNT {
+            let kind = OpKind::from_byte(byte).unwrap();
+            let classes = [
+                kind.is_branch(),
+                kind.is_jump(),
+                kind.is_memory(),
+                kind.is_cap(),
+                kind.is_alu(),
+            ];
+            let count = classes.into_iter().filter(|class| *class).count();
+            let expected = usize::from(!matches!(
+                kind,
+                OpKind::Cpy | OpKind::LoadI | OpKind::Syscall
+            ));
+            assert_eq!(count, expected, "{kind}");
+        }
+    }
+
+    #[test]
+    fn operand_roles() {
+        for byte in 0..OpKind::COUNT {
+            let kind = OpKind::from_byte(byte).unwrap();
+            let roles = kind.operand_roles();
+            let count = usize::from(kind.operand_count());
+            // used operands come first
+            assert!(roles[..count].iter().all(Option::is_some), "{kind}");
+            assert!(roles[count..].iter().all(Option::is_none), "{kind}");
+        }
+    }
 }
 
 mod revoke {
     use crate::abi::{Align, Layout};
+    use crate::alloc::{InitFlags, Strategy};
+    use crate::capability::{Address, Capability, OType, Permissions};
+    use crate::int::UGRAN_SIZE;
     use crate::mem::Memory;
-    use crate::registers::Register;
+    use crate::registers::{Register, Registers};
     use crate::{alloc, revoke};
 
+    use super::alloc::allocator;
+
     #[test]
     fn endb_is_harmless() -> anyhow::Result<()> {
         let mut mem = Memory::new(16, 0, [].iter())?;
@@ -53,12 +173,1141 @@ mod revoke {
         assert!(new_ation.is_valid());
         Ok(())
     }
+
+    #[test]
+    fn malformed_tagged() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        // reversed bounds can't be checked against what's being revoked
+        let malformed = Capability::new(
+            Address(64),
+            Address(72),
+            Address(64),
+            Permissions::READ,
+            OType::UNSEALED,
+        );
+        mem.write(mem.root.set_addr(Address(96)), malformed.to_ugran())?;
+        let idx = usize::from(Registers::COUNT + 96 / UGRAN_SIZE);
+        mem.tags.mem.set(idx, true);
+
+        revoke::by_bounds(&mut mem, Address(0), Address(8))?;
+        assert!(!mem.tags.mem[idx]);
+        Ok(())
+    }
+
+    #[test]
+    fn sweep_all() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        for addr in [64, 80, 96] {
+            mem.write(mem.root.set_addr(Address(addr)), mem.root)?;
+        }
+        mem.regs.write(&mut mem.tags, Register::T0 as _, mem.root)?;
+        // tagged, but not holding a well formed capability
+        let reversed = Capability::new(
+            Address(64),
+            Address(72),
+            Address(64),
+            Permissions::READ,
+            OType::UNSEALED,
+        );
+        mem.write(mem.root.set_addr(Address(112)), reversed.to_ugran())?;
+        mem.tags
+            .mem
+            .set(usize::from(Registers::COUNT + 112 / UGRAN_SIZE), true);
+        assert!(mem.tag_density() > 0.0);
+
+        revoke::sweep_all(&mut mem)?;
+        assert_eq!(mem.tag_density(), 0.0);
+        assert_eq!(mem.iter_tagged_caps().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn by_otype() -> anyhow::Result<()> {
+        let mut mem = Memory::new(256, 0, [].iter())?;
+        let root_cap = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+        let ation = alloc::alloc(
+            root_cap,
+            Layout {
+                size: 8,
+                align: Align::new(1).unwrap(),
+            },
+            &mut mem,
+        )?;
+        let sealer_a = mem.root.set_addr(Address(0));
+        let sealer_b = mem.root.set_addr(Address(OType::VALID_ALIGN.get()));
+        let sealed_a = ation.seal(sealer_a);
+        let sealed_b = ation.seal(sealer_b);
+        assert!(sealed_a.is_valid() && sealed_b.is_valid());
+        assert_ne!(sealed_a.otype(), sealed_b.otype());
+        mem.regs.write(&mut mem.tags, Register::T0 as _, sealed_a)?;
+        mem.regs.write(&mut mem.tags, Register::T1 as _, sealed_b)?;
+
+        revoke::by_otype(&mut mem, sealed_a.otype())?;
+        assert!(!mem.regs.read(&mem.tags, Register::T0 as _)?.is_valid());
+        assert!(mem.regs.read(&mem.tags, Register::T1 as _)?.is_valid());
+        Ok(())
+    }
+
+    #[test]
+    fn quarantine() -> anyhow::Result<()> {
+        let mut mem = Memory::new(512, 0, [].iter())?;
+        let ator = allocator(&mut mem, Strategy::Bump, InitFlags::QUARANTINE, 256)?;
+        let layout = Layout {
+            size: 8,
+            align: Align::new(1).unwrap(),
+        };
+        let is_valid = |mem: &Memory, reg: Register| -> anyhow::Result<bool> {
+            Ok(mem.regs.read(&mem.tags, reg as _)?.is_valid())
+        };
+
+        // freeing defers revocation until a sweep
+        let ation = alloc::alloc(ator, layout, &mut mem)?;
+        mem.regs.write(&mut mem.tags, Register::T0 as _, ation)?;
+        alloc::free_all(ator, &mut mem)?;
+        assert!(is_valid(&mem, Register::T0)?);
+        revoke::sweep_quarantine(&mut mem)?;
+        assert!(!is_valid(&mem, Register::T0)?);
+
+        // the allocator survives the sweep, and sweeps before reusing memory
+        let ation = alloc::alloc(ator, layout, &mut mem)?;
+        mem.regs.write(&mut mem.tags, Register::T1 as _, ation)?;
+        alloc::free_all(ator, &mut mem)?;
+        assert!(is_valid(&mem, Register::T1)?);
+        let reused = alloc::alloc(ator, layout, &mut mem)?;
+        assert_eq!(reused.start(), ation.start());
+        assert!(!is_valid(&mem, Register::T1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn by_perms() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let cap = mem.root.set_bounds(Address(0), Address(8));
+        let regs = [
+            (Register::T0, Permissions::READ, true),
+            (Register::T1, Permissions::READ | Permissions::WRITE, true),
+            (Register::T2, Permissions::SEAL, false),
+            (Register::T3, Permissions::READ | Permissions::UNSEAL, false),
+            (Register::T4, Permissions::empty(), true),
+        ];
+        for (reg, perms, _) in regs {
+            mem.regs
+                .write(&mut mem.tags, reg as _, cap.set_perms(perms))?;
+        }
+
+        revoke::by_perms(&mut mem, Permissions::SEAL | Permissions::UNSEAL)?;
+        for (reg, perms, expect_valid) in regs {
+            let cap = mem.regs.read(&mem.tags, reg as _)?;
+            assert_eq!(cap.perms(), perms);
+            assert_eq!(cap.is_valid(), expect_valid, "{reg}");
+        }
+        Ok(())
+    }
+}
+
+mod perms {
+    use crate::abi::Ty;
+    use crate::capability::{Address, Permissions, TaggedCapability};
+    use crate::exception::Exception;
+    use crate::mem::Memory;
+    use crate::op::{Op, OpKind};
+    use crate::registers::Register;
+
+    fn exec_op(
+        mem: &mut Memory,
+        kind: OpKind,
+        op1: Register,
+        op2: Register,
+    ) -> Result<(), Exception> {
+        let op = Op {
+            kind,
+            op1: TaggedCapability::from_ugran(op1 as _),
+            op2: TaggedCapability::from_ugran(op2 as _),
+            op3: TaggedCapability::from_ugran(0),
+        };
+        mem.execute_op(op, None, false)
+    }
+
+    fn slot(mem: &Memory, perms: Permissions) -> TaggedCapability {
+        let start = mem.root.endb().sub(TaggedCapability::LAYOUT.size);
+        mem.root
+            .set_addr(start)
+            .set_bounds(start, mem.root.endb())
+            .set_perms(perms)
+    }
+
+    #[test]
+    fn load_cap() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let stored = mem.root.set_addr(Address(8));
+        mem.write(slot(&mem, Permissions::WRITE), stored)?;
+
+        let src = slot(&mem, Permissions::READ);
+        mem.regs.write(&mut mem.tags, Register::T1 as _, src)?;
+        exec_op(&mut mem, OpKind::LoadC, Register::T0, Register::T1)?;
+        let loaded = mem.regs.read(&mem.tags, Register::T0 as _)?;
+        assert!(!loaded.is_valid());
+        assert_eq!(loaded.capability(), stored.capability());
+
+        let src = slot(&mem, Permissions::READ | Permissions::LOAD_CAP);
+        mem.regs.write(&mut mem.tags, Register::T1 as _, src)?;
+        exec_op(&mut mem, OpKind::LoadC, Register::T0, Register::T1)?;
+        assert_eq!(mem.regs.read(&mem.tags, Register::T0 as _)?, stored);
+        Ok(())
+    }
+
+    #[test]
+    fn store_cap() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let stored = mem.root.set_addr(Address(8));
+        mem.regs.write(&mut mem.tags, Register::T0 as _, stored)?;
+
+        let dst = slot(&mem, Permissions::WRITE);
+        mem.regs.write(&mut mem.tags, Register::T1 as _, dst)?;
+        assert!(matches!(
+            exec_op(&mut mem, OpKind::StoreC, Register::T1, Register::T0),
+            Err(Exception::InvalidMemAccess { .. })
+        ));
+
+        // data can still be stored
+        mem.regs
+            .write_data(&mut mem.tags, Register::T0 as _, stored.to_ugran())?;
+        exec_op(&mut mem, OpKind::StoreC, Register::T1, Register::T0)?;
+
+        mem.regs.write(&mut mem.tags, Register::T0 as _, stored)?;
+        let dst = slot(&mem, Permissions::WRITE | Permissions::STORE_CAP);
+        mem.regs.write(&mut mem.tags, Register::T1 as _, dst)?;
+        exec_op(&mut mem, OpKind::StoreC, Register::T1, Register::T0)?;
+        assert_eq!(
+            mem.read::<TaggedCapability>(mem.root.set_addr(dst.addr()))?,
+            stored
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn store_local() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let local = mem
+            .root
+            .set_addr(Address(8))
+            .set_perms(Permissions::all().difference(Permissions::GLOBAL));
+        mem.regs.write(&mut mem.tags, Register::T0 as _, local)?;
+        let stored_at = |mem: &Memory, dst: TaggedCapability| {
+            mem.read::<TaggedCapability>(mem.root.set_addr(dst.addr()))
+        };
+
+        // denied through a capability lacking STORE_LOCAL, even a local one
+        for perms in [
+            Permissions::all(),
+            Permissions::all().difference(Permissions::GLOBAL),
+        ] {
+            let dst = slot(&mem, perms.difference(Permissions::STORE_LOCAL));
+            mem.regs.write(&mut mem.tags, Register::T1 as _, dst)?;
+            exec_op(&mut mem, OpKind::StoreC, Register::T1, Register::T0)?;
+            let stored = stored_at(&mem, dst)?;
+            assert!(!stored.is_valid());
+            assert_eq!(stored.capability(), local.capability());
+        }
+
+        // allowed through a capability with STORE_LOCAL, even a global one
+        let dst = slot(&mem, Permissions::all());
+        mem.regs.write(&mut mem.tags, Register::T1 as _, dst)?;
+        exec_op(&mut mem, OpKind::StoreC, Register::T1, Register::T0)?;
+        assert_eq!(stored_at(&mem, dst)?, local);
+
+        // global capabilities may be stored anywhere
+        let dst = slot(
+            &mem,
+            Permissions::all().difference(Permissions::STORE_LOCAL),
+        );
+        mem.regs.write(&mut mem.tags, Register::T0 as _, mem.root)?;
+        mem.regs.write(&mut mem.tags, Register::T1 as _, dst)?;
+        exec_op(&mut mem, OpKind::StoreC, Register::T1, Register::T0)?;
+        assert_eq!(stored_at(&mem, dst)?, mem.root);
+        Ok(())
+    }
+}
+
+mod abi {
+    use crate::abi::{self, Align, Layout, Ty};
+    use crate::int::UAddr;
+    use crate::mem::Memory;
+
+    #[test]
+    fn granule_consistency() {
+        use crate::capability::{Capability, TaggedCapability};
+        use crate::int::{UGran, UGRAN_BITS, UGRAN_SIZE};
+
+        let gran = UAddr::from(UGRAN_SIZE);
+        assert_eq!(UGran::LAYOUT.size, gran);
+        assert_eq!(UGRAN_BITS, u32::from(UGRAN_SIZE) * 8);
+        assert_eq!(Capability::LAYOUT.size, gran);
+        assert_eq!(TaggedCapability::LAYOUT.size, gran);
+        assert_eq!(TaggedCapability::LAYOUT.align.get(), gran);
+        // every capability survives a trip through a granule
+        assert_eq!(
+            Capability::from_ugran(UGran::MAX).to_ugran(),
+            UGran::MAX,
+            "capability fields don't cover the granule"
+        );
+    }
+
+    #[test]
+    fn layout_display() {
+        let layout = |size, align| Layout {
+            size,
+            align: Align::new(align).unwrap(),
+        };
+        assert_eq!(layout(16, 8).to_string(), "Layout { 16 bytes, align 8 }");
+        assert_eq!(layout(1, 1).to_string(), "Layout { 1 byte, align 1 }");
+    }
+
+    fn round_trip<T: Ty + PartialEq>(mem: &mut Memory, val: T) -> anyhow::Result<()> {
+        let dst = mem
+            .root
+            .set_addr(mem.root.endb().sub(T::LAYOUT.size))
+            .set_bounds(mem.root.endb().sub(T::LAYOUT.size), mem.root.endb());
+        mem.write(dst, val)?;
+        assert_eq!(mem.read::<T>(dst)?, val);
+        Ok(())
+    }
+
+    #[test]
+    fn int_widths() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        round_trip(&mut mem, u8::MAX - 1)?;
+        round_trip(&mut mem, u16::MAX - 1)?;
+        round_trip(&mut mem, u32::MAX - 1)?;
+        round_trip(&mut mem, u64::MAX - 1)?;
+        round_trip(&mut mem, u128::MAX - 1)?;
+        round_trip(&mut mem, i8::MIN + 1)?;
+        round_trip(&mut mem, i16::MIN + 1)?;
+        round_trip(&mut mem, i32::MIN + 1)?;
+        round_trip(&mut mem, i64::MIN + 1)?;
+        round_trip(&mut mem, i128::MIN + 1)?;
+        Ok(())
+    }
+
+    #[test]
+    fn field_alignment() {
+        let field_offset = |offset: UAddr, align: Align| {
+            let pad = Layout {
+                size: offset,
+                align: Align::MIN,
+            };
+            let field = Layout { size: 0, align };
+            abi::layout(&[pad, field]).size
+        };
+
+        // previously, bumping to the next power of two overshot
+        assert_eq!(field_offset(9, Align::new(4).unwrap()), 12);
+        assert_eq!(field_offset(17, Align::new(8).unwrap()), 24);
+
+        for repr in 0..8 {
+            let align = Align::from_repr(repr).unwrap();
+            for offset in 0..300 {
+                let mut expect = offset;
+                while expect % align.get() != 0 {
+                    expect += 1;
+                }
+                assert_eq!(field_offset(offset, align), expect, "{offset} {align}");
+            }
+        }
+    }
+
+    #[test]
+    fn field_tags() -> anyhow::Result<()> {
+        use crate::capability::{Address, TaggedCapability};
+        use crate::op::{Op, OpKind};
+
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let op = Op {
+            kind: OpKind::Cpy,
+            op1: mem.root,
+            op2: TaggedCapability::from_ugran(0),
+            op3: TaggedCapability::from_ugran(0),
+        };
+        round_trip(&mut mem, op)?;
+
+        // each field's tag belongs to the granule holding it
+        let dst = mem.root.set_addr(Address(64));
+        mem.write(dst, op)?;
+        assert!(!mem.read::<TaggedCapability>(dst)?.is_valid());
+        assert_eq!(
+            mem.read::<TaggedCapability>(dst.set_addr(Address(72)))?,
+            mem.root
+        );
+        Ok(())
+    }
+}
+
+mod alloc {
+    use crate::abi::{Align, Layout, Ty};
+    use crate::alloc::{self, AllocErrKind, InitFlags, Stats, Strategy};
+    use crate::capability::{Address, OType, TaggedCapability};
+    use crate::exception::Exception;
+    use crate::int::{UAddr, UNINIT_BYTE};
+    use crate::mem::Memory;
+    use crate::registers::Register;
+
+    /// Initialize an allocator over `size` bytes from the root allocator.
+    pub(super) fn allocator(
+        mem: &mut Memory,
+        strat: Strategy,
+        flags: InitFlags,
+        size: UAddr,
+    ) -> Result<TaggedCapability, Exception> {
+        let root_alloc = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+        let region = Layout {
+            size,
+            align: OType::VALID_ALIGN,
+        };
+        let region = alloc::alloc(root_alloc, region, mem)?;
+        alloc::init(strat, flags, region, mem)
+    }
+
+    #[test]
+    fn stack() -> anyhow::Result<()> {
+        let mut mem = Memory::new(512, 0, [].iter())?;
+        let ator = allocator(&mut mem, Strategy::Stack, InitFlags::empty(), 256)?;
+        let bytes_free = alloc::stat(ator, &mem)?.bytes_free;
+
+        let ations = [(3, 1), (8, 8), (5, 2)].map(|(size, align)| {
+            let layout = Layout {
+                size,
+                align: Align::new(align).unwrap(),
+            };
+            alloc::alloc(ator, layout, &mut mem).unwrap()
+        });
+        for pair in ations.windows(2) {
+            assert!(pair[0].endb() <= pair[1].start());
+        }
+        mem.regs
+            .write(&mut mem.tags, Register::T0 as _, ations[2])?;
+
+        // only the most recent allocation may be freed
+        for ation in &ations[..2] {
+            let Err(Exception::AllocErr { err }) = alloc::free(ator, *ation, &mut mem) else {
+                panic!("out of order free should fail");
+            };
+            assert_eq!(err.kind, AllocErrKind::OutOfOrderFree);
+        }
+
+        for ation in ations.iter().rev() {
+            alloc::free(ator, *ation, &mut mem)?;
+        }
+        assert!(!mem.regs.read(&mem.tags, Register::T0 as _)?.is_valid());
+        assert_eq!(alloc::stat(ator, &mem)?.bytes_free, bytes_free);
+        assert!(alloc::free(ator, ations[0], &mut mem).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn double_free() -> anyhow::Result<()> {
+        let mut mem = Memory::new(512, 0, [].iter())?;
+        let ator = allocator(&mut mem, Strategy::Stack, InitFlags::empty(), 256)?;
+        let layout = Layout {
+            size: 0,
+            align: Align::new(1).unwrap(),
+        };
+        // empty allocations aren't mistaken for freed ones
+        let empty = alloc::alloc(ator, layout, &mut mem)?;
+        let ation = alloc::alloc(ator, Layout { size: 8, ..layout }, &mut mem)?;
+        alloc::free(ator, ation, &mut mem)?;
+        let Err(Exception::DoubleFree { addr }) = alloc::free(ator, ation, &mut mem) else {
+            panic!("double free should be caught");
+        };
+        assert_eq!(addr, ation.start());
+        alloc::free(ator, empty, &mut mem)?;
+        assert!(matches!(
+            alloc::free(ator, empty, &mut mem),
+            Err(Exception::DoubleFree { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn free_moved_addr() -> anyhow::Result<()> {
+        let mut mem = Memory::new(512, 0, [].iter())?;
+        let ator = allocator(&mut mem, Strategy::Stack, InitFlags::INIT_ON_FREE, 128)?;
+        let bytes_free = alloc::stat(ator, &mem)?.bytes_free;
+        let layout = Layout {
+            size: 16,
+            align: Align::new(8).unwrap(),
+        };
+        let ation = alloc::alloc(ator, layout, &mut mem)?;
+        mem.write_bytes(ation, &[1; 16])?;
+
+        // the address is past the start, but still within bounds
+        alloc::free(ator, ation.set_addr(ation.start().add(12)), &mut mem)?;
+        assert_eq!(alloc::stat(ator, &mem)?.bytes_free, bytes_free);
+        let freed = mem.root.set_addr(ation.start());
+        assert_eq!(mem.read_bytes(freed, 16)?, [UNINIT_BYTE; 16]);
+        Ok(())
+    }
+
+    #[test]
+    fn free_narrowed() -> anyhow::Result<()> {
+        let mut mem = Memory::new(512, 0, [].iter())?;
+        let ator = allocator(&mut mem, Strategy::Stack, InitFlags::empty(), 128)?;
+        let below = alloc::alloc(ator, u64::LAYOUT, &mut mem)?;
+        let layout = Layout {
+            size: 16,
+            align: Align::new(8).unwrap(),
+        };
+        let ation = alloc::alloc(ator, layout, &mut mem)?;
+
+        // pass off the start of the allocation as the link of a narrower one
+        let start = ation.start().add(8);
+        let narrowed = ation.set_addr(start).set_bounds(start, ation.endb());
+        assert!(narrowed.is_valid());
+        mem.write(ation, UAddr::MAX)?;
+        let Err(Exception::AllocErr { err }) = alloc::free(ator, narrowed, &mut mem) else {
+            panic!("only the exact allocation may be freed");
+        };
+        assert_eq!(err.kind, AllocErrKind::OutOfOrderFree);
+
+        assert_eq!(alloc::stat(ator, &mem)?.free_start, ation.endb());
+        alloc::free(ator, ation, &mut mem)?;
+        alloc::free(ator, below, &mut mem)?;
+        Ok(())
+    }
+
+    #[test]
+    fn largest_free() -> anyhow::Result<()> {
+        let mut mem = Memory::new(512, 0, [].iter())?;
+        for strat in [Strategy::Bump, Strategy::Stack] {
+            let ator = allocator(&mut mem, strat, InitFlags::empty(), 128)?;
+            let stats = alloc::stat(ator, &mem)?;
+            let endb = stats.free_start.add(stats.bytes_free);
+            let byte = Layout {
+                size: 1,
+                align: Align::new(1).unwrap(),
+            };
+            let _ = alloc::alloc(ator, byte, &mut mem)?;
+
+            // the next free address is misaligned, so padding is needed
+            let stats = alloc::stat(ator, &mem)?;
+            let align = Align::new(16).unwrap();
+            let largest = stats.largest_free(align);
+            assert!(largest < stats.bytes_free, "{strat:?}");
+            assert!(largest > 0, "{strat:?}");
+            let layout = |size| Layout { size, align };
+            assert!(alloc::alloc(ator, layout(largest + 1), &mut mem).is_err());
+            let ation = alloc::alloc(ator, layout(largest), &mut mem)?;
+            assert_eq!(ation.endb(), endb);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn realloc() -> anyhow::Result<()> {
+        let mut mem = Memory::new(1024, 0, [].iter())?;
+        let layout = |size| Layout {
+            size,
+            align: Align::new(8).unwrap(),
+        };
+        for flags in [InitFlags::INIT_ON_REALLOC, InitFlags::empty()] {
+            let ator = allocator(&mut mem, Strategy::Bump, flags, 128)?;
+            let ation = alloc::alloc(ator, layout(8), &mut mem)?;
+            mem.write_bytes(ation, &[1; 8])?;
+            // leftovers in the memory the allocation grows into
+            mem.write_bytes(mem.root.set_addr(ation.endb()), &[2; 8])?;
+
+            // grows in place
+            let grown = alloc::realloc(ator, ation, layout(16), &mut mem)?;
+            assert_eq!(grown.start(), ation.start());
+            assert_eq!(grown.span_len(), 16);
+            assert_eq!(mem.read_bytes(grown, 8)?, [1; 8]);
+            let tail = mem.read_bytes(grown.set_addr(ation.endb()), 8)?;
+            if flags.contains(InitFlags::INIT_ON_REALLOC) {
+                assert_eq!(tail, [UNINIT_BYTE; 8], "{flags}");
+            } else {
+                assert_eq!(tail, [2; 8], "{flags}");
+            }
+
+            // moves once it isn't the most recent allocation
+            let _ = alloc::alloc(ator, layout(8), &mut mem)?;
+            let moved = alloc::realloc(ator, grown, layout(24), &mut mem)?;
+            assert!(moved.start() > grown.endb());
+            assert_eq!(moved.span_len(), 24);
+            assert_eq!(mem.read_bytes(moved, 8)?, [1; 8]);
+        }
+
+        // shrinking in place gives up the tail, so the old capability to it
+        // must not alias the next allocation
+        for strat in [Strategy::Bump, Strategy::Stack] {
+            let ator = allocator(&mut mem, strat, InitFlags::empty(), 64)?;
+            let ation = alloc::alloc(ator, layout(16), &mut mem)?;
+            mem.set_register(Register::T0, ation)?;
+            let shrunk = alloc::realloc(ator, ation, layout(8), &mut mem)?;
+            assert_eq!(shrunk.start(), ation.start(), "{strat:?}");
+            assert_eq!(shrunk.span_len(), 8, "{strat:?}");
+            assert!(!mem.register(Register::T0)?.is_valid(), "{strat:?}");
+
+            assert_eq!(
+                alloc::stat(ator, &mem)?.free_start,
+                shrunk.endb(),
+                "{strat:?}"
+            );
+            let _ = alloc::alloc(ator, layout(8), &mut mem)?;
+            let old = mem.register(Register::T0)?.set_addr(shrunk.endb());
+            assert!(mem.write(old, 1_u64).is_err(), "{strat:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stats_display() {
+        let stats = |flags, bytes_free| Stats {
+            strategy: Strategy::Bump,
+            flags,
+            bytes_free,
+            free_start: Address(0),
+        };
+        assert_eq!(
+            stats(InitFlags::INIT_ON_ALLOC | InitFlags::INIT_ON_FREE, 4096).to_string(),
+            "Stats { bump, init-on-alloc|init-on-free, 4096 bytes free }"
+        );
+        assert_eq!(
+            stats(InitFlags::empty(), 1).to_string(),
+            "Stats { bump, no flags, 1 byte free }"
+        );
+    }
+
+    #[test]
+    fn layouts() -> anyhow::Result<()> {
+        let mut mem = Memory::new(64, 0, [].iter())?;
+        let root_cap = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+
+        // zero sized requests yield empty capabilities
+        let ation = alloc::alloc(
+            root_cap,
+            Layout {
+                size: 0,
+                align: Align::new(8).unwrap(),
+            },
+            &mut mem,
+        )?;
+        assert!(ation.is_valid());
+        assert_eq!(ation.span_len(), 0);
+
+        // alignment read from memory may not fit in an address
+        let buf = mem.root.set_addr(Address(256));
+        mem.write_bytes(buf, &[8, 0, 20])?;
+        let layout: Layout = mem.read(buf)?;
+        assert!(!layout.align.is_valid());
+        let Err(Exception::AllocErr { err }) = alloc::alloc(root_cap, layout, &mut mem) else {
+            panic!("malformed layout should be rejected");
+        };
+        assert_eq!(err.kind, AllocErrKind::InvalidLayout);
+        assert!(format!("{}", Exception::AllocErr { err }).contains("2^20"));
+        Ok(())
+    }
+}
+
+mod mem {
+    use crate::abi::{Align, Layout, Ty};
+    use crate::alloc::{self, InitFlags, Strategy};
+    use crate::capability::{Address, Capability, OType, Permissions, TaggedCapability};
+    use crate::exception::Exception;
+    use crate::int::UNINIT_BYTE;
+    use crate::mem::Memory;
+    use crate::registers::Register;
+
+    #[test]
+    fn address_space() {
+        use crate::int::{UAddr, UGRAN_SIZE};
+
+        assert_eq!(u32::from(Address::BITS), UAddr::BITS);
+        assert_eq!(Address(UAddr::MAX).get(), UAddr::MAX);
+
+        // the largest memory which fits is accepted
+        let max = UAddr::MAX / UAddr::from(UGRAN_SIZE);
+        assert!(Memory::new(max, 0, [].iter()).is_ok());
+        // anything larger doesn't fit in the address space
+        let err = Memory::new(max + 1, 0, [].iter()).unwrap_err();
+        assert!(err.to_string().contains("address space"), "{err}");
+    }
+
+    #[test]
+    fn bytes_round_trip() -> anyhow::Result<()> {
+        let mut mem = Memory::new(64, 0, [].iter())?;
+        let buf = mem
+            .root
+            .set_addr(Address(256))
+            .set_bounds(Address(256), Address(272));
+        mem.write(buf, mem.root)?;
+        mem.write_bytes(buf.set_addr(Address(259)), b"fruticose")?;
+        assert_eq!(mem.read_bytes(buf.set_addr(Address(259)), 9)?, b"fruticose");
+        // the capability was overwritten
+        assert!(!mem.read::<TaggedCapability>(buf)?.is_valid());
+
+        // accesses are bounds checked
+        assert!(matches!(
+            mem.read_bytes(buf.set_addr(Address(264)), 9),
+            Err(Exception::InvalidMemAccess { .. })
+        ));
+        assert!(matches!(
+            mem.write_bytes(buf.set_addr(Address(270)), b"abc"),
+            Err(Exception::InvalidMemAccess { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn memset_top_of_memory() -> anyhow::Result<()> {
+        let mut mem = Memory::new(256, 0, [].iter())?;
+        let mem_endb = mem.root.endb();
+        let top = OType::VALID_ALIGN.get();
+        let region = mem
+            .root
+            .set_addr(Address(top))
+            .set_bounds(Address(top), mem_endb);
+        let ator = alloc::init(Strategy::Bump, InitFlags::INIT_ON_ALLOC, region, &mut mem)?;
+        let bytes_free = alloc::stat(ator, &mem)?.bytes_free;
+
+        // allocation ends exactly at the end of physical memory
+        let ation = alloc::alloc(
+            ator,
+            Layout {
+                size: bytes_free,
+                align: Align::new(1).unwrap(),
+            },
+            &mut mem,
+        )?;
+        assert_eq!(ation.endb(), mem_endb);
+        mem.memset(ation, ation.span_len(), 0)?;
+
+        // capability is valid, but points past the end of physical memory
+        let beyond = TaggedCapability::new(
+            Capability::new(
+                mem_endb,
+                mem_endb,
+                mem_endb.add(8),
+                Permissions::all(),
+                OType::UNSEALED,
+            ),
+            true,
+        );
+        assert!(matches!(
+            mem.memset(beyond, 8, 0),
+            Err(Exception::InvalidMemAccess { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn uninitialized_read() -> anyhow::Result<()> {
+        let mut mem = Memory::new(32, 0, [].iter())?;
+        let root_alloc = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+        mem.set_track_uninit(true);
+        let ation = alloc::alloc(root_alloc, u64::LAYOUT.array(2).unwrap(), &mut mem)?;
+        let second = ation.set_addr(ation.addr().add(8));
+
+        assert!(matches!(
+            mem.read::<u64>(second),
+            Err(Exception::UninitializedRead { addr }) if addr == second.addr()
+        ));
+        mem.write(ation, 1_u64)?;
+        assert_eq!(mem.read::<u64>(ation)?, 1);
+        assert!(mem.read::<u64>(second).is_err());
+
+        // fresh allocations are uninitialized again
+        alloc::free_all(root_alloc, &mut mem)?;
+        let ation = alloc::alloc(root_alloc, u64::LAYOUT, &mut mem)?;
+        assert!(matches!(
+            mem.read::<u64>(ation),
+            Err(Exception::UninitializedRead { .. })
+        ));
+
+        // no longer raised once disabled
+        mem.set_track_uninit(false);
+        assert_eq!(
+            mem.read::<u64>(ation)?,
+            u64::from_le_bytes([UNINIT_BYTE; 8])
+        );
+        Ok(())
+    }
+
+    fn read_bytes(mem: &Memory, addr: u16, count: u16) -> Result<Vec<u8>, Exception> {
+        (addr..addr + count)
+            .map(|addr| mem.read(mem.root.set_addr(Address(addr))))
+            .collect()
+    }
+
+    #[test]
+    fn memcpy_tags() -> anyhow::Result<()> {
+        let mut mem = Memory::new(32, 0, [].iter())?;
+        let stored = mem
+            .root
+            .set_addr(Address(64))
+            .set_bounds(Address(64), Address(72));
+        mem.write(mem.root.set_addr(Address(128)), 0xaa_u8)?;
+        mem.write(mem.root.set_addr(Address(136)), stored)?;
+
+        // aligned copy preserves tags of fully overwritten granules
+        let src = mem.root.set_addr(Address(128));
+        mem.memcpy(mem.root.set_addr(Address(192)), src, 16)?;
+        assert_eq!(mem.read::<u8>(mem.root.set_addr(Address(192)))?, 0xaa);
+        assert_eq!(
+            mem.read::<TaggedCapability>(mem.root.set_addr(Address(200)))?,
+            stored
+        );
+
+        // misaligned copy clears tags
+        mem.memcpy(mem.root.set_addr(Address(161)), src, 16)?;
+        assert_eq!(
+            read_bytes(&mem, 169, 8)?,
+            stored.capability().to_ugran().to_le_bytes()
+        );
+        assert!(!mem.tags.grans(Address(168), 16).unwrap().any());
+
+        // tags aren't copied without permission
+        let dst = mem
+            .root
+            .set_addr(Address(224))
+            .set_perms(Permissions::all().difference(Permissions::STORE_CAP));
+        mem.memcpy(dst, src, 16)?;
+        let copied = mem.read::<TaggedCapability>(mem.root.set_addr(Address(232)))?;
+        assert!(!copied.is_valid());
+        assert_eq!(copied.capability(), stored.capability());
+        Ok(())
+    }
+
+    #[test]
+    fn memcpy_overlapping() -> anyhow::Result<()> {
+        let mut mem = Memory::new(32, 0, [].iter())?;
+        let bytes: Vec<u8> = (0..8).collect();
+        mem.write_iter(mem.root.set_addr(Address(64)), bytes.iter())?;
+
+        // forward overlap behaves like memmove
+        mem.memcpy(
+            mem.root.set_addr(Address(66)),
+            mem.root.set_addr(Address(64)),
+            8,
+        )?;
+        assert_eq!(read_bytes(&mem, 64, 10)?, [0, 1, 0, 1, 2, 3, 4, 5, 6, 7]);
+
+        // backward overlap too
+        mem.memcpy(
+            mem.root.set_addr(Address(64)),
+            mem.root.set_addr(Address(66)),
+            8,
+        )?;
+        assert_eq!(read_bytes(&mem, 64, 8)?, bytes);
+
+        // source must be readable
+        assert!(matches!(
+            mem.memcpy(
+                mem.root.set_addr(Address(64)),
+                mem.root.set_addr(Address(66)).set_perms(Permissions::WRITE),
+                8,
+            ),
+            Err(Exception::InvalidMemAccess { .. })
+        ));
+        Ok(())
+    }
+}
+
+mod coredump {
+    use crate::capability::{Address, TaggedCapability};
+    use crate::int::UAddr;
+    use crate::mem::Memory;
+    use crate::registers::Register;
+
+    #[test]
+    fn round_trip() -> anyhow::Result<()> {
+        let mut mem = Memory::new(64, 16, [].iter())?;
+        let cap = mem
+            .root
+            .set_addr(Address(72))
+            .set_bounds(Address(64), Address(128));
+        mem.write(mem.root.set_addr(Address(256)), cap)?;
+        mem.write(mem.root.set_addr(Address(300)), 0xabcd_u16)?;
+        mem.regs.write(&mut mem.tags, Register::T0 as _, cap)?;
+        mem.regs.write_data(&mut mem.tags, Register::T1 as _, 42)?;
+
+        let mut image = Vec::new();
+        mem.dump_core(&mut image)?;
+        let loaded = Memory::load_core(image.as_slice())?;
+
+        assert_eq!(loaded.mem, mem.mem);
+        assert_eq!(loaded.tags.mem, mem.tags.mem);
+        assert_eq!(loaded.root, mem.root);
+        assert_eq!(
+            loaded.read::<TaggedCapability>(loaded.root.set_addr(Address(256)))?,
+            cap
+        );
+        assert_eq!(
+            loaded.read::<u16>(loaded.root.set_addr(Address(300)))?,
+            0xabcd
+        );
+        for reg in [
+            Register::T0,
+            Register::T1,
+            Register::Sp,
+            Register::Pc,
+            Register::Z0,
+        ] {
+            assert_eq!(
+                loaded.regs.read(&loaded.tags, reg as _)?,
+                mem.regs.read(&mem.tags, reg as _)?,
+                "{reg}"
+            );
+        }
+
+        // truncated and foreign images are rejected
+        assert!(Memory::load_core(&image[..image.len() - 1]).is_err());
+        image[0] ^= 0xff;
+        assert!(Memory::load_core(image.as_slice()).is_err());
+        image[0] ^= 0xff;
+
+        // images which claim more memory than is addressable are rejected
+        // before anything is allocated
+        let granules = 8..8 + size_of::<UAddr>();
+        image[granules].copy_from_slice(&UAddr::MAX.to_le_bytes());
+        let err = Memory::load_core(image.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("address space"), "{err}");
+        Ok(())
+    }
+}
+
+#[cfg(feature = "viz")]
+mod viz {
+    use crate::capability::{Address, Permissions};
+    use crate::mem::Memory;
+    use crate::registers::Register;
+
+    #[test]
+    fn stored_cap_edge() -> anyhow::Result<()> {
+        let mut mem = Memory::new(64, 0, [].iter())?;
+        let holder = mem
+            .root
+            .set_addr(Address(256))
+            .set_bounds(Address(256), Address(288));
+        let target = mem
+            .root
+            .set_addr(Address(384))
+            .set_bounds(Address(384), Address(400))
+            .set_perms(Permissions::READ);
+        mem.regs.write(&mut mem.tags, Register::T0 as _, holder)?;
+        mem.write(holder.set_addr(Address(264)), target)?;
+
+        let mut dot = Vec::new();
+        mem.write_dot(&mut dot)?;
+        let dot = String::from_utf8(dot)?;
+        assert!(dot.starts_with("digraph capabilities {"), "{dot}");
+        // the register points to the holder, which points to the target
+        assert!(dot.contains("reg_t0 -> region_0x0100_0x0120"), "{dot}");
+        assert!(
+            dot.contains(
+                "region_0x0100_0x0120 -> region_0x0180_0x0190 [label=\"r-------l @ 0x0108\"]"
+            ),
+            "{dot}"
+        );
+        Ok(())
+    }
+}
+
+mod tags {
+    use crate::capability::Address;
+    use crate::mem::{CapLocation, Memory};
+    use crate::registers::Register;
+    use crate::revoke;
+
+    #[test]
+    fn iter_tagged_caps() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let cap = mem
+            .root
+            .set_addr(Address(64))
+            .set_bounds(Address(64), Address(72));
+        let dst = mem.root.set_addr(Address(96));
+        mem.write(dst, cap)?;
+        mem.regs.write(&mut mem.tags, Register::T0 as _, cap)?;
+
+        let caps: Vec<_> = mem.iter_tagged_caps().collect();
+        assert!(caps.contains(&(CapLocation::Granule(dst.addr().gran()), cap)));
+        assert!(caps.contains(&(CapLocation::Register(Register::T0), cap)));
+        assert!(caps.iter().all(|(_, tcap)| tcap.is_valid()));
+        Ok(())
+    }
+
+    #[test]
+    fn density() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let (count, density) = (mem.tags.count_valid(), mem.tag_density());
+        let cap = mem
+            .root
+            .set_addr(Address(64))
+            .set_bounds(Address(64), Address(72));
+        for addr in [96, 104] {
+            mem.write(mem.root.set_addr(Address(addr)), cap)?;
+        }
+        // registers aren't counted
+        mem.regs.write(&mut mem.tags, Register::T0 as _, cap)?;
+        assert_eq!(mem.tags.count_valid(), count + 2);
+        assert_eq!(mem.tag_density(), density + 2.0 / 16.0);
+
+        revoke::by_bounds(&mut mem, Address(64), Address(72))?;
+        assert_eq!(mem.tags.count_valid(), count);
+        assert_eq!(mem.tag_density(), density);
+        Ok(())
+    }
+}
+
+mod registers {
+    use crate::capability::{Address, TaggedCapability};
+    use crate::mem::Memory;
+    use crate::registers::{Register, Registers};
+
+    #[test]
+    fn distinct_storage() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let regs: Vec<Register> = (0..Registers::COUNT)
+            .map(|byte| Register::from_byte(byte).unwrap())
+            .collect();
+        assert_eq!(regs.len(), usize::from(Registers::COUNT));
+
+        // give each register its own value, alternating tags
+        let val = |idx: usize| {
+            let cap = mem.root.set_addr(Address(idx as _));
+            TaggedCapability::new(cap.capability(), idx.is_multiple_of(2))
+        };
+        for (idx, reg) in regs.iter().enumerate().skip(1) {
+            mem.regs.write(&mut mem.tags, *reg as _, val(idx))?;
+        }
+        for (idx, reg) in regs.iter().enumerate().skip(1) {
+            assert_eq!(mem.regs.read(&mem.tags, *reg as _)?, val(idx), "{reg}");
+        }
+
+        // reserved registers don't overlap general ones
+        let s11 = mem.regs.read(&mem.tags, Register::S11 as _)?;
+        mem.regs
+            .write(&mut mem.tags, Register::Z0 as _, TaggedCapability::INVALID)?;
+        mem.regs
+            .write(&mut mem.tags, Register::Z1 as _, TaggedCapability::INVALID)?;
+        assert_eq!(mem.regs.read(&mem.tags, Register::S11 as _)?, s11);
+        Ok(())
+    }
+
+    #[test]
+    fn accessors() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let root = mem.root_capability();
+        assert_eq!(root, mem.root);
+        assert_eq!(root.start(), Address(0));
+        assert_eq!(root.endb(), Address(16 * 8));
+
+        let tcap = root.set_addr(Address(24));
+        mem.regs.write(&mut mem.tags, Register::T3 as _, tcap)?;
+        assert_eq!(mem.register(Register::T3)?, tcap);
+        assert_eq!(mem.tags().read_reg(Register::T3 as _), Some(true));
+        Ok(())
+    }
 }
 
 mod capability {
     use crate::abi::Align;
     use crate::access::MemAccessKind;
-    use crate::capability::{Address, Capability, OType, Permissions, TaggedCapability};
+    use crate::capability::{Address, Capability, DeriveErr, OType, Permissions, TaggedCapability};
+    use crate::exception::Exception;
+    use crate::int::UAddr;
+    use crate::mem::Memory;
+
+    #[test]
+    fn otype_valid_align() {
+        let align = OType::VALID_ALIGN.get();
+        assert_eq!(
+            u32::from(align),
+            1 << (Address::BITS - OType::BITS),
+            "every address should map to an object type"
+        );
+        for addr in 0..=UAddr::MAX {
+            let otype = OType::from_addr(Address(addr));
+            assert_eq!(otype.is_some(), addr % align == 0, "{addr}");
+            if let Some(otype) = otype {
+                assert_eq!(otype.get_addr(), Address(addr));
+            }
+        }
+    }
+
+    #[test]
+    fn permission_bits() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let dst = mem.root.set_addr(Address(64));
+        mem.write(dst, mem.root)?;
+        let loaded: TaggedCapability = mem.read(dst)?;
+        assert_eq!(loaded.perms(), Permissions::all());
+        assert!(loaded.is_valid());
+
+        // a tagged granule setting an undefined permission reads back untagged
+        let undefined = Permissions::from_bits_retain(1 << (Permissions::BITS - 1));
+        assert!(!Permissions::all().contains(undefined));
+        let forged = mem.root.capability();
+        let forged = Capability::new(
+            forged.addr(),
+            forged.start(),
+            forged.endb(),
+            forged.perms().union(undefined),
+            forged.otype(),
+        );
+        mem.write(dst, forged.to_ugran())?;
+        mem.tags.grans_mut(dst.addr(), 1).unwrap().set(0, true);
+        let read: TaggedCapability = mem.read(dst)?;
+        assert_eq!(read.capability(), forged);
+        assert!(!read.is_valid());
+        Ok(())
+    }
+
+    #[test]
+    fn granule_addr() {
+        use crate::capability::Granule;
+        use crate::int::{UAddr, UGRAN_SIZE};
+
+        for addr in [0, 8, 64, 0x1238] {
+            let gran = Address(addr).gran();
+            assert_eq!(gran.addr(), Some(Address(addr)));
+            // every byte in a granule maps to its start
+            assert_eq!(Address(addr + 7).gran(), gran);
+        }
+        assert_eq!(
+            Granule(3).addr(),
+            Some(Address(3 * UAddr::from(UGRAN_SIZE)))
+        );
+
+        // granules past the address space don't panic
+        assert_eq!(Granule(UAddr::MAX).addr(), None);
+    }
+
+    #[test]
+    fn granule_round_trip() {
+        use crate::int::{UAddr, UGRAN_SIZE};
+
+        let align = Align::new(UAddr::from(UGRAN_SIZE)).unwrap();
+        for addr in (0..=UAddr::MAX).map(Address) {
+            let rounded = addr.gran().addr().unwrap();
+            assert_eq!(rounded, addr.align_down(align), "{addr}");
+        }
+    }
+
+    #[test]
+    fn is_sealed() -> anyhow::Result<()> {
+        let mem = Memory::new(16, 0, [].iter())?;
+        let unsealed = mem.root.set_bounds(Address(0), Address(8));
+        let sealed = unsealed.seal(mem.root.set_addr(Address(0)));
+        assert!(sealed.is_valid());
+        for tcap in [unsealed, sealed, TaggedCapability::INVALID] {
+            assert_eq!(tcap.is_sealed(), tcap.otype().is_sealed());
+            assert_eq!(tcap.is_unsealed(), tcap.otype().is_unsealed());
+            assert_ne!(tcap.is_sealed(), tcap.is_unsealed());
+        }
+        assert!(sealed.is_sealed());
+        assert!(unsealed.is_unsealed());
+        Ok(())
+    }
 
     #[test]
     fn is_bounded() {
@@ -117,6 +1366,178 @@ mod capability {
         assert!(!oob_left.is_bounded_with_len(18));
     }
 
+    #[test]
+    fn contains() {
+        let cap = |start, endb| {
+            Capability::new(
+                Address(start),
+                Address(start),
+                Address(endb),
+                Permissions::empty(),
+                OType::UNSEALED,
+            )
+        };
+        let outer = cap(8, 16);
+        assert!(outer.contains(&outer)); // equal
+        assert!(outer.contains(&cap(9, 15))); // nested
+        assert!(outer.contains(&cap(8, 8))); // empty at start
+        assert!(outer.contains(&cap(16, 16))); // empty at endb
+        assert!(!cap(9, 15).contains(&outer)); // nesting is not symmetric
+        assert!(!outer.contains(&cap(4, 12))); // overlapping left
+        assert!(!outer.contains(&cap(12, 20))); // overlapping right
+        assert!(!outer.contains(&cap(0, 8))); // disjoint, adjacent
+        assert!(!outer.contains(&cap(24, 32))); // disjoint
+    }
+
+    #[test]
+    fn well_formed() {
+        let cap = |addr, start, endb| {
+            Capability::new(
+                Address(addr),
+                Address(start),
+                Address(endb),
+                Permissions::empty(),
+                OType::UNSEALED,
+            )
+        };
+        assert!(cap(8, 8, 16).is_well_formed());
+        assert!(cap(16, 8, 16).is_well_formed());
+        assert!(cap(8, 8, 8).is_well_formed());
+        assert!(!cap(4, 8, 16).is_well_formed());
+        assert!(!cap(17, 8, 16).is_well_formed());
+        assert!(!cap(8, 16, 8).is_well_formed());
+    }
+
+    #[test]
+    fn read_reversed_bounds() -> anyhow::Result<()> {
+        let mut mem = Memory::new(16, 0, [].iter())?;
+        let reversed = Capability::new(
+            Address(8),
+            Address(16),
+            Address(0),
+            Permissions::all(),
+            OType::UNSEALED,
+        );
+        let dst = mem.root.set_addr(Address(96));
+
+        // a tagged granule holding a malformed capability reads back untagged
+        mem.write(dst, reversed.to_ugran())?;
+        mem.tags.grans_mut(dst.addr(), 1).unwrap().set(0, true);
+        let read: TaggedCapability = mem.read(dst)?;
+        assert_eq!(read.capability(), reversed);
+        assert!(!read.is_valid());
+
+        // and can't be written tagged
+        mem.write(dst, TaggedCapability::new(reversed, true))?;
+        assert!(!mem.read::<TaggedCapability>(dst)?.is_valid());
+        Ok(())
+    }
+
+    #[test]
+    fn display() {
+        let cap = TaggedCapability::new(
+            Capability::new(
+                Address(4),
+                Address(0),
+                Address(16),
+                Permissions::READ | Permissions::WRITE | Permissions::EXEC | Permissions::GLOBAL,
+                OType::UNSEALED,
+            ),
+            true,
+        );
+        assert_eq!(
+            cap.to_string(),
+            "cap[0x0000..0x0010 @0x0004 rwx-----g unsealed]"
+        );
+        let sealer = TaggedCapability::new(
+            Capability::new(
+                Address(0),
+                Address(0),
+                Address(16),
+                Permissions::SEAL,
+                OType::UNSEALED,
+            ),
+            true,
+        );
+        let sealed = cap.seal(sealer);
+        assert_eq!(
+            sealed.to_string(),
+            "cap[0x0000..0x0010 @0x0004 rwx-----g sealed 0x0000]"
+        );
+        assert_eq!(
+            TaggedCapability::from_ugran(0x1234).to_string(),
+            "0x0000000000001234"
+        );
+    }
+
+    #[test]
+    fn derive() {
+        let root = TaggedCapability::new(
+            Capability::new(
+                Address(0),
+                Address(0),
+                Address(64),
+                Permissions::READ | Permissions::WRITE,
+                OType::UNSEALED,
+            ),
+            true,
+        );
+        let derive = |addr, start, endb, perms| {
+            let derived = root.derive(Address(addr), Address(start), Address(endb), perms);
+            let tried = root.try_derive(Address(addr), Address(start), Address(endb), perms);
+            assert_eq!(derived.is_valid(), tried.is_ok());
+            if let Ok(tried) = tried {
+                assert_eq!(derived, tried);
+            }
+            tried
+        };
+
+        let sub = derive(12, 8, 16, Permissions::READ).unwrap();
+        assert_eq!(sub.addr(), Address(12));
+        assert_eq!(sub.start(), Address(8));
+        assert_eq!(sub.endb(), Address(16));
+        assert_eq!(sub.perms(), Permissions::READ);
+        assert!(derive(0, 0, 64, root.perms()).is_ok());
+        assert!(derive(0, 0, 0, Permissions::empty()).is_ok());
+
+        assert_eq!(derive(0, 0, 65, Permissions::READ), Err(DeriveErr::Bounds));
+        assert_eq!(derive(16, 16, 8, Permissions::READ), Err(DeriveErr::Bounds));
+        assert_eq!(derive(0, 0, 8, Permissions::EXEC), Err(DeriveErr::Perms));
+        assert_eq!(
+            sub.try_derive(Address(8), Address(0), Address(16), Permissions::READ),
+            Err(DeriveErr::Bounds)
+        );
+        assert_eq!(
+            TaggedCapability::new(root.capability(), false).try_derive(
+                Address(0),
+                Address(0),
+                Address(8),
+                Permissions::READ
+            ),
+            Err(DeriveErr::Invalid)
+        );
+        assert!(!TaggedCapability::new(root.capability(), false)
+            .derive(Address(0), Address(0), Address(8), Permissions::READ)
+            .is_valid());
+    }
+
+    #[test]
+    fn deref_sealed() -> anyhow::Result<()> {
+        let mut mem = Memory::new(256, 0, [].iter())?;
+        let sealer = mem.root.set_addr(Address(OType::VALID_ALIGN.get()));
+        let cap = mem.root.set_addr(Address(8)).seal(sealer);
+        assert!(cap.is_valid());
+        assert!(matches!(
+            mem.read::<u8>(cap),
+            Err(Exception::CapabilitySealed { otype }) if otype.get_addr() == sealer.addr()
+        ));
+        assert!(matches!(
+            mem.write(cap, 0u8),
+            Err(Exception::CapabilitySealed { .. })
+        ));
+        Ok(())
+    }
+
     #[test]
     fn set_perms() {
         // TODO: automate
@@ -201,9 +1622,9 @@ mod capability {
                 ),
                 TaggedCapability::new(
                     Capability::new(
-                        Address(256),
-                        Address(256),
-                        Address(320),
+                        Address(1024),
+                        Address(1024),
+                        Address(1088),
                         Permissions::SEAL,
                         OType::UNSEALED,
                     ),
@@ -215,9 +1636,9 @@ mod capability {
                 TaggedCapability::INVALID,
                 TaggedCapability::new(
                     Capability::new(
-                        Address(256),
-                        Address(256),
-                        Address(320),
+                        Address(1024),
+                        Address(1024),
+                        Address(1088),
                         Permissions::SEAL,
                         OType::UNSEALED,
                     ),
@@ -282,15 +1703,15 @@ mod capability {
                         Address(0),
                         Address(16),
                         Permissions::READ | Permissions::WRITE,
-                        OType::from_addr(Address(256)).unwrap(),
+                        OType::from_addr(Address(1024)).unwrap(),
                     ),
                     true,
                 ),
                 TaggedCapability::new(
                     Capability::new(
-                        Address(256),
-                        Address(256),
-                        Address(320),
+                        Address(1024),
+                        Address(1024),
+                        Address(1088),
                         Permissions::UNSEAL,
                         OType::UNSEALED,
                     ),
@@ -302,9 +1723,9 @@ mod capability {
                 TaggedCapability::INVALID,
                 TaggedCapability::new(
                     Capability::new(
-                        Address(256),
-                        Address(256),
-                        Address(320),
+                        Address(1024),
+                        Address(1024),
+                        Address(1088),
                         Permissions::UNSEAL,
                         OType::UNSEALED,
                     ),
@@ -333,7 +1754,7 @@ mod capability {
                         Address(0),
                         Address(16),
                         Permissions::READ | Permissions::WRITE,
-                        OType::from_addr(Address(256)).unwrap(),
+                        OType::from_addr(Address(1024)).unwrap(),
                     ),
                     true,
                 ),
@@ -367,7 +1788,7 @@ mod capability {
                 Address(0),
                 Address(16),
                 Permissions::READ | Permissions::WRITE,
-                OType::from_addr(Address(256)).unwrap(),
+                OType::from_addr(Address(1024)).unwrap(),
             ),
             true,
         );
@@ -384,19 +1805,22 @@ mod capability {
                 Address(0),
                 Address(16),
                 Permissions::READ | Permissions::WRITE,
-                OType::from_addr(Address(256)).unwrap(),
+                OType::from_addr(Address(1024)).unwrap(),
             ),
             true,
         );
         let access = |cap: TaggedCapability| {
             cap.check_access(MemAccessKind::Read, Align::new(1).unwrap(), Some(16))
         };
-        assert!(access(sealed).is_err());
+        assert!(matches!(
+            access(sealed),
+            Err(Exception::CapabilitySealed { otype }) if otype == sealed.otype()
+        ));
         let unsealed = sealed.unseal(TaggedCapability::new(
             Capability::new(
-                Address(256),
-                Address(256),
-                Address(512),
+                Address(1024),
+                Address(1024),
+                Address(1088),
                 Permissions::UNSEAL,
                 OType::UNSEALED,
             ),
@@ -0,0 +1,82 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::capability::{Address, TaggedCapability};
+use crate::mem::{CapLocation, Memory};
+
+/* graph layout:
+ *   one node per register holding a capability, and one per region which some
+ *   capability grants access to.
+ *   one edge per tagged capability, from where it is stored to the region it
+ *   grants access to. capabilities stored in memory are drawn from the
+ *   smallest region containing them, or from their granule if no region does.
+ */
+
+impl Memory {
+    /// Write the graph of tagged capabilities as Graphviz DOT.
+    pub fn write_dot<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let caps: Vec<_> = self.iter_tagged_caps().collect();
+        let regions: BTreeSet<(Address, Address)> = caps
+            .iter()
+            .map(|(_, tcap)| (tcap.start(), tcap.endb()))
+            .collect();
+
+        writeln!(w, "digraph capabilities {{")?;
+        writeln!(w, "    node [shape=box, fontname=monospace];")?;
+        for (start, endb) in &regions {
+            writeln!(
+                w,
+                "    {} [label=\"[{start}, {endb})\"];",
+                region_id(*start, *endb)
+            )?;
+        }
+        for (loc, tcap) in &caps {
+            let from = match loc {
+                CapLocation::Register(reg) => {
+                    writeln!(w, "    reg_{reg} [label=\"{reg}\", shape=ellipse];")?;
+                    format!("reg_{reg}")
+                }
+                CapLocation::Granule(gran) => {
+                    let addr = gran.addr().unwrap();
+                    match smallest_containing(&regions, addr) {
+                        Some((start, endb)) => region_id(start, endb),
+                        None => {
+                            writeln!(w, "    gran_{addr} [label=\"{addr}\", shape=point];")?;
+                            format!("gran_{addr}")
+                        }
+                    }
+                }
+            };
+            writeln!(
+                w,
+                "    {from} -> {} [label=\"{}\"];",
+                region_id(tcap.start(), tcap.endb()),
+                edge_label(*loc, *tcap)
+            )?;
+        }
+        writeln!(w, "}}")?;
+        w.flush()
+    }
+}
+
+fn region_id(start: Address, endb: Address) -> String {
+    format!("region_{start}_{endb}")
+}
+
+fn smallest_containing(
+    regions: &BTreeSet<(Address, Address)>,
+    addr: Address,
+) -> Option<(Address, Address)> {
+    regions
+        .iter()
+        .filter(|(start, endb)| *start <= addr && addr < *endb)
+        .min_by_key(|(start, endb)| endb.get() - start.get())
+        .copied()
+}
+
+fn edge_label(loc: CapLocation, tcap: TaggedCapability) -> String {
+    match loc {
+        CapLocation::Register(_) => format!("{}", tcap.perms()),
+        CapLocation::Granule(gran) => format!("{} @ {}", tcap.perms(), gran.addr().unwrap()),
+    }
+}
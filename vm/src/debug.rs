@@ -6,8 +6,13 @@ use core::str::FromStr;
 use core::sync::atomic::{AtomicBool, Ordering};
 use std::io::{self, BufRead, Write};
 
+use fruticose_asm::source::SourceMap;
+use fruticose_vm::abi::Ty;
+use fruticose_vm::capability::Address;
 use fruticose_vm::exception::Exception;
-use fruticose_vm::mem::Memory;
+use fruticose_vm::int::{self, UAddr};
+use fruticose_vm::mem::{CapLocation, Memory};
+use fruticose_vm::op::Op;
 use fruticose_vm::registers::Register;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -76,8 +81,15 @@ fn launch_inner<W: Write>(
                     writeln!(out, "quit. quit.")?;
                     writeln!(out, "help. list commands.")?;
                     writeln!(out, "log [on | off]. toggle logs.")?;
-                    writeln!(out, "step [<count> | while]. execute the next Op(s).")?;
-                    writeln!(out, "print <location>. print value at location.")?;
+                    writeln!(
+                        out,
+                        "step [<count> | while] [verbose]. execute the next Op(s)."
+                    )?;
+                    writeln!(
+                        out,
+                        "print <location> [short | hex | dec | sdec]. print value at location."
+                    )?;
+                    writeln!(out, "caps. list all valid capabilities.")?;
                     writeln!(out, "do <operation>. execute operation.")?;
                 }
 
@@ -111,19 +123,27 @@ fn launch_inner<W: Write>(
 
                 "step" | "s" => {
                     let mut remain = Some(1);
-                    if let Some(qual) = cmd.next() {
+                    let mut verbose = false;
+                    let mut invalid = false;
+                    for qual in cmd.by_ref() {
                         if qual == "while" {
                             remain = None;
+                        } else if qual == "verbose" || qual == "v" {
+                            verbose = true;
                         } else {
                             match qual.parse::<u128>() {
                                 Ok(n) => remain = Some(n),
                                 Err(err) => {
                                     writeln!(out, "error: invalid count: {err}")?;
-                                    continue;
+                                    invalid = true;
+                                    break;
                                 }
                             }
                         }
                     }
+                    if invalid {
+                        continue;
+                    }
 
                     let mut count: Option<u128> = Some(0); // none indicates overflow
                     let mut raised = None;
@@ -132,9 +152,16 @@ fn launch_inner<W: Write>(
                             writeln!(out, "ctrl-c pressed, aborting step")?;
                             break;
                         }
-                        if let Err(except) = mem.execute_next() {
-                            raised = Some(except);
-                            break;
+                        match mem.step() {
+                            Ok(op) => {
+                                if verbose {
+                                    writeln!(out, "{op}")?;
+                                }
+                            }
+                            Err(except) => {
+                                raised = Some(except);
+                                break;
+                            }
                         }
                         // TODO: if process exits it says "executed 0 ops" but did syscall
                         if let Some(n) = count {
@@ -163,8 +190,7 @@ fn launch_inner<W: Write>(
                 "print" | "p" => match cmd.next() {
                     Some(loc) => {
                         if let Some(reg) = Register::from_str(loc) {
-                            let val = mem.regs.read(&mem.tags, reg as _)?;
-                            writeln!(out, "{val:#?}")?;
+                            print_reg(&mut out, mem, reg, cmd.next())?;
                         } else {
                             writeln!(out, "error: unknown location '{loc}'")?;
                         }
@@ -173,6 +199,8 @@ fn launch_inner<W: Write>(
                     None => writeln!(out, "error: missing argument <location>")?,
                 },
 
+                "caps" | "c" => print_caps(&mut out, mem)?,
+
                 "do" | "d" => {
                     let src = if let Some(s) = cmd.remainder() {
                         s
@@ -182,7 +210,7 @@ fn launch_inner<W: Write>(
                     };
                     // HACK: assembler api doesn't let me expect the contents of a line, excluding the newline
                     let src = format!("{src}\n");
-                    if let Ok(ops) = super::assemble_src(&src, None) {
+                    if let Ok(ops) = super::assemble_src(&SourceMap::anonymous(&src)) {
                         debug_assert!(ops.len() <= 1);
                         for op in ops {
                             if let Err(raised) = mem.execute_op(op, None, false) {
@@ -217,6 +245,68 @@ fn pretty_println_exception<W: Write>(mut f: W, except: Exception) -> io::Result
     Ok(())
 }
 
+pub fn print_caps<W: Write>(mut f: W, mem: &Memory) -> io::Result<()> {
+    for (loc, tcap) in mem.iter_tagged_caps() {
+        match loc {
+            CapLocation::Register(reg) => write!(f, "{reg}")?,
+            CapLocation::Granule(gran) => match gran.addr() {
+                Some(addr) => write!(f, "{addr}")?,
+                None => write!(f, "granule {}", gran.0)?,
+            },
+        }
+        writeln!(f, ": {tcap:?}")?;
+    }
+    writeln!(
+        f,
+        "{} granules tagged ({:.1}%)",
+        mem.tags().count_valid(),
+        mem.tag_density() * 100.0
+    )?;
+    Ok(())
+}
+
+/// Print the operations within `radius` operations of `pc`, marking the one
+/// at `pc`. Addresses which can't be read through the program counter are
+/// skipped.
+pub fn print_ops_around<W: Write>(
+    mut f: W,
+    mem: &Memory,
+    pc: Address,
+    radius: u16,
+) -> anyhow::Result<()> {
+    let pc_cap = mem.register(Register::Pc)?;
+    let stride = i32::from(Op::LAYOUT.size);
+    for delta in -i32::from(radius)..=i32::from(radius) {
+        let Ok(addr) = UAddr::try_from(i32::from(pc.get()) + delta * stride) else {
+            continue;
+        };
+        let addr = Address(addr);
+        if let Ok(op) = mem.read::<Op>(pc_cap.set_addr(addr)) {
+            let marker = if addr == pc { "->" } else { "  " };
+            writeln!(f, "{marker} {addr}: {op}")?;
+        }
+    }
+    Ok(())
+}
+
+pub fn print_reg<W: Write>(
+    mut f: W,
+    mem: &Memory,
+    reg: Register,
+    format: Option<&str>,
+) -> anyhow::Result<()> {
+    let val = mem.register(reg)?;
+    match format {
+        None => writeln!(f, "{val:#?}")?,
+        Some("short" | "s") => writeln!(f, "{val}")?,
+        Some("hex" | "x") => writeln!(f, "0x{:x}", val.to_ugran())?,
+        Some("dec") => writeln!(f, "{}", val.to_ugran())?,
+        Some("sdec") => writeln!(f, "{}", int::gran_sign(val.to_ugran()))?,
+        Some(unk) => writeln!(f, "error: unknown format '{unk}'")?,
+    }
+    Ok(())
+}
+
 fn readln<W: Write>(mut out: W, prompt: &str) -> io::Result<String> {
     write!(out, "{prompt}")?;
     out.flush()?;
@@ -1,6 +1,7 @@
 #![feature(str_split_whitespace_remainder)]
 
 mod debug;
+mod repl;
 
 #[cfg(test)]
 mod tests;
@@ -8,26 +9,32 @@ mod tests;
 use anyhow::Context;
 use argh::FromArgs;
 use nu_ansi_term::{Color, Style};
-use tracing::{span, Level};
+use tracing::{span, Level, Subscriber};
 use tracing_subscriber::filter::LevelFilter;
-use tracing_subscriber::{fmt, prelude::*, reload};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{fmt, prelude::*, reload, Layer};
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 use core::num::IntErrorKind;
-use std::io::{stderr, stdout, BufWriter, Write};
+use core::str::FromStr;
+use std::fs::File;
+use std::io;
+use std::io::{stderr, stdin, stdout, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use std::{fs, io};
 
 use fruticose_asm::lex::{LexErrTyp, TokenTyp};
 use fruticose_asm::parse1::{ParseErr, ParseErrTyp, TokenClass};
-use fruticose_asm::parse2::Parser2;
+use fruticose_asm::source::{Location, SourceMap};
 use fruticose_asm::Span;
+use fruticose_vm::capability::{Address, OType, Permissions};
 use fruticose_vm::exception::Exception;
-use fruticose_vm::int::UAddr;
+use fruticose_vm::int::{UAddr, UADDR_SIZE, UGRAN_SIZE};
 use fruticose_vm::mem::Memory;
-use fruticose_vm::op::Op;
+use fruticose_vm::op::{Op, OpKind};
+use fruticose_vm::registers::Registers;
 
 use crate::debug::DebugMode;
 
@@ -46,33 +53,109 @@ struct Args {
     #[argh(option, short = 'd', default = "DebugMode::Never")]
     debug: DebugMode,
 
-    /// path to init program assembly
+    /// path to init program assembly, required unless using the repl
     #[argh(option, short = 'i')]
-    init: PathBuf,
+    init: Option<PathBuf>,
+
+    /// read, assemble, and execute operations from stdin one line at a time
+    #[argh(switch, short = 'r')]
+    repl: bool,
+
+    /// print how many times each operation was executed once execution halts
+    #[argh(switch, short = 'p')]
+    profile: bool,
+
+    /// abort if the init program executes more than this many operations
+    #[argh(option)]
+    max_ops: Option<u64>,
+
+    /// write a core image to this path if the init program raises an exception
+    #[argh(option)]
+    core: Option<PathBuf>,
+
+    /// format of log output on stderr, either "pretty" or "json"
+    #[argh(option, default = "LogFormat::Pretty")]
+    log_format: LogFormat,
+
+    /// print the parameters of the target machine and exit
+    #[argh(switch)]
+    info: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+    Pretty,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("pretty") {
+            Ok(Self::Pretty)
+        } else if s.eq_ignore_ascii_case("json") {
+            Ok(Self::Json)
+        } else {
+            Err("unrecognized log format")
+        }
+    }
+}
+
+/// Build the layer which formats log output.
+fn log_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::Layer::new()
+        .with_writer(writer)
+        .with_timer(tracing_subscriber::fmt::time::uptime());
+    match format {
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
 }
 
 fn main() -> ExitCode {
+    let args: Args = argh::from_env();
+
     let (filter, reload_handle) = reload::Layer::new(LevelFilter::TRACE);
     tracing_subscriber::registry()
         .with(filter)
-        .with(
-            fmt::Layer::new()
-                .with_writer(stderr)
-                .with_timer(tracing_subscriber::fmt::time::uptime())
-                .pretty(),
-        )
+        .with(log_layer(args.log_format, stderr))
         .init();
 
-    let args: Args = argh::from_env();
-
     if let Err(err) = try_main(args, reload_handle) {
+        let code = exit_code(&err);
         _ = pretty_print_main_err(BufWriter::new(stderr()), err);
-        ExitCode::FAILURE
+        ExitCode::from(code)
     } else {
         ExitCode::SUCCESS
     }
 }
 
+/// Describe the parameters of the target machine, which programs are
+/// assembled for.
+fn print_info<W: Write>(mut w: W) -> io::Result<()> {
+    writeln!(w, "granule size:     {UGRAN_SIZE} bytes")?;
+    writeln!(w, "address size:     {UADDR_SIZE} bytes")?;
+    writeln!(w, "address bits:     {}", Address::BITS)?;
+    writeln!(w, "otype bits:       {}", OType::BITS)?;
+    writeln!(w, "permission bits:  {}", Permissions::BITS)?;
+    writeln!(w, "registers:        {}", Registers::COUNT)?;
+    Ok(())
+}
+
+/// Exit status for a fatal error: the exception's code if it was raised by the
+/// VM, else 1.
+fn exit_code(err: &anyhow::Error) -> u8 {
+    match err.downcast_ref::<Exception>() {
+        Some(except) => except.to_code(),
+        None => 1,
+    }
+}
+
 fn try_main(
     args: Args,
     log_handle: reload::Handle<LevelFilter, tracing_subscriber::Registry>,
@@ -83,17 +166,34 @@ fn try_main(
         granules = args.granules,
         stack_size = args.stack_size,
         debug_mode = format_args!("{:?}", args.debug),
+        max_ops = args.max_ops,
     );
     let _guard = span.enter();
 
     let mut stdout = BufWriter::new(stdout());
 
+    if args.info {
+        print_info(&mut stdout)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
     let mut mem = {
-        let init: Vec<Op> = assemble_init(&args.init).context("failed to load init program")?;
+        let init: Vec<Op> = match &args.init {
+            Some(init) => assemble_init(init).context("failed to load init program")?,
+            None if args.repl => Vec::new(),
+            None => anyhow::bail!("missing init program, see --help"),
+        };
         Memory::new(args.granules, args.stack_size, init.iter())
             .context("failed to instantiate memory")?
     };
 
+    if args.repl {
+        tracing::info!("launching repl");
+        mem.set_op_budget(args.max_ops);
+        return repl::run(&mut mem, stdin().lock(), &mut stdout);
+    }
+
     if args.debug == DebugMode::Always {
         tracing::info!("launching debugger before execution start");
         args.debug.launch(&mut mem, None, log_handle, &mut stdout)?;
@@ -102,10 +202,11 @@ fn try_main(
     }
 
     tracing::info!("execution start");
-    loop {
+    mem.set_op_budget(args.max_ops);
+    let res = loop {
         match mem.execute_next() {
             Ok(()) => (),
-            Err(Exception::ProcessExit) => break,
+            Err(Exception::ProcessExit) => break Ok(()),
             Err(raised) => {
                 if args.debug == DebugMode::Error {
                     tracing::info!("launching debugger, exception raised");
@@ -113,12 +214,46 @@ fn try_main(
                         .launch(&mut mem, Some(raised), log_handle, &mut stdout)?;
                     tracing::info!("debugger yielded, resuming exception handling");
                 }
-                return Err(raised.into());
+                if let Exception::InvalidMemAccess { pc: Some(pc), .. } = raised {
+                    let mut f = stderr().lock();
+                    writeln!(f, "faulting operation:")?;
+                    debug::print_ops_around(&mut f, &mem, pc, 2)?;
+                }
+                if let Some(path) = &args.core {
+                    tracing::info!(path = format_args!("{}", path.display()), "dumping core");
+                    let file = File::create(path).context("failed to create core image")?;
+                    mem.dump_core(BufWriter::new(file))
+                        .context("failed to write core image")?;
+                }
+                break Err(raised.into());
             }
         }
-    }
+    };
     tracing::info!("execution halted");
 
+    if args.profile {
+        print_op_histogram(&mut stdout, &mem)?;
+        stdout.flush()?;
+    }
+
+    res
+}
+
+fn print_op_histogram<W: Write>(mut f: W, mem: &Memory) -> io::Result<()> {
+    let mut counts: Vec<(OpKind, u64)> = mem
+        .op_histogram()
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count != 0)
+        .map(|(idx, count)| {
+            let kind = OpKind::from_byte(idx as u8).expect("histogram index is valid op kind");
+            (kind, *count)
+        })
+        .collect();
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+    for (kind, count) in counts {
+        writeln!(f, "{count:>12} {kind}")?;
+    }
     Ok(())
 }
 
@@ -133,38 +268,34 @@ fn assemble_init(init: &Path) -> anyhow::Result<Vec<Op>> {
     tracing::debug!("loading init program");
 
     tracing::trace!("reading init program");
-    let init_src = fs::read_to_string(init).context("failed to read init program source")?;
+    let mut init_src = SourceMap::load(init).context("failed to read init program source")?;
+    init_src
+        .expand_macros()
+        .context("failed to expand init program macros")?;
 
     tracing::trace!("assembling init program");
-    assemble_src(&init_src, Some(init))
+    assemble_src(&init_src)
 }
 
-fn assemble_src(src: &str, path: Option<&Path>) -> anyhow::Result<Vec<Op>> {
+fn assemble_src(src: &SourceMap) -> anyhow::Result<Vec<Op>> {
     let span = span!(Level::TRACE, "assemble_src");
     let _guard = span.enter();
 
-    let parser = Parser2::new(src);
-    let mut ops = Vec::new();
-    let mut err_count: usize = 0;
+    let errs = match fruticose_asm::assemble(src.text()) {
+        Ok(ops) => return Ok(ops),
+        Err(errs) => errs,
+    };
+    let err_count = errs.len();
     let mut err_out = BufWriter::new(stderr());
-    for try_op in parser {
-        match try_op {
-            Ok(op) => ops.push(op),
-            Err(err) => {
-                err_count += 1;
-                pretty_print_parse_err(&mut err_out, path, err)?;
-                writeln!(err_out)?;
-            }
-        }
+    for err in errs {
+        pretty_print_parse_err(&mut err_out, src, err)?;
+        writeln!(err_out)?;
     }
     err_out.flush()?;
-    if err_count > 0 {
-        anyhow::bail!(
-            "failed to assemble source due to {err_count} previous error{}",
-            if err_count == 1 { "" } else { "s" }
-        );
-    }
-    Ok(ops)
+    anyhow::bail!(
+        "failed to assemble source due to {err_count} previous error{}",
+        if err_count == 1 { "" } else { "s" }
+    );
 }
 
 fn pretty_print_main_err<W: Write>(mut f: W, err: anyhow::Error) -> anyhow::Result<()> {
@@ -205,7 +336,7 @@ fn pretty_print_main_err<W: Write>(mut f: W, err: anyhow::Error) -> anyhow::Resu
 
 fn pretty_print_parse_err<W: Write>(
     mut f: W,
-    src_path: Option<&Path>,
+    src: &SourceMap,
     err: ParseErr<'_>,
 ) -> anyhow::Result<()> {
     let err_title = Color::LightRed.bold();
@@ -233,9 +364,14 @@ fn pretty_print_parse_err<W: Write>(
                 }
                 _ => write!(f, "unsigned integer literal is invalid ({err})")?,
             },
+            LexErrTyp::InvalidPermissions => {
+                write!(f, "permission literal is invalid, expected eg. %rwxsuRWLg")?;
+            }
+            LexErrTyp::TypedIntOverflow { ty } => {
+                write!(f, "integer literal overflows {ty}")?;
+            }
         },
         ParseErrTyp::ExpectedTyp { expected, found } => {
-            // TODO: show operand count if missing comma
             write!(f, "expected {expected}, but found {found}")?;
         }
         ParseErrTyp::ExpectedClass { expected, found } => {
@@ -263,30 +399,31 @@ fn pretty_print_parse_err<W: Write>(
         )?,
         ParseErrTyp::LabelRedef { first_def: _ } => write!(f, "labels cannot be redefined")?, // TODO: show where first defined
         ParseErrTyp::LabelUndef => write!(f, "undefined label")?,
-        ParseErrTyp::LabelOffsetOverflow => {
+        ParseErrTyp::MissingComma { after_operand } => {
+            write!(f, "expected comma after operand {after_operand}")?;
+        }
+        ParseErrTyp::TooManyOperands { op, extra } => write!(
+            f,
+            "{op} takes {} operands, but found extra {extra}",
+            op.operand_count()
+        )?,
+        ParseErrTyp::LabelOffsetOverflow { label: _ } => {
             write!(f, "overflow occured while computing label offset")?;
         }
     }
     writeln!(f)?;
 
-    let diag = Diagnostic::new(
-        span,
-        src_path.unwrap_or(Path::new("<anonymous>")),
-        text,
-        symbols,
-        err_span,
-        err_body,
-        err_underline,
-    );
+    let loc = src.locate(span).expect("span points into source map");
+    let diag = Diagnostic::new(span, loc, text, symbols, err_span, err_body, err_underline);
     diag.write(&mut f)?;
 
     f.flush()?;
     Ok(())
 }
 
-struct Diagnostic<'s, 'p> {
+struct Diagnostic<'s, 'm> {
     span: Span<'s>,
-    src_path: &'p Path,
+    loc: Location<'m>,
     text: Style,
     symbols: Style,
     err_span: Style,
@@ -294,10 +431,10 @@ struct Diagnostic<'s, 'p> {
     err_underline: Style,
 }
 
-impl<'s, 'p> Diagnostic<'s, 'p> {
+impl<'s, 'm> Diagnostic<'s, 'm> {
     pub fn new(
         span: Span<'s>,
-        src_path: &'p Path,
+        loc: Location<'m>,
         text: Style,
         symbols: Style,
         err_span: Style,
@@ -306,7 +443,7 @@ impl<'s, 'p> Diagnostic<'s, 'p> {
     ) -> Self {
         Self {
             span,
-            src_path,
+            loc,
             text,
             symbols,
             err_span,
@@ -319,33 +456,22 @@ impl<'s, 'p> Diagnostic<'s, 'p> {
         let span = self.span;
         let text = self.text;
         let symbols = self.symbols;
-        let err_span = self.err_span;
         let err_body = self.err_body;
-        let err_underline = self.err_underline;
-
-        let pre_span = &span.get_line()[..span.col_idx];
-        let mut in_span = span.get();
-        let mut post_span = &span.get_line()[span.col_idx..][span.len..];
-        if post_span.is_empty() {
-            in_span = in_span.trim_end();
-        } else {
-            post_span = post_span.trim_end();
-        }
-        let pre_span_len = UnicodeWidthStr::width(pre_span);
-        let in_span_len = UnicodeWidthStr::width(in_span).max(1);
 
-        let line = span.line + 1;
+        let lines = span.lines();
+        let line = self.loc.line + 1;
         let col = {
             let graphs = || UnicodeSegmentation::grapheme_indices(span.get_line(), true);
             if let Some(col) = graphs().position(|(idx, _)| idx == span.col_idx) {
                 col + 1
             } else {
                 // eof isnt a real character! but its still loved
-                graphs().map(|(idx, _)| idx).last().unwrap_or(0)
+                graphs().map(|(idx, _)| idx).next_back().unwrap_or(0)
             }
         };
 
-        let line_fmt_width = line.ilog10() as usize + 1;
+        let last_line = line + lines.len() - 1;
+        let line_fmt_width = last_line.ilog10() as usize + 1;
         let side_pad = 1;
         let line_pad = side_pad + line_fmt_width + side_pad;
         // TODO: allocating strings here is silly
@@ -354,10 +480,10 @@ impl<'s, 'p> Diagnostic<'s, 'p> {
 
         writeln!(
             f,
-            "{side_padding}{}@{} {src_path}:{line}:{col}",
+            "{side_padding}{}@{} {name}:{line}:{col}",
             err_body.infix(symbols),
             symbols.infix(text),
-            src_path = self.src_path.display(),
+            name = self.loc.name,
         )?;
 
         writeln!(
@@ -366,16 +492,51 @@ impl<'s, 'p> Diagnostic<'s, 'p> {
             text.infix(symbols),
             symbols.infix(text)
         )?;
+        for (idx, part) in lines.into_iter().enumerate() {
+            self.write_line(&mut f, part, line + idx, line_fmt_width)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write one line of source text, underlining the part of it in `span`.
+    fn write_line<W: Write>(
+        &self,
+        mut f: W,
+        span: Span<'s>,
+        line: usize,
+        line_fmt_width: usize,
+    ) -> io::Result<()> {
+        let text = self.text;
+        let symbols = self.symbols;
+        let err_span = self.err_span;
+        let err_underline = self.err_underline;
+
+        let pre_span = &span.get_line()[..span.col_idx];
+        let mut in_span = span.get();
+        let mut post_span = &span.get_line()[span.col_idx..][span.len..];
+        if post_span.is_empty() {
+            in_span = in_span.trim_end();
+        } else {
+            post_span = post_span.trim_end();
+        }
+        let pre_span_len = UnicodeWidthStr::width(pre_span);
+        let in_span_len = UnicodeWidthStr::width(in_span).max(1);
+
+        let side_pad = 1;
+        let line_padding = " ".repeat(side_pad + line_fmt_width + side_pad);
+        let side_padding = " ".repeat(side_pad);
+
         writeln!(
-        f,
-        "{side_padding}{}{line}{}{side_padding}{}|{}{side_padding}{pre_span}{}{in_span}{}{post_span}",
-        text.infix(symbols),
-        symbols.infix(text),
-        text.infix(symbols),
-        symbols.infix(text),
-        text.infix(err_span),
-        err_span.infix(text),
-    )?;
+            f,
+            "{side_padding}{}{line:>line_fmt_width$}{}{side_padding}{}|{}{side_padding}{pre_span}{}{in_span}{}{post_span}",
+            text.infix(symbols),
+            symbols.infix(text),
+            text.infix(symbols),
+            symbols.infix(text),
+            text.infix(err_span),
+            err_span.infix(text),
+        )?;
         writeln!(
             f,
             "{line_padding}{}|{}{side_padding}{skip_pre}{}{fake_underline}{}",
@@ -385,8 +546,6 @@ impl<'s, 'p> Diagnostic<'s, 'p> {
             err_underline.suffix(),
             skip_pre = " ".repeat(pre_span_len),
             fake_underline = "^".repeat(in_span_len),
-        )?;
-
-        Ok(())
+        )
     }
 }
@@ -0,0 +1,43 @@
+use std::io::{BufRead, Write};
+
+use fruticose_asm::source::SourceMap;
+use fruticose_vm::capability::TaggedCapability;
+use fruticose_vm::exception::Exception;
+use fruticose_vm::mem::Memory;
+use fruticose_vm::registers::{Register, Registers};
+
+/// Assemble and execute each line of `input`, printing which registers
+/// changed. Stops at end of input or when the process exits.
+pub fn run<R: BufRead, W: Write>(mem: &mut Memory, input: R, mut out: W) -> anyhow::Result<()> {
+    for line in input.lines() {
+        let src = format!("{}\n", line?);
+        let Ok(ops) = super::assemble_src(&SourceMap::anonymous(&src)) else {
+            continue;
+        };
+        for op in ops {
+            let before = snapshot(mem);
+            match mem.execute_op(op, None, false) {
+                Ok(()) => (),
+                Err(Exception::ProcessExit) => {
+                    writeln!(out, ":: process exited")?;
+                    return Ok(());
+                }
+                Err(raised) => writeln!(out, ":: {raised}")?,
+            }
+            for ((reg, old), (_, new)) in before.into_iter().zip(snapshot(mem)) {
+                if old != new {
+                    writeln!(out, "{reg}: {old} -> {new}")?;
+                }
+            }
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn snapshot(mem: &Memory) -> Vec<(Register, TaggedCapability)> {
+    (0..Registers::COUNT)
+        .filter_map(Register::from_byte)
+        .filter_map(|reg| Some((reg, mem.regs.read(&mem.tags, reg as _).ok()?)))
+        .collect()
+}
@@ -22,25 +22,106 @@ mod rt {
     }
 }
 
-mod exec {
-    use fruticose_asm::parse1::ParseErr;
+mod debug {
     use fruticose_asm::parse2::Parser2;
+    use fruticose_vm::abi::Ty;
+    use fruticose_vm::alloc;
     use fruticose_vm::capability::TaggedCapability;
     use fruticose_vm::exception::Exception;
+    use fruticose_vm::int::{self, UGran};
     use fruticose_vm::mem::Memory;
     use fruticose_vm::op::Op;
     use fruticose_vm::registers::Register;
 
+    use crate::debug;
+
+    #[test]
+    fn caps() -> Result<(), Exception> {
+        let mut mem = Memory::new(32, 0, [].iter()).unwrap();
+        let root_alloc = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+        let ation = alloc::alloc(root_alloc, TaggedCapability::LAYOUT, &mut mem)?;
+        mem.regs.write(&mut mem.tags, Register::T0 as _, ation)?;
+        let mut out = Vec::new();
+        debug::print_caps(&mut out, &mem).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(&format!("z0: {root_alloc:?}\n")));
+        assert!(out.contains(&format!("t0: {ation:?}\n")));
+        Ok(())
+    }
+
+    #[test]
+    fn print_format() -> Result<(), Exception> {
+        let mut mem = Memory::new(32, 0, [].iter()).unwrap();
+        mem.regs
+            .write_data(&mut mem.tags, Register::T0 as _, int::gran_unsign(-12))?;
+        let print = |format: &str| {
+            let mut out = Vec::new();
+            debug::print_reg(&mut out, &mem, Register::T0, Some(format)).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(print("sdec"), "-12\n");
+        assert_eq!(print("dec"), format!("{}\n", UGran::MAX - 11));
+        assert_eq!(print("hex"), "0xfffffffffffffff4\n");
+        assert_eq!(print("bin"), "error: unknown format 'bin'\n");
+        Ok(())
+    }
+
+    #[test]
+    fn ops_around() -> Result<(), Exception> {
+        let ops = Parser2::new("loadi t0, 1\nloadi t1, 2\nadd t2, t0, t1\n")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mem = Memory::new(64, 0, ops.iter()).unwrap();
+        let start = mem.regs.read(&mem.tags, Register::Pc as _)?.addr();
+        let pc = start.add(Op::LAYOUT.size);
+        let mut out = Vec::new();
+        debug::print_ops_around(&mut out, &mem, pc, 1).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            format!(
+                "   {start}: {}\n-> {pc}: {}\n   {}: {}\n",
+                ops[0],
+                ops[1],
+                pc.add(Op::LAYOUT.size),
+                ops[2]
+            )
+        );
+        Ok(())
+    }
+}
+
+mod exec {
+    use fruticose_asm::parse1::ParseErr;
+    use fruticose_asm::parse2::Parser2;
+    use fruticose_vm::abi::{Layout, Ty};
+    use fruticose_vm::alloc::{self, AllocErrKind, InitFlags, Stats, Strategy};
+    use fruticose_vm::capability::{Address, OType, Permissions, TaggedCapability};
+    use fruticose_vm::exception::Exception;
+    use fruticose_vm::int::{UAddr, UGran};
+    use fruticose_vm::mem::Memory;
+    use fruticose_vm::op::{Op, OpKind};
+    use fruticose_vm::process::{Dispatch, RunOutcome};
+    use fruticose_vm::registers::Register;
+    use fruticose_vm::syscall::CapInfo;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    const EXIT: &str = include_str!("../../libasm/examples/exit.asm");
     const ADD: &str = include_str!("../../libasm/examples/add.asm");
     const CMP: &str = include_str!("../../libasm/examples/cmp.asm");
     const JMP_BACK: &str = include_str!("../../libasm/examples/jmp-back.asm");
     const FIB_REC: &str = include_str!("../../libasm/examples/fibonacci-recursive.asm");
     const FIB_ITER: &str = include_str!("../../libasm/examples/fibonacci-iter.asm");
+    const STORE_DATA_OVER_CAP: &str = include_str!("../../libasm/examples/store-data-over-cap.asm");
+    const SHIFT_IMM: &str = include_str!("../../libasm/examples/shift-imm.asm");
+    const SHIFT_REG: &str = include_str!("../../libasm/examples/shift-reg.asm");
+    const OVERFLOW: &str = include_str!("../../libasm/examples/overflow.asm");
 
-    fn assemble(src: &str) -> Result<Vec<Op>, ParseErr> {
-        let ops = Parser2::new(src)
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()?;
+    fn assemble(src: &str) -> Result<Vec<Op>, ParseErr<'_>> {
+        let ops = Parser2::new(src).collect::<Result<Vec<_>, _>>()?;
         Ok(ops)
     }
 
@@ -56,12 +137,556 @@ mod exec {
         }
     }
 
+    /// Initialize an allocator over `size` bytes from the root allocator.
+    fn allocator(
+        mem: &mut Memory,
+        strat: Strategy,
+        flags: InitFlags,
+        size: UAddr,
+    ) -> Result<TaggedCapability, Exception> {
+        let root_alloc = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+        let region = Layout {
+            size,
+            align: OType::VALID_ALIGN,
+        };
+        let region = alloc::alloc(root_alloc, region, mem)?;
+        alloc::init(strat, flags, region, mem)
+    }
+
     #[track_caller]
     fn expect_in_reg(mem: &mut Memory, reg: Register, tcap: TaggedCapability) {
-        let val = mem.regs.read(&mut mem.tags, reg as _).unwrap();
+        let val = mem.regs.read(&mem.tags, reg as _).unwrap();
         assert_eq!(val, tcap);
     }
 
+    #[test]
+    fn step() {
+        let ops = assemble(EXIT).unwrap();
+        let mut mem = Memory::new(32, 0, ops.iter()).unwrap();
+        assert_eq!(mem.step().unwrap(), ops[0]);
+        assert!(matches!(mem.step(), Err(Exception::ProcessExit)));
+    }
+
+    #[test]
+    fn run() -> Result<(), Exception> {
+        let ops = assemble(JMP_BACK).unwrap();
+        let mut mem = Memory::new(32, 0, ops.iter()).unwrap();
+        assert_eq!(
+            mem.run(3)?,
+            RunOutcome {
+                ops: 3,
+                exited: false
+            }
+        );
+        expect_in_reg(&mut mem, Register::T0, TaggedCapability::from_ugran(53));
+        assert_eq!(
+            mem.run(10)?,
+            RunOutcome {
+                ops: 2,
+                exited: true
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn icache_invalidated_on_write() -> Result<(), Exception> {
+        let ops = assemble(
+            "loadi t0, 53
+            store64 s0, t0
+            loadi t1, 1
+            loadi a2, SYS_EXIT
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(32, 0, ops.iter()).unwrap();
+        // the immediate operand of the third operation, which follows the
+        // operation kind and first operand
+        let pc = mem.regs.read(&mem.tags, Register::Pc as _)?;
+        let op = pc.addr().add(2 * Op::LAYOUT.size);
+        let imm = mem
+            .root
+            .set_addr(op.add(2 * TaggedCapability::LAYOUT.size))
+            .set_bounds(op, op.add(Op::LAYOUT.size))
+            .set_perms(Permissions::READ | Permissions::WRITE | Permissions::EXEC);
+        mem.regs.write(&mut mem.tags, Register::S0 as _, imm)?;
+        exec(&mut mem)?;
+
+        let patched = mem.read::<Op>(imm.set_addr(op))?;
+        assert_eq!(patched.kind, OpKind::LoadI);
+        assert_eq!(patched.op2, TaggedCapability::from_ugran(53));
+        expect_in_reg(&mut mem, Register::T1, TaggedCapability::from_ugran(53));
+        Ok(())
+    }
+
+    #[test]
+    fn cap_info() -> Result<(), Exception> {
+        let ops = assemble("loadi a2, SYS_CAP_INFO\nsyscall\n").unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        let tcap = mem
+            .root
+            .set_addr(Address(12))
+            .set_bounds(Address(8), Address(24))
+            .set_perms(Permissions::READ | Permissions::GLOBAL);
+        let buf = mem.root.set_addr(mem.root.endb().sub(CapInfo::LAYOUT.size));
+        mem.regs.write(&mut mem.tags, Register::A3 as _, tcap)?;
+        mem.regs.write(&mut mem.tags, Register::A4 as _, buf)?;
+        mem.step()?;
+        mem.step()?;
+        assert_eq!(
+            mem.read::<CapInfo>(buf)?,
+            CapInfo {
+                valid: true,
+                addr: Address(12),
+                start: Address(8),
+                endb: Address(24),
+                perms: Permissions::READ | Permissions::GLOBAL,
+                otype: OType::UNSEALED,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn layout_array() -> Result<(), Exception> {
+        let ops = assemble("loadi a2, SYS_LAYOUT_ARRAY\nsyscall\n").unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        let pc = mem.regs.read(&mem.tags, Register::Pc as _)?;
+        let mut array = |elem: Layout, count: UAddr| {
+            mem.regs.write(&mut mem.tags, Register::Pc as _, pc)?; // reset execution
+            mem.regs.write_ty(&mut mem.tags, Register::A3 as _, elem)?;
+            mem.regs.write_ty(&mut mem.tags, Register::A4 as _, count)?;
+            mem.run(2)?;
+            mem.regs.read_ty::<Layout>(&mem.tags, Register::A0 as _)
+        };
+        assert_eq!(
+            array(u8::LAYOUT, 10)?,
+            Layout {
+                size: 10,
+                align: u8::LAYOUT.align
+            }
+        );
+        assert_eq!(
+            array(TaggedCapability::LAYOUT, 3)?,
+            Layout {
+                size: 24,
+                align: TaggedCapability::LAYOUT.align
+            }
+        );
+        assert!(matches!(
+            array(TaggedCapability::LAYOUT, UAddr::MAX),
+            Err(Exception::LayoutOverflow {
+                count: UAddr::MAX,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn alloc_stat() -> Result<(), Exception> {
+        let ops = assemble("loadi a2, SYS_ALLOC_STAT\ncpy a3, z0\nsyscall\n").unwrap();
+        let mut mem = Memory::new(256, 0, ops.iter()).unwrap();
+        mem.step()?;
+        mem.step()?;
+        mem.step()?;
+
+        let root_alloc = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+        let expected = alloc::stat(root_alloc, &mem)?;
+        assert!(expected.bytes_free > 0);
+        let stats: Stats = mem.regs.read_ty(&mem.tags, Register::A0 as _)?;
+        assert_eq!(stats, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn alloc_stat_into() -> Result<(), Exception> {
+        let ops = assemble(
+            "loadi a2, SYS_ALLOC_STAT_INTO
+            cpy a3, z0
+            cpy a4, s0
+            syscall
+            loadi a2, SYS_EXIT
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(256, 0, ops.iter()).unwrap();
+        let start = mem.root.endb().sub(16);
+        let buf = mem.root.set_addr(start).set_bounds(start, start.add(16));
+        mem.regs.write(&mut mem.tags, Register::S0 as _, buf)?;
+        exec(&mut mem)?;
+
+        let root_alloc = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+        let expected = alloc::stat(root_alloc, &mem)?;
+        assert_eq!(mem.read::<Stats>(buf)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn mem_set_cpy() -> Result<(), Exception> {
+        let ops = assemble(
+            "loadi a2, SYS_MEM_SET
+            cpy a3, s0
+            loadi a4, 16
+            loadi a5, 171
+            syscall
+            loadi a2, SYS_MEM_CPY
+            cpy a3, s1
+            cpy a4, s0
+            loadi a5, 12
+            syscall
+            loadu8 t0, s1
+            cgetaddr t3, s1
+            addi t3, t3, 11
+            csetaddr s1, t3
+            loadu8 t1, s1
+            addi t3, t3, 1
+            csetaddr s1, t3
+            loadu8 t2, s1
+            loadi a2, SYS_EXIT
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(256, 0, ops.iter()).unwrap();
+        let buf = |offset: u16| {
+            let start = mem.root.endb().sub(offset);
+            mem.root.set_addr(start).set_bounds(start, start.add(16))
+        };
+        let (src, dst) = (buf(64), buf(32));
+        mem.regs.write(&mut mem.tags, Register::S0 as _, src)?;
+        mem.regs.write(&mut mem.tags, Register::S1 as _, dst)?;
+        mem.memset(dst, 16, 0)?;
+        exec(&mut mem)?;
+
+        let byte = |reg: Register| mem.regs.read_ty::<UGran>(&mem.tags, reg as _);
+        assert_eq!(byte(Register::T0)?, 171);
+        assert_eq!(byte(Register::T1)?, 171);
+        // only 12 bytes were copied
+        assert_eq!(byte(Register::T2)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn offset_load_store() -> Result<(), Exception> {
+        let ops = assemble(
+            "loadi t0, 1234
+            store64o s0, t0, 8
+            loadu64o t1, s0, 8
+            cpy s1, s0
+            cgetaddr t3, s1
+            addi t3, t3, 8
+            csetaddr s1, t3
+            loadu64 t2, s1
+            addi t3, t3, 8
+            csetaddr s1, t3
+            loadu64o t4, s1, 65528
+            loadi a2, SYS_EXIT
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(256, 0, ops.iter()).unwrap();
+        let start = mem.root.endb().sub(32);
+        let buf = mem.root.set_addr(start).set_bounds(start, start.add(24));
+        mem.regs.write(&mut mem.tags, Register::S0 as _, buf)?;
+        exec(&mut mem)?;
+
+        // same as moving the address first, and offsets may be negative
+        expect_in_reg(&mut mem, Register::T1, TaggedCapability::from_ugran(1234));
+        expect_in_reg(&mut mem, Register::T2, TaggedCapability::from_ugran(1234));
+        expect_in_reg(&mut mem, Register::T4, TaggedCapability::from_ugran(1234));
+        // base register is left alone
+        expect_in_reg(&mut mem, Register::S0, buf);
+
+        // offset accesses are still bounds checked
+        let ops = assemble("loadu64o t0, s0, 24\n").unwrap();
+        let mut mem = Memory::new(256, 0, ops.iter()).unwrap();
+        mem.regs.write(&mut mem.tags, Register::S0 as _, buf)?;
+        assert!(mem.execute_next().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn data_segment() -> anyhow::Result<()> {
+        let ops = assemble("loadu8o t0, z1, 1\nloadi a2, SYS_EXIT\nsyscall\n").unwrap();
+        let data = [7, 42, 3];
+        let mut mem = Memory::new_with_data(64, 0, ops.iter(), data.iter())?;
+        let seg = mem.regs.read(&mem.tags, Register::Z1 as _)?;
+        assert!(seg.is_valid());
+        assert!(!seg.perms().contains(Permissions::EXEC));
+        assert!(seg.endb().get() - seg.start().get() >= data.len() as UAddr);
+        exec(&mut mem)?;
+        expect_in_reg(&mut mem, Register::T0, TaggedCapability::from_ugran(42));
+
+        // no data, no segment
+        let mem = Memory::new(64, 0, ops.iter())?;
+        assert!(!mem.regs.read(&mem.tags, Register::Z1 as _)?.is_valid());
+        Ok(())
+    }
+
+    #[test]
+    fn z1_register() -> anyhow::Result<()> {
+        use fruticose_vm::registers::Registers;
+
+        assert_eq!(Registers::COUNT, Register::Z1 as u8 + 1);
+        assert!(Register::from_byte(Registers::COUNT).is_none());
+
+        // survives a program which doesn't touch it
+        let ops = assemble(ADD).unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter())?;
+        let val = mem.root.set_addr(Address(8));
+        mem.regs.write(&mut mem.tags, Register::Z1 as _, val)?;
+        exec(&mut mem)?;
+        expect_in_reg(&mut mem, Register::Z1, val);
+
+        // and programs may use it
+        let ops = assemble("loadi z1, 5\ncpy t0, z1\nloadi a2, SYS_EXIT\nsyscall\n").unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter())?;
+        exec(&mut mem)?;
+        expect_in_reg(&mut mem, Register::T0, TaggedCapability::from_ugran(5));
+
+        // but there's nothing beyond it
+        let past = TaggedCapability::from_ugran(Registers::COUNT.into());
+        let op = Op {
+            kind: OpKind::Cpy,
+            op1: TaggedCapability::from_ugran(Register::T0 as _),
+            op2: past,
+            op3: TaggedCapability::from_ugran(0),
+        };
+        assert!(matches!(
+            mem.execute_op(op, None, false),
+            Err(Exception::InvalidRegAccess { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn csubset() -> Result<(), Exception> {
+        let ops = assemble(
+            "csubset t0, s1, s0
+            csubset t1, s0, s1
+            csubset t2, s2, s1
+            csubset t3, s3, s0
+            csubset t4, s4, s1
+            csubset t5, s3, s3
+            loadi a2, SYS_EXIT
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        let outer = mem.root.set_bounds(Address(64), Address(128));
+        let inner = outer
+            .set_bounds(Address(80), Address(96))
+            .set_perms(Permissions::READ);
+        let regs = [
+            (Register::S0, outer),
+            (Register::S1, inner),
+            // disjoint from inner
+            (
+                Register::S2,
+                outer
+                    .set_bounds(Address(96), Address(128))
+                    .set_perms(Permissions::READ),
+            ),
+            // same authority, but untagged
+            (
+                Register::S3,
+                TaggedCapability::new(outer.capability(), false),
+            ),
+            // same bounds, more permissions
+            (Register::S4, outer.set_bounds(Address(80), Address(96))),
+        ];
+        for (reg, tcap) in regs {
+            mem.regs.write(&mut mem.tags, reg as _, tcap)?;
+        }
+        exec(&mut mem)?;
+
+        let subset = |reg: Register| mem.regs.read_ty::<UGran>(&mem.tags, reg as _);
+        assert_eq!(subset(Register::T0)?, 1);
+        assert_eq!(subset(Register::T1)?, 0);
+        assert_eq!(subset(Register::T2)?, 0);
+        assert_eq!(subset(Register::T3)?, 0);
+        assert_eq!(subset(Register::T4)?, 0);
+        // integers aren't capabilities, let alone subsets of each other
+        assert_eq!(subset(Register::T5)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn cclear() -> Result<(), Exception> {
+        let ops = assemble(
+            "cclear t0, s0
+            cgetvalid t1, t0
+            cgetvalid t2, s0
+            loadi a2, SYS_EXIT
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        let tcap = mem.root.set_bounds(Address(64), Address(128));
+        mem.regs.write(&mut mem.tags, Register::S0 as _, tcap)?;
+        exec(&mut mem)?;
+
+        expect_in_reg(
+            &mut mem,
+            Register::T0,
+            TaggedCapability::from_ugran(tcap.to_ugran()),
+        );
+        expect_in_reg(&mut mem, Register::T1, TaggedCapability::from_ugran(0));
+        // the source keeps its tag
+        expect_in_reg(&mut mem, Register::T2, TaggedCapability::from_ugran(1));
+        Ok(())
+    }
+
+    #[test]
+    fn alloc_free_bump() -> anyhow::Result<()> {
+        let ops = assemble(
+            "loadi a2, SYS_ALLOC_FREE
+            cpy a3, z0
+            cpy a4, s0
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter())?;
+        let root_alloc = mem.regs.read(&mem.tags, Register::Z0 as _)?;
+        let ation = alloc::alloc(root_alloc, u64::LAYOUT, &mut mem)?;
+        mem.regs.write(&mut mem.tags, Register::S0 as _, ation)?;
+        let stats = alloc::stat(root_alloc, &mem)?;
+
+        let Err(Exception::AllocErr { err }) = exec(&mut mem) else {
+            panic!("bump allocator can't free single allocations");
+        };
+        assert_eq!(err.kind, AllocErrKind::Unsupported);
+        assert_eq!(err.stats, stats);
+        Ok(())
+    }
+
+    #[test]
+    fn alloc_init_flags() {
+        let ops = assemble(
+            "loadi a2, SYS_ALLOC_INIT
+            loadi a3, 1
+            loadi a4, 132
+            cpy a5, z0
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        assert!(matches!(
+            exec(&mut mem),
+            Err(Exception::InvalidAllocInitFlags { flags: 0x84 })
+        ));
+    }
+
+    #[test]
+    fn use_after_free() -> anyhow::Result<()> {
+        let ops = assemble("loadu8 t0, s0\nloadi a2, SYS_EXIT\nsyscall\n").unwrap();
+        let mut mem = Memory::new(512, 0, ops.iter())?;
+        let ator = allocator(&mut mem, Strategy::Stack, InitFlags::empty(), 128)?;
+        let ation = alloc::alloc(ator, u64::LAYOUT, &mut mem)?;
+        mem.write(ation, 42_u64)?;
+        mem.regs.write(&mut mem.tags, Register::S0 as _, ation)?;
+        alloc::free(ator, ation, &mut mem)?;
+
+        let Err(Exception::UseAfterFree { addr }) = exec(&mut mem) else {
+            panic!("expected use after free");
+        };
+        assert_eq!(addr, ation.addr());
+        Ok(())
+    }
+
+    #[test]
+    fn fault_pc() -> Result<(), Exception> {
+        let ops = assemble(
+            "loadi t0, 5
+            store64 s0, t0
+            loadi a2, SYS_EXIT
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        let tcap = mem.root.set_bounds(Address(256), Address(264));
+        mem.regs.write(
+            &mut mem.tags,
+            Register::S0 as _,
+            tcap.set_addr(Address(264)),
+        )?;
+        let start = mem.regs.read(&mem.tags, Register::Pc as _)?.addr();
+
+        let Err(Exception::InvalidMemAccess { pc, .. }) = exec(&mut mem) else {
+            panic!("expected invalid memory access");
+        };
+        assert_eq!(pc, Some(start.add(Op::LAYOUT.size)));
+        Ok(())
+    }
+
+    #[test]
+    fn op_budget() -> Result<(), Exception> {
+        let ops = assemble("spin:\njal zero, spin\n").unwrap();
+        let mut mem = Memory::new(32, 0, ops.iter()).unwrap();
+        mem.set_op_budget(Some(3));
+        for _ in 0..3 {
+            mem.execute_next()?;
+        }
+        assert_eq!(mem.op_budget(), Some(0));
+        assert!(matches!(mem.execute_next(), Err(Exception::BudgetExceeded)));
+
+        // budget isn't exceeded if the process exits in time
+        let ops = assemble(EXIT).unwrap();
+        let mut mem = Memory::new(32, 0, ops.iter()).unwrap();
+        mem.set_op_budget(Some(ops.len() as u64));
+        exec(&mut mem)
+    }
+
+    #[test]
+    fn branch_skips() -> Result<(), Exception> {
+        let ops = assemble(
+            "loadi t0, 1
+            beq zero, zero, skip
+            loadi t0, 2
+            loadi t0, 3
+            skip:
+            addi t1, t0, 10
+            loadi a2, SYS_EXIT
+            syscall
+",
+        )
+        .unwrap();
+        let mut mem = Memory::new(32, 0, ops.iter()).unwrap();
+        exec(&mut mem)?;
+        assert_eq!(mem.regs.read_data(Register::T0 as _)?, 1);
+        assert_eq!(mem.regs.read_data(Register::T1 as _)?, 11);
+        assert_eq!(mem.op_histogram()[usize::from(OpKind::LoadI.to_byte())], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn trace_hook() -> Result<(), Exception> {
+        let ops = assemble(JMP_BACK).unwrap();
+        let mut mem = Memory::new(32, 0, ops.iter()).unwrap();
+        let base = mem.regs.read(&mem.tags, Register::Pc as _)?.addr();
+        let pcs = Rc::new(RefCell::new(Vec::new()));
+        let hook_pcs = Rc::clone(&pcs);
+        mem.set_trace_hook(Some(Box::new(move |pc, _op| {
+            hook_pcs.borrow_mut().push(pc);
+        })));
+        exec(&mut mem)?;
+        let expected: Vec<Address> = [0, 4, 1, 2, 3]
+            .into_iter()
+            .map(|idx| base.add(idx * Op::LAYOUT.size))
+            .collect();
+        assert_eq!(*pcs.borrow(), expected);
+        Ok(())
+    }
+
     #[test]
     fn add() {
         let ops = assemble(ADD).unwrap();
@@ -73,6 +698,17 @@ mod exec {
         expect_in_reg(&mut mem, Register::T0, TaggedCapability::from_ugran(71));
     }
 
+    #[test]
+    fn nop() {
+        let src = format!("loadi t0, 5\nnop\nnop\n{EXIT}");
+        let ops = assemble(&src).unwrap();
+        let mut mem = Memory::new(32, 0, ops.iter()).unwrap();
+        drop(ops);
+        exec(&mut mem).unwrap();
+        expect_in_reg(&mut mem, Register::T0, TaggedCapability::from_ugran(5));
+        expect_in_reg(&mut mem, Register::Zero, TaggedCapability::from_ugran(0));
+    }
+
     #[test]
     fn cmp() {
         let ops = assemble(CMP).unwrap();
@@ -93,6 +729,82 @@ mod exec {
         expect_in_reg(&mut mem, Register::T0, TaggedCapability::from_ugran(53));
     }
 
+    #[test]
+    fn store_data_over_cap() {
+        let ops = assemble(STORE_DATA_OVER_CAP).unwrap();
+        let mut mem = Memory::new(64, 64, ops.iter()).unwrap();
+        drop(ops);
+        exec(&mut mem).unwrap();
+        expect_in_reg(&mut mem, Register::T4, TaggedCapability::from_ugran(1));
+        expect_in_reg(&mut mem, Register::T3, TaggedCapability::from_ugran(0));
+    }
+
+    #[test]
+    fn shift_imm() {
+        let ops = assemble(SHIFT_IMM).unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        drop(ops);
+        exec(&mut mem).unwrap();
+        expect_in_reg(&mut mem, Register::T1, TaggedCapability::from_ugran(2));
+        expect_in_reg(&mut mem, Register::T2, TaggedCapability::from_ugran(16));
+        expect_in_reg(
+            &mut mem,
+            Register::T3,
+            TaggedCapability::from_ugran(-4i64 as _),
+        );
+    }
+
+    #[test]
+    fn shift_reg() {
+        let ops = assemble(SHIFT_REG).unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        drop(ops);
+        exec(&mut mem).unwrap();
+        expect_in_reg(&mut mem, Register::T1, TaggedCapability::from_ugran(3));
+        expect_in_reg(&mut mem, Register::T2, TaggedCapability::from_ugran(6));
+        expect_in_reg(&mut mem, Register::T3, TaggedCapability::from_ugran(3));
+        expect_in_reg(
+            &mut mem,
+            Register::T4,
+            TaggedCapability::from_ugran(-3i64 as _),
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let ops = assemble(OVERFLOW).unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        drop(ops);
+        match exec(&mut mem) {
+            Err(Exception::IntegerOverflow {
+                op: OpKind::AddOvf,
+                a: UGran::MAX,
+                b: 1,
+            }) => (),
+            res => panic!("expected integer overflow, got {res:?}"),
+        }
+        expect_in_reg(&mut mem, Register::T2, TaggedCapability::from_ugran(0));
+    }
+
+    #[test]
+    fn checked_arithmetic() -> Result<(), Exception> {
+        let ops = assemble("subovf t0, t1, t2\nmulovf t0, t1, t2\n").unwrap();
+        let mut mem = Memory::new(64, 0, ops.iter()).unwrap();
+        mem.regs.write_data(&mut mem.tags, Register::T1 as _, 6)?;
+        mem.regs.write_data(&mut mem.tags, Register::T2 as _, 7)?;
+        assert!(matches!(
+            mem.step(),
+            Err(Exception::IntegerOverflow {
+                op: OpKind::SubOvf,
+                a: 6,
+                b: 7,
+            })
+        ));
+        mem.step()?;
+        expect_in_reg(&mut mem, Register::T0, TaggedCapability::from_ugran(42));
+        Ok(())
+    }
+
     #[test]
     fn fibonacci_iter() -> Result<(), Exception> {
         let ops = assemble(FIB_ITER).unwrap();
@@ -113,6 +825,64 @@ mod exec {
         Ok(())
     }
 
+    #[test]
+    fn op_histogram() -> Result<(), Exception> {
+        let ops = assemble(FIB_ITER).unwrap();
+        let mut mem = Memory::new(1024, 1024, ops.iter()).unwrap();
+        mem.regs.write_data(&mut mem.tags, Register::A2 as _, 10)?;
+        exec(&mut mem)?;
+        let count = |kind: OpKind| mem.op_histogram()[usize::from(kind.to_byte())];
+        assert_eq!(count(OpKind::Bltu), 10);
+        assert_eq!(count(OpKind::Add), 9);
+        assert_eq!(count(OpKind::AddI), 9);
+        assert_eq!(count(OpKind::Cpy), 20);
+        assert_eq!(count(OpKind::Syscall), 1);
+        Ok(())
+    }
+
+    fn run_fib(src: &str, dispatch: Dispatch, range: core::ops::Range<UGran>) -> Duration {
+        let ops = assemble(src).unwrap();
+        let mut mem = Memory::new(1024, 1024, ops.iter()).unwrap();
+        mem.set_dispatch(dispatch);
+        let pc = mem.regs.read(&mem.tags, Register::Pc as _).unwrap();
+        let now = Instant::now();
+        for n in range {
+            mem.regs
+                .write(&mut mem.tags, Register::Pc as _, pc)
+                .unwrap(); // reset execution
+            mem.regs
+                .write_data(&mut mem.tags, Register::A2 as _, n)
+                .unwrap();
+            exec(&mut mem).unwrap();
+            expect_in_reg(
+                &mut mem,
+                Register::A0,
+                TaggedCapability::from_ugran(super::fib(n)),
+            );
+        }
+        now.elapsed()
+    }
+
+    #[test]
+    fn dispatch_table() {
+        run_fib(FIB_ITER, Dispatch::Table, 0..94);
+        run_fib(FIB_REC, Dispatch::Table, 0..10);
+    }
+
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_dispatch() {
+        const ROUNDS: u32 = 100;
+        for dispatch in [Dispatch::Match, Dispatch::Table] {
+            let mut total = Duration::ZERO;
+            for _ in 0..ROUNDS {
+                total += run_fib(FIB_ITER, dispatch, 0..94);
+                total += run_fib(FIB_REC, dispatch, 0..14);
+            }
+            println!("{dispatch:?}: {:?} per round", total / ROUNDS);
+        }
+    }
+
     #[test]
     fn fibonacci_recursive() -> Result<(), Exception> {
         let ops = assemble(FIB_REC).unwrap();
@@ -148,5 +918,193 @@ fn fib(n: UGran) -> UGran {
         f2 = f1;
         f1 = f;
     }
-    return f1;
+    f1
+}
+
+mod log {
+    use tracing_subscriber::prelude::*;
+
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use crate::LogFormat;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_lines() {
+        let buf = SharedBuf::default();
+        let writer = buf.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(crate::log_layer(LogFormat::Json, move || writer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("exec", pc = 8);
+            let _guard = span.enter();
+            tracing::info!(op_kind = "add", "executing");
+            tracing::debug!("done");
+        });
+
+        let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["fields"]["op_kind"], "add");
+        assert_eq!(lines[0]["span"]["pc"], 8);
+    }
+}
+
+mod repl {
+    use fruticose_vm::exception::Exception;
+    use fruticose_vm::mem::Memory;
+    use fruticose_vm::registers::Register;
+
+    use crate::repl;
+
+    #[test]
+    fn loadi() -> Result<(), Exception> {
+        let mut mem = Memory::new(32, 0, [].iter()).unwrap();
+        let mut out = Vec::new();
+        repl::run(&mut mem, "loadi t0, 5\n".as_bytes(), &mut out).unwrap();
+        assert_eq!(mem.regs.read_data(Register::T0 as _)?, 5);
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("t0: "));
+        assert_eq!(out.lines().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn exit() {
+        let mut mem = Memory::new(32, 0, [].iter()).unwrap();
+        let mut out = Vec::new();
+        let input = "loadi a2, SYS_EXIT\nsyscall\nloadi t0, 5\n";
+        repl::run(&mut mem, input.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with(":: process exited\n"));
+        assert!(!out.contains("t0"));
+    }
+}
+
+mod exit {
+    use fruticose_vm::exception::Exception;
+    use fruticose_vm::mem::Memory;
+    use fruticose_vm::registers::Register;
+
+    use crate::exit_code;
+
+    #[test]
+    fn codes() -> anyhow::Result<()> {
+        // not enough memory for the stack
+        let Err(alloc_err) = Memory::new(4, 1024, [].iter()) else {
+            panic!("allocation should fail");
+        };
+        assert!(alloc_err.downcast_ref::<Exception>().is_some());
+
+        // program counter points at garbage
+        let ops = crate::assemble_src(&fruticose_asm::source::SourceMap::anonymous(
+            "loadi a2, SYS_EXIT\nsyscall\n",
+        ))?;
+        let mut mem = Memory::new(64, 0, ops.iter())?;
+        let pc = mem.regs.read(&mem.tags, Register::Pc as _)?;
+        mem.write(mem.root.set_addr(pc.addr()), 0xff_u8)?;
+        let raised = mem.execute_next().unwrap_err();
+        assert!(matches!(raised, Exception::InvalidOpKind { byte: 0xff }));
+        let op_err = anyhow::Error::from(raised);
+
+        let (alloc_code, op_code) = (exit_code(&alloc_err), exit_code(&op_err));
+        assert_ne!(alloc_code, op_code);
+        assert!(alloc_code > 1 && op_code > 1);
+        assert_eq!(exit_code(&anyhow::anyhow!("not from the vm")), 1);
+        Ok(())
+    }
+}
+
+mod info {
+    use fruticose_vm::int::UGRAN_SIZE;
+
+    #[test]
+    fn granule_size() {
+        let mut out = Vec::new();
+        crate::print_info(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.contains(&format!("granule size:     {UGRAN_SIZE} bytes")),
+            "{out}"
+        );
+        assert!(out.contains("registers:"));
+    }
+}
+
+mod diag {
+    use fruticose_asm::source::SourceMap;
+    use fruticose_asm::Span;
+    use nu_ansi_term::Style;
+
+    use crate::Diagnostic;
+
+    fn render(map: &SourceMap, span: Span<'_>) -> String {
+        let loc = map.locate(span).unwrap();
+        let plain = Style::new();
+        let diag = Diagnostic::new(span, loc, plain, plain, plain, plain, plain);
+        let mut out = Vec::new();
+        diag.write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn two_lines() {
+        let map = SourceMap::anonymous("loadi t0, 5\nadd t0, t1, t2\n");
+        // from "5" through "add t0"
+        let span = Span {
+            line: 0,
+            col_idx: 10,
+            len: 8,
+            line_start: 0,
+            src: map.text(),
+        };
+        assert_eq!(span.get(), "5\nadd t0");
+        let out = render(&map, span);
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(
+            lines[1..],
+            [
+                "   |",
+                " 1 | loadi t0, 5",
+                "   |           ^",
+                " 2 | add t0, t1, t2",
+                "   | ^^^^^^",
+            ],
+            "{out}"
+        );
+        assert!(lines[0].ends_with(":1:11"), "{out}");
+    }
+
+    #[test]
+    fn line_numbers_align() {
+        let src = format!("{}syscall\nsyscall\n", "\n".repeat(8));
+        let map = SourceMap::anonymous(&src);
+        let span = Span {
+            line: 8,
+            col_idx: 0,
+            len: 15,
+            line_start: 8,
+            src: map.text(),
+        };
+        let out = render(&map, span);
+        assert!(out.contains("\n  9 | syscall\n"), "{out}");
+        assert!(out.contains("\n 10 | syscall\n"), "{out}");
+    }
 }